@@ -4,20 +4,61 @@ use askama::Template;
 use axum::response::{Html, IntoResponse, Response};
 use reqwest::StatusCode;
 
-use crate::cdrag::CDragon;
+use crate::{
+    cdrag::{CDragon, store::AssetStore},
+    config::Config,
+    db::Db,
+};
 
+pub mod admin;
+pub mod api;
+pub mod assets;
+pub mod caching;
 pub mod champions;
+pub mod collections;
+pub mod creations;
+pub mod derived;
+pub mod graphql;
+pub mod health;
+pub mod home;
+pub mod metrics;
+pub mod mount;
+pub mod posters;
+pub mod request_id;
+pub mod skins;
+pub mod slideshows;
+pub mod wallpapers;
+
+/// Where rendered images are cached in `data_dir`. They're made again when they're missing.
+pub const RENDER_CACHES: [&str; 3] = [
+    derived::DERIVED_DIR,
+    wallpapers::WALLPAPERS_DIR,
+    slideshows::SLIDESHOWS_DIR,
+];
 
 #[derive(Debug, Clone)]
 pub struct AppState {
     pub cdrag: Arc<CDragon>,
+    pub db: Arc<Db>,
+    pub config: Arc<Config>,
+    /// [`CDragon::dataset_version`], which doesn't change while serving
+    pub dataset_version: Arc<str>,
 }
 
 impl AppState {
-    pub async fn new() -> anyhow::Result<AppState> {
-        let cdrag = CDragon::new().await?;
+    pub async fn new(config: Config) -> anyhow::Result<AppState> {
+        let db = Arc::new(Db::open(&config.data_dir)?);
+        let mut cdrag = CDragon::open(&config).with_store(AssetStore::new(
+            db.clone(),
+            config.data_dir.clone(),
+            config.asset_quota,
+        ));
+        cdrag.sync(&config.prefetch).await?;
         Ok(AppState {
+            dataset_version: cdrag.dataset_version().into(),
             cdrag: Arc::new(cdrag),
+            db,
+            config: Arc::new(config),
         })
     }
 }
@@ -30,20 +71,40 @@ pub enum AppError {
     Anyhow(#[from] anyhow::Error),
     /// missing
     NotFound,
+    /// bad request: {0}
+    BadRequest(String),
+}
+
+impl AppError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::Render(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::Anyhow(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::NotFound => StatusCode::NOT_FOUND,
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+        }
+    }
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         #[derive(Debug, Template)]
         #[template(path = "error.html")]
-        struct Tmpl {}
+        struct Tmpl {
+            /// To quote when reporting the error, and to find it in the logs
+            request_id: Option<String>,
+        }
 
-        let status = match &self {
-            AppError::Render(_) => StatusCode::INTERNAL_SERVER_ERROR,
-            AppError::Anyhow(_) => StatusCode::INTERNAL_SERVER_ERROR,
-            AppError::NotFound => StatusCode::NOT_FOUND,
+        let status = self.status_code();
+        if status.is_server_error() {
+            match &self {
+                AppError::Anyhow(err) => tracing::error!("{err:#}"),
+                err => tracing::error!("{err}"),
+            }
+        }
+        let tmpl = Tmpl {
+            request_id: request_id::current(),
         };
-        let tmpl = Tmpl {};
         if let Ok(body) = tmpl.render() {
             (status, Html(body)).into_response()
         } else {
@@ -51,3 +112,115 @@ impl IntoResponse for AppError {
         }
     }
 }
+
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use super::*;
+    use crate::{
+        cdrag::{Champion, Rarity, Skin, SkinLine},
+        imaging::{focal::FocalPoint, palette::Color, phash::PerceptualHash},
+    };
+    use rstest::fixture;
+
+    /// A small offline dataset: Annie and Kai'Sa, each with a Bullet Angel skin
+    pub fn offline_cdrag() -> CDragon {
+        let kaisa = Champion {
+            id: 145,
+            name: "Kai'Sa".into(),
+            alias: "Kaisa".into(),
+            roles: vec!["marksman".into()],
+            skins: vec![
+                Skin {
+                    id: 145000,
+                    is_base: true,
+                    name: "Kai'Sa".into(),
+                    ..Default::default()
+                },
+                Skin {
+                    id: 145001,
+                    name: "Bullet Angel Kai'Sa".into(),
+                    uncentered_splash_path:
+                        "assets/characters/kaisa/skins/skin01/kaisa_splash_uncentered_1.jpg".into(),
+                    rarity: Rarity::KEpic,
+                    skin_lines: vec![7],
+                    palette: vec![Color([208, 208, 224]), Color([32, 64, 160])],
+                    focal_point: Some(FocalPoint { x: 0.6, y: 0.4 }),
+                    phash: Some(PerceptualHash(0x9c3e_61f0_0f1e_c387)),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let annie = Champion {
+            id: 1,
+            name: "Annie".into(),
+            alias: "Annie".into(),
+            roles: vec!["mage".into()],
+            skins: vec![
+                Skin {
+                    id: 1000,
+                    is_base: true,
+                    name: "Annie".into(),
+                    ..Default::default()
+                },
+                Skin {
+                    id: 1001,
+                    name: "Bullet Angel Annie".into(),
+                    uncentered_splash_path:
+                        "assets/characters/annie/skins/skin01/annie_splash_uncentered_1.jpg".into(),
+                    is_legacy: true,
+                    skin_lines: vec![7],
+                    palette: vec![Color([224, 32, 32])],
+                    // The same group splash as Kai'Sa's, cropped a little differently
+                    phash: Some(PerceptualHash(0x9c3e_61f0_0f1e_c38e)),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let mut cdrag = CDragon::default();
+        cdrag.set_champions([(kaisa.id, kaisa), (annie.id, annie)].into());
+        cdrag.skin_lines = [(
+            7,
+            SkinLine {
+                id: 7,
+                name: "Bullet Angel".into(),
+                description: String::new(),
+            },
+        )]
+        .into();
+        cdrag
+    }
+
+    #[fixture]
+    pub fn state() -> AppState {
+        let cdrag = offline_cdrag();
+        AppState {
+            dataset_version: cdrag.dataset_version().into(),
+            cdrag: Arc::new(cdrag),
+            db: Arc::new(Db::open_in_memory().unwrap()),
+            config: Arc::new(Config::default()),
+        }
+    }
+
+    /// Like [`state`], for handlers that write files. Keep the directory alive for the test.
+    #[fixture]
+    pub fn disk_state() -> (AppState, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(Db::open_in_memory().unwrap());
+        let data_dir = dir.path().to_path_buf();
+        let mut cdrag = offline_cdrag();
+        cdrag.data_dir = data_dir.clone();
+        let cdrag = cdrag.with_store(AssetStore::new(db.clone(), data_dir, None));
+        let state = AppState {
+            dataset_version: cdrag.dataset_version().into(),
+            cdrag: Arc::new(cdrag),
+            db,
+            config: Arc::new(Config {
+                data_dir: dir.path().to_path_buf(),
+                ..Default::default()
+            }),
+        };
+        (state, dir)
+    }
+}
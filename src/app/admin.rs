@@ -0,0 +1,108 @@
+use askama::Template;
+use axum::{
+    Router,
+    extract::State,
+    response::{Html, IntoResponse},
+    routing::get,
+};
+
+use crate::{
+    cdrag::store::{StoreUsage, format_bytes},
+    config::Config,
+};
+
+use super::{AppError, AppState};
+
+pub fn router<S>(state: AppState) -> Router<S> {
+    Router::new().route("/admin", get(admin)).with_state(state)
+}
+
+#[derive(Template)]
+#[template(path = "admin.html")]
+struct AdminTemplate {
+    /// Where the config was read from, or where it would be
+    config_path: String,
+    loaded: bool,
+    /// Each setting and its value as it would be written in TOML
+    settings: Vec<(String, String)>,
+    /// `None` when downloads aren't tracked
+    usage: Option<StoreUsage>,
+}
+
+impl AdminTemplate {
+    fn size(&self, bytes: &u64) -> String {
+        format_bytes(*bytes)
+    }
+}
+
+/// The settings the server is running with. Read-only: edit the config file and restart.
+async fn admin(State(state): State<AppState>) -> Result<impl IntoResponse, AppError> {
+    let config = &state.config;
+    let table: toml::Table = toml::from_str(&config.to_toml())
+        .map_err(|err| anyhow::anyhow!("the config doesn't round-trip through TOML: {err}"))?;
+    Ok(Html(
+        AdminTemplate {
+            config_path: config
+                .source
+                .clone()
+                .unwrap_or_else(Config::default_path)
+                .display()
+                .to_string(),
+            loaded: config.source.is_some(),
+            settings: table
+                .into_iter()
+                .map(|(key, value)| (key, value.to_string()))
+                .collect(),
+            usage: state.cdrag.store().map(|store| store.usage()).transpose()?,
+        }
+        .render()?,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::app::fixtures::{disk_state, state};
+    use axum::{body::Body, http::Request};
+    use reqwest::StatusCode;
+    use rstest::*;
+    use tower::ServiceExt;
+
+    #[rstest]
+    #[tokio::test]
+    async fn shows_the_running_config(state: AppState) {
+        let res = router::<()>(state)
+            .oneshot(Request::get("/admin").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = String::from_utf8_lossy(&body);
+        assert!(body.contains("cdragon_url"));
+        assert!(body.contains("https://raw.communitydragon.org"));
+        assert!(body.contains("uncentered_splash"));
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn shows_asset_usage(disk_state: (AppState, tempfile::TempDir)) {
+        let (state, _dir) = disk_state;
+        let path = std::path::Path::new("assets/icon.png");
+        std::fs::create_dir_all(state.cdrag.data_dir.join("assets")).unwrap();
+        std::fs::write(state.cdrag.data_dir.join(path), [0; 2048]).unwrap();
+        state.cdrag.store().unwrap().record(path, true).unwrap();
+
+        let res = router::<()>(state)
+            .oneshot(Request::get("/admin").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = String::from_utf8_lossy(&body);
+        assert!(body.contains("2.0 KB"), "{body}");
+        assert!(body.contains("no quota"), "{body}");
+    }
+}
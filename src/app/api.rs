@@ -0,0 +1,426 @@
+use axum::{
+    Json, Router,
+    extract::{Path, Query, State},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use serde::Serialize;
+use utoipa::{OpenApi, ToSchema};
+use utoipa_axum::{router::OpenApiRouter, routes};
+
+use crate::cdrag::{Champion, Plugin, Skin, SkinLine, Status};
+
+use super::champions::ChampionQuery;
+use super::skins::ColorQuery;
+use super::{AppError, AppState, caching, mount};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "fourth-shot", description = "Champion, skin and skin line data from CommunityDragon"),
+    tags(
+        (name = "champions", description = "Champions and their skins"),
+        (name = "skins", description = "Individual skins and skin lines"),
+        (name = "status", description = "State of the local CommunityDragon cache"),
+    )
+)]
+struct ApiDoc;
+
+fn v1_routes() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .routes(routes!(champions))
+        .routes(routes!(champion))
+        .routes(routes!(champion_skins))
+        .routes(routes!(skins_by_color))
+        .routes(routes!(skin))
+        .routes(routes!(skin_lines))
+        .routes(routes!(skin_line))
+        .routes(routes!(status))
+}
+
+fn api_routes() -> OpenApiRouter<AppState> {
+    OpenApiRouter::with_openapi(ApiDoc::openapi()).nest("/api/v1", v1_routes())
+}
+
+/// The OpenAPI document describing every route under `/api/v1`
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    api_routes().into_openapi()
+}
+
+/// The JSON API under `/api/v1`, plus its OpenAPI document at `/api/openapi.json`. The
+/// document names the host it was requested from, so unlike the API it isn't tagged with the
+/// dataset version.
+pub fn router<S>(state: AppState) -> Router<S> {
+    let (router, openapi) = api_routes().split_for_parts();
+    router
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            caching::revalidate,
+        ))
+        .route(
+            "/api/openapi.json",
+            get(move || async move { Json(with_server(openapi)) }),
+        )
+        .with_state(state)
+}
+
+/// Points the spec's server at the app as the client reached it, so tools that try requests
+/// send them to the same host and path prefix
+fn with_server(mut openapi: utoipa::openapi::OpenApi) -> utoipa::openapi::OpenApi {
+    let url = mount::absolute("");
+    if !url.is_empty() {
+        openapi.servers = Some(vec![utoipa::openapi::Server::new(url)]);
+    }
+    openapi
+}
+
+/// Wraps an [`AppError`] so it renders as a JSON body instead of the HTML error page
+#[derive(Debug)]
+pub struct ApiError(pub AppError);
+
+impl<E: Into<AppError>> From<E> for ApiError {
+    fn from(err: E) -> Self {
+        ApiError(err.into())
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorBody {
+    pub status: u16,
+    pub error: String,
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = self.0.status_code();
+        let body = ErrorBody {
+            status: status.as_u16(),
+            error: self.0.to_string(),
+        };
+        (status, Json(body)).into_response()
+    }
+}
+
+type ApiResult<T> = Result<Json<T>, ApiError>;
+
+#[utoipa::path(
+    get,
+    path = "/champions",
+    tag = "champions",
+    params(ChampionQuery),
+    responses((status = 200, description = "Champions matching the search, sorted", body = [Champion])),
+)]
+async fn champions(
+    State(state): State<AppState>,
+    Query(query): Query<ChampionQuery>,
+) -> ApiResult<Vec<Champion>> {
+    let champs = query
+        .apply(state.cdrag.champions.values())
+        .into_iter()
+        .cloned()
+        .collect();
+    Ok(Json(champs))
+}
+
+#[utoipa::path(
+    get,
+    path = "/champions/{key}",
+    tag = "champions",
+    params(("key" = String, Path, description = "Numeric champion id, name or alias")),
+    responses(
+        (status = 200, description = "The champion", body = Champion),
+        (status = 404, description = "No such champion", body = ErrorBody),
+    ),
+)]
+async fn champion(State(state): State<AppState>, Path(key): Path<String>) -> ApiResult<Champion> {
+    let champ = state
+        .cdrag
+        .champion_by_key(&key)
+        .ok_or(AppError::NotFound)?;
+    Ok(Json(champ.clone()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/champions/{key}/skins",
+    tag = "champions",
+    params(("key" = String, Path, description = "Numeric champion id, name or alias")),
+    responses(
+        (status = 200, description = "Every skin of the champion, including the base skin", body = [Skin]),
+        (status = 404, description = "No such champion", body = ErrorBody),
+    ),
+)]
+async fn champion_skins(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+) -> ApiResult<Vec<Skin>> {
+    let champ = state
+        .cdrag
+        .champion_by_key(&key)
+        .ok_or(AppError::NotFound)?;
+    Ok(Json(champ.skins.clone()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/skins",
+    tag = "skins",
+    params(ColorQuery),
+    responses(
+        (status = 200, description = "Skins with a palette color close to `color`, closest first", body = [Skin]),
+        (status = 400, description = "`color` isn't a #rrggbb color", body = ErrorBody),
+    ),
+)]
+async fn skins_by_color(
+    State(state): State<AppState>,
+    Query(query): Query<ColorQuery>,
+) -> ApiResult<Vec<Skin>> {
+    let skins = query.apply(&state)?.into_iter().cloned().collect();
+    Ok(Json(skins))
+}
+
+#[utoipa::path(
+    get,
+    path = "/skins/{id}",
+    tag = "skins",
+    params(("id" = u64, Path, description = "Skin id")),
+    responses(
+        (status = 200, description = "The skin", body = Skin),
+        (status = 404, description = "No such skin", body = ErrorBody),
+    ),
+)]
+async fn skin(State(state): State<AppState>, Path(id): Path<u64>) -> ApiResult<Skin> {
+    let skin = state.cdrag.skin_by_id(id).ok_or(AppError::NotFound)?;
+    Ok(Json(skin.clone()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/skin-lines",
+    tag = "skins",
+    responses((status = 200, description = "Every skin line, sorted by name", body = [SkinLine])),
+)]
+async fn skin_lines(State(state): State<AppState>) -> ApiResult<Vec<SkinLine>> {
+    let mut lines: Vec<SkinLine> = state.cdrag.skin_lines.values().cloned().collect();
+    lines.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(Json(lines))
+}
+
+/// A [`SkinLine`] along with every [`Skin`] that belongs to it
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SkinLineDetail {
+    #[serde(flatten)]
+    pub skin_line: SkinLine,
+    pub skins: Vec<Skin>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/skin-lines/{id}",
+    tag = "skins",
+    params(("id" = u64, Path, description = "Skin line id")),
+    responses(
+        (status = 200, description = "The skin line and its skins", body = SkinLineDetail),
+        (status = 404, description = "No such skin line", body = ErrorBody),
+    ),
+)]
+async fn skin_line(
+    State(state): State<AppState>,
+    Path(id): Path<u64>,
+) -> ApiResult<SkinLineDetail> {
+    let skin_line = state
+        .cdrag
+        .skin_line_by_id(id)
+        .ok_or(AppError::NotFound)?
+        .clone();
+    let skins = state.cdrag.skins_in_line(id).into_iter().cloned().collect();
+    Ok(Json(SkinLineDetail { skin_line, skins }))
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CacheStatus {
+    pub status: Status,
+    pub champions: usize,
+    pub skin_lines: usize,
+    pub plugins: Vec<Plugin>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/status",
+    tag = "status",
+    responses((status = 200, description = "Cache status and dataset sizes", body = CacheStatus)),
+)]
+async fn status(State(state): State<AppState>) -> ApiResult<CacheStatus> {
+    let cdrag = &state.cdrag;
+    Ok(Json(CacheStatus {
+        status: cdrag.current_status(),
+        champions: cdrag.champions.len(),
+        skin_lines: cdrag.skin_lines.len(),
+        plugins: cdrag.plugins.clone(),
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::app::fixtures::state;
+    use axum::{
+        body::Body,
+        http::{Request, header},
+    };
+    use reqwest::StatusCode;
+    use rstest::*;
+    use tower::ServiceExt;
+
+    async fn get(state: AppState, uri: &str) -> (StatusCode, serde_json::Value) {
+        let res = router(state)
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = res.status();
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn champions_sorted_desc(state: AppState) {
+        let (status, body) = get(state, "/api/v1/champions?sort_order=desc").await;
+        assert_eq!(status, StatusCode::OK);
+        let names: Vec<&str> = body
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|champ| champ["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["Kai'Sa", "Annie"]);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn champion_by_alias(state: AppState) {
+        let (status, body) = get(state, "/api/v1/champions/kaisa").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["id"], 145);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn skin_line_with_skins(state: AppState) {
+        let (status, body) = get(state, "/api/v1/skin-lines/7").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["name"], "Bullet Angel");
+        assert_eq!(body["skins"][1]["id"], 145001);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn missing_skin_is_json_404(state: AppState) {
+        let (status, body) = get(state, "/api/v1/skins/145999").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["status"], 404);
+        assert_eq!(body["error"], "missing");
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn only_the_api_is_tagged(state: AppState) {
+        for (uri, tagged) in [("/api/v1/status", true), ("/api/openapi.json", false)] {
+            let res = router::<()>(state.clone())
+                .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(res.headers().contains_key(header::ETAG), tagged, "{uri}");
+        }
+    }
+
+    /// The committed contract other teams build against. Regenerate it with
+    /// `UPDATE_OPENAPI=1 cargo test openapi` after an intentional API change.
+    const OPENAPI_SNAPSHOT: &str = "openapi.json";
+
+    #[test]
+    fn openapi_matches_snapshot() {
+        let generated = openapi().to_pretty_json().unwrap() + "\n";
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(OPENAPI_SNAPSHOT);
+        if std::env::var_os("UPDATE_OPENAPI").is_some() {
+            std::fs::write(&path, &generated).unwrap();
+        }
+        let committed = std::fs::read_to_string(&path).unwrap_or_default();
+        assert!(
+            committed == generated,
+            "{OPENAPI_SNAPSHOT} is out of date, rerun with UPDATE_OPENAPI=1 to regenerate it"
+        );
+    }
+
+    #[rstest]
+    #[case("/api/v1/champions", 200)]
+    #[case("/api/v1/champions/1", 200)]
+    #[case("/api/v1/champions/nobody", 404)]
+    #[case("/api/v1/champions/kaisa/skins", 200)]
+    #[case("/api/v1/skins?color=%23ff0000", 200)]
+    #[case("/api/v1/skins?color=red", 400)]
+    #[case("/api/v1/skins/145001", 200)]
+    #[case("/api/v1/skins/1", 404)]
+    #[case("/api/v1/skin-lines", 200)]
+    #[case("/api/v1/skin-lines/7", 200)]
+    #[case("/api/v1/skin-lines/8", 404)]
+    #[case("/api/v1/status", 200)]
+    #[tokio::test]
+    async fn handlers_return_documented_statuses(
+        state: AppState,
+        #[case] uri: &str,
+        #[case] expected: u16,
+    ) {
+        let (status, _) = get(state, uri).await;
+        assert_eq!(status.as_u16(), expected);
+
+        let spec = serde_json::to_value(openapi()).unwrap();
+        let documented = spec["paths"]
+            .as_object()
+            .unwrap()
+            .iter()
+            .find(|(template, _)| path_matches(template, uri.split('?').next().unwrap()))
+            .unwrap_or_else(|| panic!("{uri} is served but not documented"));
+        let responses = &documented.1["get"]["responses"];
+        assert!(
+            responses.get(expected.to_string()).is_some(),
+            "{uri} returned {expected}, which {} doesn't document",
+            documented.0
+        );
+    }
+
+    #[test]
+    fn every_documented_path_is_covered() {
+        let spec = serde_json::to_value(openapi()).unwrap();
+        let covered = [
+            "/api/v1/champions",
+            "/api/v1/champions/{key}",
+            "/api/v1/champions/{key}/skins",
+            "/api/v1/skins",
+            "/api/v1/skins/{id}",
+            "/api/v1/skin-lines",
+            "/api/v1/skin-lines/{id}",
+            "/api/v1/status",
+        ];
+        for path in spec["paths"].as_object().unwrap().keys() {
+            assert!(
+                covered.contains(&path.as_str()),
+                "{path} has no case in handlers_return_documented_statuses"
+            );
+        }
+    }
+
+    fn path_matches(template: &str, uri: &str) -> bool {
+        let template: Vec<&str> = template.split('/').collect();
+        let uri: Vec<&str> = uri.split('/').collect();
+        template.len() == uri.len()
+            && template
+                .iter()
+                .zip(uri)
+                .all(|(t, u)| t.starts_with('{') || *t == u)
+    }
+}
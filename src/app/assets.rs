@@ -0,0 +1,118 @@
+use std::path::Path;
+
+use axum::{
+    Router,
+    extract::{Request, State},
+    http::{HeaderValue, header},
+    middleware::{self, Next},
+    response::Response,
+};
+use reqwest::StatusCode;
+use tower_http::services::ServeDir;
+
+use crate::metrics::{Lookup, LookupLabels, METRICS};
+
+use super::{AppState, caching};
+
+/// Where downloaded assets live in `data_dir`, and so their paths start
+const ASSETS_DIR: &str = "assets";
+
+/// Serves the downloaded CDragon assets at `/cdrag-assets`, recording each use in the asset
+/// store so the quota evicts what's least used. Only `data_dir/assets` is served: the
+/// database and users' creations sit beside it.
+pub fn router<S>(state: AppState) -> Router<S> {
+    let files = Router::new()
+        .fallback_service(ServeDir::new(state.cdrag.data_dir.join(ASSETS_DIR)))
+        .layer(middleware::from_fn_with_state(state.clone(), record_use));
+    Router::new()
+        .nest(&format!("/cdrag-assets/{ASSETS_DIR}"), files)
+        .with_state(state)
+}
+
+async fn record_use(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    // Nesting strips `/cdrag-assets/assets`, leaving the rest of the Normalized Path
+    let asset_path = format!("{ASSETS_DIR}{}", request.uri().path());
+    let mut response = next.run(request).await;
+    let status = response.status();
+    if status.is_success() {
+        state.cdrag.touch_asset(Path::new(&asset_path));
+        response.headers_mut().insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static(caching::ASSET_CACHE),
+        );
+    }
+    let lookup = match status {
+        _ if status.is_success() || status == StatusCode::NOT_MODIFIED => Some(Lookup::Hit),
+        StatusCode::NOT_FOUND => Some(Lookup::Miss),
+        _ => None,
+    };
+    if let Some(result) = lookup {
+        METRICS
+            .asset_lookups
+            .get_or_create(&LookupLabels { result })
+            .inc();
+    }
+    response
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::app::fixtures::disk_state;
+    use axum::{body::Body, http::Request};
+    use reqwest::StatusCode;
+    use rstest::*;
+    use std::fs;
+    use tower::ServiceExt;
+
+    #[rstest]
+    #[tokio::test]
+    async fn serving_an_asset_marks_it_used(disk_state: (AppState, tempfile::TempDir)) {
+        let (state, _dir) = disk_state;
+        let store = state.cdrag.store().unwrap();
+        for name in ["a.jpg", "b.jpg"] {
+            let path = Path::new("assets").join(name);
+            fs::create_dir_all(state.cdrag.data_dir.join("assets")).unwrap();
+            fs::write(state.cdrag.data_dir.join(&path), b"jpeg").unwrap();
+            store.record(&path, false).unwrap();
+            tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+        }
+
+        let res = router::<()>(state.clone())
+            .oneshot(
+                Request::get("/cdrag-assets/assets/a.jpg")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let order: Vec<String> = state
+            .db
+            .evictable_assets()
+            .unwrap()
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(order, ["assets/b.jpg", "assets/a.jpg"]);
+    }
+
+    #[rstest]
+    #[case("/cdrag-assets/fourth-shot.db")]
+    #[case("/cdrag-assets/creations/1.png")]
+    #[case("/cdrag-assets/assets/../fourth-shot.db")]
+    #[tokio::test]
+    async fn only_assets_are_served(disk_state: (AppState, tempfile::TempDir), #[case] uri: &str) {
+        let (state, _dir) = disk_state;
+        let data_dir = &state.cdrag.data_dir;
+        fs::create_dir_all(data_dir.join("creations")).unwrap();
+        fs::write(data_dir.join("fourth-shot.db"), b"SQLite format 3").unwrap();
+        fs::write(data_dir.join("creations/1.png"), b"png").unwrap();
+
+        let res = router::<()>(state.clone())
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND, "{uri}");
+    }
+}
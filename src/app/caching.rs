@@ -0,0 +1,194 @@
+use std::{
+    collections::HashMap,
+    fs, io,
+    path::Path,
+    sync::{
+        LazyLock,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderValue, Method, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use reqwest::StatusCode;
+use sha2::{Digest, Sha256};
+
+use super::{AppState, mount};
+
+/// Where the stylesheets, scripts and fonts served at `/assets` live
+const STATIC_DIR: &str = "assets";
+/// For URLs whose content never changes
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+/// For downloaded assets and their derivatives. CommunityDragon keeps an asset's path across
+/// patches and only rarely changes what's at it.
+pub const ASSET_CACHE: &str = "public, max-age=604800";
+/// Storable, but checked with the server before every use
+const REVALIDATE: &str = "no-cache";
+
+/// Short content hashes of the files in [`STATIC_DIR`], read once
+static STATIC_VERSIONS: LazyLock<HashMap<String, String>> = LazyLock::new(|| {
+    let Ok(entries) = fs::read_dir(STATIC_DIR) else {
+        return HashMap::new();
+    };
+    entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let bytes = fs::read(&path).ok()?;
+            let name = path.file_name()?.to_str()?.to_string();
+            let hash = format!("{:x}", Sha256::digest(bytes));
+            Some((name, hash[..12].to_string()))
+        })
+        .collect()
+});
+
+/// The URL of a file in [`STATIC_DIR`] with its content hash, which browsers can keep until
+/// the file changes, e.g. `{{ crate::app::caching::versioned("main.css") }}` in a template
+pub fn versioned(name: &str) -> String {
+    match STATIC_VERSIONS.get(name) {
+        Some(version) => mount::path(&format!("/{STATIC_DIR}/{name}?v={version}")),
+        None => mount::path(&format!("/{STATIC_DIR}/{name}")),
+    }
+}
+
+/// Lets browsers keep `/assets` files requested through [`versioned`] URLs forever, and makes
+/// them check back for anything else
+pub async fn static_assets(request: Request, next: Next) -> Response {
+    let name = request.uri().path().trim_start_matches('/').to_string();
+    let current = request
+        .uri()
+        .query()
+        .and_then(|query| query.strip_prefix("v="))
+        .is_some_and(|version| STATIC_VERSIONS.get(&name).is_some_and(|v| v == version));
+    let mut response = next.run(request).await;
+    if response.status().is_success() {
+        let policy = if current { IMMUTABLE } else { REVALIDATE };
+        response
+            .headers_mut()
+            .insert(header::CACHE_CONTROL, HeaderValue::from_static(policy));
+    }
+    response
+}
+
+/// Tags pages rendered only from the CDragon data with the [dataset
+/// version](AppState::dataset_version), so browsers can revalidate them for a 304 instead of
+/// downloading them again. Pages that also show the database mustn't use it.
+pub async fn revalidate(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if !matches!(*request.method(), Method::GET | Method::HEAD) {
+        return next.run(request).await;
+    }
+    let opaque = format!(
+        "\"{}-{}\"",
+        env!("CARGO_PKG_VERSION"),
+        state.dataset_version
+    );
+    // Weak, since compression changes the bytes but not the page
+    let Ok(etag) = HeaderValue::from_str(&format!("W/{opaque}")) else {
+        return next.run(request).await;
+    };
+    let matches = request
+        .headers()
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == opaque);
+    let mut response = if matches {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        next.run(request).await
+    };
+    if response.status().is_success() || response.status() == StatusCode::NOT_MODIFIED {
+        let headers = response.headers_mut();
+        headers.insert(header::ETAG, etag);
+        headers.insert(header::CACHE_CONTROL, HeaderValue::from_static(REVALIDATE));
+    }
+    response
+}
+
+/// Writes a rendered file into its cache beside `path`, then swaps it in, so a request racing
+/// the write, or a crash partway through, never finds half a file there. Every write gets its
+/// own temporary name, since two requests can render the same file at once.
+pub fn write_cached(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    static WRITES: AtomicU64 = AtomicU64::new(0);
+    let mut name = std::ffi::OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(format!(
+        ".{}.{}.writing",
+        std::process::id(),
+        WRITES.fetch_add(1, Ordering::Relaxed)
+    ));
+    let temp = path.with_file_name(name);
+    let written = fs::write(&temp, bytes).and_then(|()| fs::rename(&temp, path));
+    if written.is_err() {
+        fs::remove_file(&temp).ok();
+    }
+    written
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::app::{fixtures::state, skins};
+    use axum::{body::Body, middleware};
+    use rstest::*;
+    use tower::ServiceExt;
+
+    #[rstest]
+    #[tokio::test]
+    async fn unchanged_pages_are_not_modified(state: AppState) {
+        let app = skins::router::<()>(state.clone())
+            .layer(middleware::from_fn_with_state(state, revalidate));
+        let res = app
+            .clone()
+            .oneshot(Request::get("/skin-lines").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let etag = res.headers()[header::ETAG].clone();
+        assert!(etag.to_str().unwrap().starts_with("W/\""), "{etag:?}");
+
+        for (if_none_match, expected) in [
+            (etag.to_str().unwrap(), StatusCode::NOT_MODIFIED),
+            ("W/\"0.0.0-stale\"", StatusCode::OK),
+        ] {
+            let res = app
+                .clone()
+                .oneshot(
+                    Request::get("/skin-lines")
+                        .header(header::IF_NONE_MATCH, if_none_match)
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(res.status(), expected, "{if_none_match}");
+            assert_eq!(res.headers()[header::ETAG], etag);
+        }
+    }
+
+    #[test]
+    fn cached_files_are_swapped_in_whole() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("1280x720.jpg");
+        fs::write(&path, b"old").unwrap();
+        write_cached(&path, b"new").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"new");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+        assert!(write_cached(&dir.path().join("missing/1.jpg"), b"new").is_err());
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn static_files_are_versioned_by_content() {
+        let url = versioned("main.css");
+        let (path, version) = url.split_once("?v=").unwrap();
+        assert_eq!(path, "/assets/main.css");
+        assert_eq!(version.len(), 12);
+        assert_eq!(versioned("missing.css"), "/assets/missing.css");
+    }
+}
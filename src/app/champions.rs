@@ -1,69 +1,127 @@
-use crate::cdrag::Skin;
-use anyhow::anyhow;
-use std::collections::HashMap;
+use crate::cdrag::{Champion, Skin};
+use crate::db::collections::{Collection, ItemKind};
+use serde::Deserialize;
+use std::collections::HashSet;
+use utoipa::IntoParams;
 
 use askama::Template;
 use axum::{
     Router,
     extract::{Path, Query, State},
-    response::{Html, IntoResponse},
+    http::Uri,
+    response::{Html, IntoResponse, Redirect, Response},
     routing::get,
 };
 
 use super::AppError;
-use super::AppState;
+use super::{AppState, mount};
 
 pub fn router<S>(state: AppState) -> Router<S> {
     Router::new()
         .route("/champions", get(champions_grid))
-        .route("/champions/{id}", get(champion_detail))
+        .route("/champions/{key}", get(champion_detail))
         .with_state(state)
 }
 
+/// The filter and sort parameters shared by the champion grid and the JSON API
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ChampionQuery {
+    /// Case-insensitive substring of the champion's name
+    pub search_term: Option<String>,
+    /// Only `name` is supported
+    pub sort_by: Option<String>,
+    /// `asc` or `desc`
+    pub sort_order: Option<String>,
+}
+
+impl ChampionQuery {
+    /// Returns the champions whose name contains the search term, sorted by the requested order
+    pub fn apply<'a>(&self, champions: impl Iterator<Item = &'a Champion>) -> Vec<&'a Champion> {
+        let search_term = self.search_term.as_deref().unwrap_or("").to_lowercase();
+        let mut champs: Vec<&Champion> = champions
+            .filter(|ch| ch.name.to_lowercase().contains(&search_term))
+            .collect();
+
+        let sort_by = self.sort_by.as_deref().unwrap_or("name");
+        let sort_order = self.sort_order.as_deref().unwrap_or("asc");
+
+        match (sort_by, sort_order) {
+            ("name", "asc") => champs.sort_by(|a, b| a.name.cmp(&b.name)),
+            ("name", "desc") => champs.sort_by(|a, b| b.name.cmp(&a.name)),
+            _ => champs.sort_by(|a, b| a.name.cmp(&b.name)), // Default sort
+        }
+        champs
+    }
+}
+
+/// Which collection's items to highlight. Defaults to the user's favorites.
+#[derive(Debug, Default, Deserialize)]
+struct CollectionSelection {
+    collection_id: Option<i64>,
+}
+
+impl CollectionSelection {
+    fn collection(&self, state: &AppState) -> Result<Collection, AppError> {
+        match self.collection_id {
+            Some(id) => state.db.collection(id)?.ok_or(AppError::NotFound),
+            None => Ok(state.db.default_collection()?),
+        }
+    }
+
+    /// The collection along with the ids of its items of the given kind
+    fn selected(
+        &self,
+        state: &AppState,
+        kind: ItemKind,
+    ) -> Result<(Collection, HashSet<u64>), AppError> {
+        let collection = self.collection(state)?;
+        let ids = state
+            .db
+            .collection_items(collection.id)?
+            .into_iter()
+            .filter(|item| item.kind == kind)
+            .map(|item| item.item_id)
+            .collect();
+        Ok((collection, ids))
+    }
+}
+
 struct ChampionGridItem {
     id: u64,
+    slug: String,
     name: String,
     icon_url: String,
+    selected: bool,
 }
 
 #[derive(Template)]
 #[template(path = "champions_grid.html")]
 struct ChampionsGridTemplate {
+    collection_id: i64,
     champions: Vec<ChampionGridItem>,
 }
 
 async fn champions_grid(
     State(state): State<AppState>,
-    Query(search_params): Query<HashMap<String, String>>,
+    Query(query): Query<ChampionQuery>,
+    Query(selection): Query<CollectionSelection>,
 ) -> Result<impl IntoResponse, AppError> {
-    let search_term = search_params
-        .get("search_term")
-        .map_or("", |s| s.as_str())
-        .to_lowercase();
-    let mut champ_objs: Vec<ChampionGridItem> = state
-        .cdrag
-        .champions
-        .values()
-        .filter(|ch| ch.name.to_lowercase().contains(&search_term))
+    let (collection, selected) = selection.selected(&state, ItemKind::Champion)?;
+    let champ_objs: Vec<ChampionGridItem> = query
+        .apply(state.cdrag.champions.values())
+        .into_iter()
         .map(|ch| ChampionGridItem {
             id: ch.id,
+            slug: ch.slug(),
             name: ch.name.clone(),
             icon_url: ch.square_portrait_path.clone(),
+            selected: selected.contains(&ch.id),
         })
         .collect();
 
-    let sort_by = search_params.get("sort_by").map_or("name", |s| s.as_str());
-    let sort_order = search_params
-        .get("sort_order")
-        .map_or("asc", |s| s.as_str());
-
-    match (sort_by, sort_order) {
-        ("name", "asc") => champ_objs.sort_by(|a, b| a.name.cmp(&b.name)),
-        ("name", "desc") => champ_objs.sort_by(|a, b| b.name.cmp(&a.name)),
-        _ => champ_objs.sort_by(|a, b| a.name.cmp(&b.name)), // Default sort
-    }
-
     let template = ChampionsGridTemplate {
+        collection_id: collection.id,
         champions: champ_objs,
     };
 
@@ -92,31 +150,85 @@ impl ChampionDetail {
 #[template(path = "champion_detail.html")]
 struct ChampionDetailTemplate {
     champion: ChampionDetail,
+    collection: Collection,
+    selected_skins: HashSet<u64>,
 }
 
+/// Renders a champion's page. Any key other than the champion's slug (its id, alias or a
+/// differently cased name) redirects to the canonical `/champions/{slug}` URL, keeping the
+/// query.
 async fn champion_detail(
     State(state): State<AppState>,
-    Path(champion_id): Path<u64>,
-) -> Result<impl IntoResponse, AppError> {
-    let champion = state.cdrag.champion_by_id(champion_id);
-    match champion {
-        None => Err(AppError::NotFound),
-        Some(champ) => {
-            
-
-            let skins = champ.skins.clone();
-            return Ok(Html(
-                ChampionDetailTemplate {
-                    champion: ChampionDetail {
-                        id: champ.id,
-                        name: champ.name.clone(),
-                        title: champ.title.clone(),
-                        short_bio: champ.short_bio.clone(),
-                        skins,
-                    },
-                }
-                .render()?,
-            ));
+    Path(key): Path<String>,
+    Query(selection): Query<CollectionSelection>,
+    uri: Uri,
+) -> Result<Response, AppError> {
+    let champ = state
+        .cdrag
+        .champion_by_key(&key)
+        .ok_or(AppError::NotFound)?;
+    let slug = champ.slug();
+    if key != slug {
+        let query = uri
+            .query()
+            .map(|query| format!("?{query}"))
+            .unwrap_or_default();
+        let canonical = mount::absolute(&format!("/champions/{slug}{query}"));
+        return Ok(Redirect::permanent(&canonical).into_response());
+    }
+    let (collection, selected_skins) = selection.selected(&state, ItemKind::Skin)?;
+    Ok(Html(
+        ChampionDetailTemplate {
+            champion: ChampionDetail {
+                id: champ.id,
+                name: champ.name.clone(),
+                title: champ.title.clone(),
+                short_bio: champ.short_bio.clone(),
+                skins: champ.skins.clone(),
+            },
+            collection,
+            selected_skins,
         }
+        .render()?,
+    )
+    .into_response())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::app::fixtures::state;
+    use axum::{body::Body, http::Request};
+    use reqwest::{StatusCode, header::LOCATION};
+    use rstest::*;
+    use tower::ServiceExt;
+
+    #[rstest]
+    #[case("/champions/145", "/champions/kaisa")]
+    #[case("/champions/Kai'Sa", "/champions/kaisa")]
+    #[case("/champions/KAISA", "/champions/kaisa")]
+    #[case("/champions/145?collection_id=3", "/champions/kaisa?collection_id=3")]
+    #[tokio::test]
+    async fn redirects_to_slug(state: AppState, #[case] uri: &str, #[case] expected: &str) {
+        let res = router::<()>(state)
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::PERMANENT_REDIRECT);
+        assert_eq!(res.headers()[LOCATION], expected);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn unknown_champion(state: AppState) {
+        let res = router::<()>(state)
+            .oneshot(
+                Request::get("/champions/teemo")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
     }
 }
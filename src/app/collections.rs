@@ -0,0 +1,384 @@
+use askama::Template;
+use axum::{
+    Form, Router,
+    extract::{Path, Query, State},
+    http::HeaderMap,
+    response::{Html, IntoResponse, Response},
+    routing::{delete, get, post},
+};
+use serde::Deserialize;
+
+use crate::db::collections::{Collection, CollectionItem, ItemKind};
+
+use super::{AppError, AppState, mount};
+
+pub fn router<S>(state: AppState) -> Router<S> {
+    Router::new()
+        .route(
+            "/collections",
+            get(collections_page).post(create_collection),
+        )
+        .route("/collections/picker", get(collection_picker))
+        .route(
+            "/collections/{id}",
+            get(collection_page).delete(delete_collection),
+        )
+        .route("/collections/{id}/rename", post(rename_collection))
+        .route(
+            "/collections/{id}/items",
+            post(add_item).delete(clear_items),
+        )
+        .route("/collections/{id}/items/toggle", post(toggle_item))
+        .route("/collections/{id}/items/move", post(move_item))
+        .route(
+            "/collections/{id}/items/{kind}/{item_id}",
+            delete(remove_item),
+        )
+        .with_state(state)
+}
+
+/// An item in a collection, resolved against the loaded CDragon data for display
+struct CollectionEntry {
+    kind: ItemKind,
+    item_id: u64,
+    name: String,
+    image_url: String,
+    href: String,
+}
+
+fn entries(state: &AppState, items: Vec<CollectionItem>) -> Vec<CollectionEntry> {
+    items
+        .into_iter()
+        .filter_map(|item| match item.kind {
+            ItemKind::Champion => {
+                let champ = state.cdrag.champion_by_id(item.item_id)?;
+                Some(CollectionEntry {
+                    kind: item.kind,
+                    item_id: item.item_id,
+                    name: champ.name.clone(),
+                    image_url: champ.square_portrait_path.clone(),
+                    href: mount::path(&format!("/champions/{}", champ.slug())),
+                })
+            }
+            ItemKind::Skin => {
+                let skin = state.cdrag.skin_by_id(item.item_id)?;
+                let champ = state.cdrag.champion_by_id(item.item_id / 1000)?;
+                Some(CollectionEntry {
+                    kind: item.kind,
+                    item_id: item.item_id,
+                    name: skin.name.clone(),
+                    image_url: skin.tile_path.clone(),
+                    href: mount::path(&format!("/champions/{}", champ.slug())),
+                })
+            }
+        })
+        .collect()
+}
+
+fn find_collection(state: &AppState, id: i64) -> Result<Collection, AppError> {
+    state.db.collection(id)?.ok_or(AppError::NotFound)
+}
+
+fn validate_name(name: &str) -> Result<&str, AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::BadRequest(
+            "collection names can't be blank".into(),
+        ));
+    }
+    Ok(name)
+}
+
+#[derive(Template)]
+#[template(path = "collections.html")]
+struct CollectionsTemplate {
+    collections: Vec<Collection>,
+}
+
+#[derive(Template)]
+#[template(path = "collection_list.html")]
+struct CollectionListTemplate {
+    collections: Vec<Collection>,
+}
+
+async fn collections_page(State(state): State<AppState>) -> Result<impl IntoResponse, AppError> {
+    state.db.default_collection()?;
+    Ok(Html(
+        CollectionsTemplate {
+            collections: state.db.collections()?,
+        }
+        .render()?,
+    ))
+}
+
+#[derive(Deserialize)]
+struct NameForm {
+    name: String,
+}
+
+async fn create_collection(
+    State(state): State<AppState>,
+    Form(form): Form<NameForm>,
+) -> Result<impl IntoResponse, AppError> {
+    state.db.create_collection(validate_name(&form.name)?)?;
+    Ok(Html(
+        CollectionListTemplate {
+            collections: state.db.collections()?,
+        }
+        .render()?,
+    ))
+}
+
+#[derive(Deserialize)]
+struct PickerQuery {
+    collection_id: Option<i64>,
+}
+
+#[derive(Template)]
+#[template(path = "collection_picker.html")]
+struct CollectionPickerTemplate {
+    collections: Vec<Collection>,
+    selected: i64,
+}
+
+/// The `<select name="collection_id">` used by pages that add things to a collection
+async fn collection_picker(
+    State(state): State<AppState>,
+    Query(query): Query<PickerQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let selected = match query.collection_id {
+        Some(id) => id,
+        None => state.db.default_collection()?.id,
+    };
+    Ok(Html(
+        CollectionPickerTemplate {
+            collections: state.db.collections()?,
+            selected,
+        }
+        .render()?,
+    ))
+}
+
+#[derive(Template)]
+#[template(path = "collection_detail.html")]
+struct CollectionDetailTemplate {
+    collection: Collection,
+    entries: Vec<CollectionEntry>,
+}
+
+async fn collection_page(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    let collection = find_collection(&state, id)?;
+    let entries = entries(&state, state.db.collection_items(id)?);
+    Ok(Html(
+        CollectionDetailTemplate {
+            collection,
+            entries,
+        }
+        .render()?,
+    ))
+}
+
+async fn rename_collection(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Form(form): Form<NameForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let name = validate_name(&form.name)?;
+    if !state.db.rename_collection(id, name)? {
+        return Err(AppError::NotFound);
+    }
+    Ok(Html(CollectionNameTemplate { name }.render()?))
+}
+
+#[derive(Template)]
+#[template(source = "{{ name }}", ext = "html")]
+struct CollectionNameTemplate<'a> {
+    name: &'a str,
+}
+
+/// Deletes the collection and sends htmx back to the collection list
+async fn delete_collection(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    if !state.db.delete_collection(id)? {
+        return Err(AppError::NotFound);
+    }
+    let mut headers = HeaderMap::new();
+    headers.insert("HX-Redirect", mount::path("/collections").parse().unwrap());
+    Ok(headers)
+}
+
+#[derive(Template)]
+#[template(path = "collection_items.html")]
+struct CollectionItemsTemplate {
+    collection: Collection,
+    entries: Vec<CollectionEntry>,
+}
+
+fn render_items(state: &AppState, collection: Collection) -> Result<Response, AppError> {
+    let entries = entries(state, state.db.collection_items(collection.id)?);
+    Ok(Html(
+        CollectionItemsTemplate {
+            collection,
+            entries,
+        }
+        .render()?,
+    )
+    .into_response())
+}
+
+#[derive(Deserialize)]
+struct ItemForm {
+    kind: ItemKind,
+    item_id: u64,
+}
+
+impl ItemForm {
+    fn check_exists(&self, state: &AppState) -> Result<(), AppError> {
+        let exists = match self.kind {
+            ItemKind::Champion => state.cdrag.champion_by_id(self.item_id).is_some(),
+            ItemKind::Skin => state.cdrag.skin_by_id(self.item_id).is_some(),
+        };
+        exists.then_some(()).ok_or(AppError::NotFound)
+    }
+}
+
+async fn add_item(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Form(form): Form<ItemForm>,
+) -> Result<Response, AppError> {
+    let collection = find_collection(&state, id)?;
+    form.check_exists(&state)?;
+    state.db.add_item(id, form.kind, form.item_id)?;
+    render_items(&state, collection)
+}
+
+async fn remove_item(
+    State(state): State<AppState>,
+    Path((id, kind, item_id)): Path<(i64, ItemKind, u64)>,
+) -> Result<Response, AppError> {
+    let collection = find_collection(&state, id)?;
+    state.db.remove_item(id, kind, item_id)?;
+    render_items(&state, collection)
+}
+
+async fn clear_items(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Response, AppError> {
+    let collection = find_collection(&state, id)?;
+    state.db.clear_items(id)?;
+    render_items(&state, collection)
+}
+
+#[derive(Deserialize)]
+struct MoveForm {
+    kind: ItemKind,
+    item_id: u64,
+    to: usize,
+}
+
+async fn move_item(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Form(form): Form<MoveForm>,
+) -> Result<Response, AppError> {
+    let collection = find_collection(&state, id)?;
+    state.db.move_item(id, form.kind, form.item_id, form.to)?;
+    render_items(&state, collection)
+}
+
+#[derive(Template)]
+#[template(path = "collection_toggle.html")]
+pub struct CollectionToggleTemplate {
+    pub collection_id: i64,
+    pub kind: ItemKind,
+    pub item_id: u64,
+    pub selected: bool,
+}
+
+/// Flips whether an item is in the collection, responding with the updated toggle button
+async fn toggle_item(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Form(form): Form<ItemForm>,
+) -> Result<impl IntoResponse, AppError> {
+    find_collection(&state, id)?;
+    form.check_exists(&state)?;
+    let selected = state.db.toggle_item(id, form.kind, form.item_id)?;
+    Ok(Html(
+        CollectionToggleTemplate {
+            collection_id: id,
+            kind: form.kind,
+            item_id: form.item_id,
+            selected,
+        }
+        .render()?,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::app::{champions, fixtures::state};
+    use axum::{body::Body, http::Request};
+    use reqwest::StatusCode;
+    use rstest::*;
+    use tower::ServiceExt;
+
+    async fn send(state: &AppState, request: Request<Body>) -> (StatusCode, String) {
+        let res = router::<()>(state.clone())
+            .merge(champions::router(state.clone()))
+            .oneshot(request)
+            .await
+            .unwrap();
+        let status = res.status();
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(bytes.to_vec()).unwrap())
+    }
+
+    fn form(uri: &str, body: &'static str) -> Request<Body> {
+        Request::post(uri)
+            .header("content-type", "application/x-www-form-urlencoded")
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn grid_highlights_collected_champions(state: AppState) {
+        let favorites = state.db.default_collection().unwrap();
+        let uri = format!("/collections/{}/items", favorites.id);
+        let (status, _) = send(&state, form(&uri, "kind=champion&item_id=145")).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (_, grid) = send(
+            &state,
+            Request::get("/champions").body(Body::empty()).unwrap(),
+        )
+        .await;
+        let kaisa = grid.find(r#"id="145""#).unwrap();
+        let annie = grid.find(r#"id="1""#).unwrap();
+        assert!(grid[kaisa..].contains(r#"data-toggled="true""#));
+        assert!(grid[annie..kaisa].contains(r#"data-toggled="false""#));
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn rejects_unknown_items_and_blank_names(state: AppState) {
+        let favorites = state.db.default_collection().unwrap();
+        let uri = format!("/collections/{}/items", favorites.id);
+        let (status, _) = send(&state, form(&uri, "kind=skin&item_id=99")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = send(&state, form("/collections", "name=++")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+}
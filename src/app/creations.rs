@@ -0,0 +1,896 @@
+use std::{
+    fs,
+    path::{Path as FsPath, PathBuf},
+};
+
+use anyhow::Context;
+use askama::Template;
+use axum::{
+    Form, Router,
+    body::Bytes,
+    extract::{DefaultBodyLimit, Multipart, Path, Query, State},
+    http::{HeaderMap, header},
+    response::{Html, IntoResponse, Redirect},
+    routing::{get, post},
+};
+use image::ImageFormat;
+use serde::Deserialize;
+use strum::IntoEnumIterator;
+
+use crate::{
+    cdrag::SkinAsset,
+    db::creations::{Creation, CreationDraft, CreationSort, Layer},
+    imaging::{
+        self, MAX_EDGE, MIN_EDGE,
+        compose::{Composition, LayerImage, Layout, OutputFormat},
+        focal::FocalPoint,
+        overlay::{self, Overlay, Prepared},
+    },
+};
+
+use super::{AppError, AppState, caching, mount};
+
+const CREATIONS_DIR: &str = "creations";
+const THUMBNAILS_DIR: &str = "thumbnails";
+/// Uploaded creations are full-resolution splash edits, well past axum's 2MB default
+const MAX_UPLOAD_BYTES: usize = 64 * 1024 * 1024;
+
+pub fn router<S>(state: AppState) -> Router<S> {
+    Router::new()
+        .route("/creations", get(creations_grid).post(create_creation))
+        .route("/creations/new", get(new_creation))
+        .route(
+            "/creations/{id}",
+            get(creation_page)
+                .post(update_creation)
+                .delete(delete_creation),
+        )
+        .route("/creations/{id}/render", post(render_creation))
+        .route("/creations/{id}/image", get(creation_image))
+        .route("/creations/{id}/thumbnail", get(creation_thumbnail))
+        .layer(DefaultBodyLimit::max(MAX_UPLOAD_BYTES))
+        .with_state(state)
+}
+
+impl AppState {
+    /// Where rendered creations and their thumbnails are kept, under the CDragon `data_dir`
+    pub fn creations_dir(&self) -> PathBuf {
+        self.cdrag.data_dir.join(CREATIONS_DIR)
+    }
+}
+
+fn thumbnail_path(state: &AppState, id: i64) -> PathBuf {
+    state
+        .creations_dir()
+        .join(THUMBNAILS_DIR)
+        .join(format!("{id}.jpg"))
+}
+
+fn find_creation(state: &AppState, id: i64) -> Result<Creation, AppError> {
+    state.db.creation(id)?.ok_or(AppError::NotFound)
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct CreationQuery {
+    search_term: Option<String>,
+    sort_by: Option<CreationSort>,
+}
+
+#[derive(Template)]
+#[template(path = "creations_grid.html")]
+struct CreationsGridTemplate {
+    creations: Vec<Creation>,
+}
+
+async fn creations_grid(
+    State(state): State<AppState>,
+    Query(query): Query<CreationQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let creations = state.db.creations(
+        query.search_term.as_deref().unwrap_or(""),
+        query.sort_by.unwrap_or_default(),
+    )?;
+    Ok(Html(CreationsGridTemplate { creations }.render()?))
+}
+
+/// A source skin of a creation, resolved for display
+struct SourceSkin {
+    id: u64,
+    name: String,
+    champion_slug: String,
+}
+
+#[derive(Template)]
+#[template(path = "creation_detail.html")]
+struct CreationDetailTemplate {
+    creation: Creation,
+    sources: Vec<SourceSkin>,
+    composition: Composition,
+    layers_json: String,
+    overlays_json: String,
+}
+
+async fn creation_page(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    let creation = find_creation(&state, id)?;
+    let sources = creation
+        .source_skins
+        .iter()
+        .filter_map(|id| {
+            let skin = state.cdrag.skin_by_id(*id)?;
+            let champ = state.cdrag.champion_by_id(id / 1000)?;
+            Some(SourceSkin {
+                id: *id,
+                name: skin.name.clone(),
+                champion_slug: champ.slug(),
+            })
+        })
+        .collect();
+    Ok(Html(
+        CreationDetailTemplate {
+            composition: creation.composition.unwrap_or_default(),
+            layers_json: serde_json::to_string_pretty(&creation.layers)
+                .context("couldn't serialize the layers")?,
+            overlays_json: serde_json::to_string_pretty(&creation.overlays)
+                .context("couldn't serialize the overlays")?,
+            creation,
+            sources,
+        }
+        .render()?,
+    ))
+}
+
+#[derive(Template)]
+#[template(path = "creation_form.html")]
+struct CreationFormTemplate {
+    creation: Option<Creation>,
+}
+
+async fn new_creation() -> Result<impl IntoResponse, AppError> {
+    Ok(Html(CreationFormTemplate { creation: None }.render()?))
+}
+
+/// The multipart body shared by creating and updating a creation
+#[derive(Default)]
+struct CreationUpload {
+    name: Option<String>,
+    source_skins: Option<Vec<u64>>,
+    layers: Option<Vec<Layer>>,
+    image: Option<Bytes>,
+}
+
+impl CreationUpload {
+    async fn parse(state: &AppState, mut multipart: Multipart) -> Result<Self, AppError> {
+        let bad_request =
+            |err: axum::extract::multipart::MultipartError| AppError::BadRequest(err.body_text());
+        let mut upload = CreationUpload::default();
+        while let Some(field) = multipart.next_field().await.map_err(bad_request)? {
+            match field.name() {
+                Some("name") => {
+                    let name = field.text().await.map_err(bad_request)?;
+                    let name = name.trim();
+                    if name.is_empty() {
+                        return Err(AppError::BadRequest("creation names can't be blank".into()));
+                    }
+                    upload.name = Some(name.to_string());
+                }
+                Some("source_skins") => {
+                    let text = field.text().await.map_err(bad_request)?;
+                    upload.source_skins = Some(parse_skin_ids(state, &text)?);
+                }
+                Some("layers") => {
+                    let text = field.text().await.map_err(bad_request)?;
+                    if !text.trim().is_empty() {
+                        let layers: Vec<Layer> = serde_json::from_str(&text).map_err(|err| {
+                            AppError::BadRequest(format!("invalid layers: {err}"))
+                        })?;
+                        for layer in &layers {
+                            layer.check().map_err(AppError::BadRequest)?;
+                        }
+                        upload.layers = Some(layers);
+                    }
+                }
+                Some("image") => {
+                    let bytes = field.bytes().await.map_err(bad_request)?;
+                    if !bytes.is_empty() {
+                        upload.image = Some(bytes);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(upload)
+    }
+
+    /// Applies the upload on top of an existing draft. Without explicit layers, source skins
+    /// keep the layer they already had, and new ones contribute their uncentered splash.
+    fn into_draft(self, mut draft: CreationDraft) -> CreationDraft {
+        if let Some(name) = self.name {
+            draft.name = name;
+        }
+        if let Some(source_skins) = self.source_skins {
+            draft.source_skins = source_skins;
+        }
+        match self.layers {
+            Some(layers) => draft.layers = layers,
+            None => {
+                draft.layers = draft
+                    .source_skins
+                    .iter()
+                    .map(|skin_id| {
+                        draft
+                            .layers
+                            .iter()
+                            .find(|layer| layer.skin_id == *skin_id)
+                            .cloned()
+                            .unwrap_or_else(|| Layer::new(*skin_id, SkinAsset::UncenteredSplash))
+                    })
+                    .collect()
+            }
+        }
+        draft
+    }
+}
+
+/// Parses a comma or whitespace separated list of skin ids, rejecting any that don't exist
+fn parse_skin_ids(state: &AppState, text: &str) -> Result<Vec<u64>, AppError> {
+    text.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|id| !id.is_empty())
+        .map(|id| {
+            id.parse::<u64>()
+                .ok()
+                .filter(|id| state.cdrag.skin_by_id(*id).is_some())
+                .ok_or_else(|| AppError::BadRequest(format!("{id} isn't a known skin id")))
+        })
+        .collect()
+}
+
+/// A creation's image with its thumbnail, ready for [`store_image`]
+struct EncodedImage {
+    format: ImageFormat,
+    bytes: Bytes,
+    thumbnail: Vec<u8>,
+}
+
+/// Decodes an upload and makes its thumbnail, before anything is saved, so a bad upload
+/// leaves the creation as it was
+async fn read_upload(bytes: Bytes) -> Result<EncodedImage, AppError> {
+    let format = image::guess_format(&bytes)
+        .map_err(|_| AppError::BadRequest("the upload isn't a supported image".into()))?;
+    tokio::task::spawn_blocking(move || {
+        let image = image::load_from_memory_with_format(&bytes, format)
+            .map_err(|err| AppError::BadRequest(format!("couldn't read the image: {err}")))?;
+        let thumbnail = imaging::thumbnail(&image)?;
+        Ok(EncodedImage {
+            format,
+            bytes,
+            thumbnail,
+        })
+    })
+    .await
+    .context("reading the upload panicked")?
+}
+
+/// Writes a creation's image and thumbnail under [`AppState::creations_dir`], then records
+/// them with the `draft` and `composition` they came from. Each file is swapped in whole, and
+/// the row only changes once they're there, so it never describes an image that's missing.
+fn store_image(
+    state: &AppState,
+    id: i64,
+    draft: Option<&CreationDraft>,
+    image: &EncodedImage,
+    composition: Option<&Composition>,
+) -> Result<(), AppError> {
+    let ext = image.format.extensions_str().first().unwrap_or(&"img");
+    let file_name = format!("{id}.{ext}");
+    let thumb_path = thumbnail_path(state, id);
+    let dir = thumb_path.parent().expect("thumbnails live in a directory");
+    fs::create_dir_all(dir).with_context(|| format!("couldn't create {dir:?}"))?;
+    let old_file = state
+        .db
+        .creation(id)?
+        .and_then(|creation| creation.image_file);
+    caching::write_cached(&state.creations_dir().join(&file_name), &image.bytes)
+        .context("couldn't save the creation image")?;
+    caching::write_cached(&thumb_path, &image.thumbnail)
+        .context("couldn't save the creation thumbnail")?;
+    state
+        .db
+        .set_creation_image(id, draft, &file_name, composition)?;
+    if let Some(old_file) = old_file.filter(|old_file| *old_file != file_name) {
+        fs::remove_file(state.creations_dir().join(old_file)).ok();
+    }
+    Ok(())
+}
+
+/// A [`Composition`], spelled out since urlencoded forms can't be flattened into one
+#[derive(Deserialize)]
+struct RenderForm {
+    layout: Layout,
+    width: u32,
+    height: u32,
+    format: OutputFormat,
+    /// JSON for the creation's layers, replacing the saved ones when present
+    layers: Option<String>,
+    /// JSON for the creation's overlays, likewise
+    overlays: Option<String>,
+}
+
+/// Loads what each overlay draws from outside the creation: logo images and skin rarities
+async fn prepare_overlays(
+    state: &AppState,
+    overlays: &[Overlay],
+) -> Result<Vec<Prepared>, AppError> {
+    if overlays.len() > overlay::MAX_OVERLAYS {
+        return Err(AppError::BadRequest(format!(
+            "creations can have at most {} overlays",
+            overlay::MAX_OVERLAYS
+        )));
+    }
+    let mut prepared = Vec::with_capacity(overlays.len());
+    for overlay in overlays {
+        overlay.check().map_err(AppError::BadRequest)?;
+        prepared.push(match overlay {
+            Overlay::Text(text) => Prepared::Text(text.clone()),
+            Overlay::Image(image) => {
+                let path = state
+                    .cdrag
+                    .requested_asset(FsPath::new(&image.path))
+                    .await
+                    .map_err(|err| {
+                        AppError::BadRequest(format!("couldn't get {}: {err:#}", image.path))
+                    })?;
+                let loaded = tokio::task::spawn_blocking(move || image::open(&path))
+                    .await
+                    .context("loading an overlay panicked")?
+                    .map_err(|err| {
+                        AppError::BadRequest(format!("{} isn't an image: {err}", image.path))
+                    })?;
+                Prepared::Image(image.clone(), loaded)
+            }
+            Overlay::RarityBadge(badge) => {
+                let skin = state.cdrag.skin_by_id(badge.skin_id).ok_or_else(|| {
+                    AppError::BadRequest(format!("{} isn't a known skin id", badge.skin_id))
+                })?;
+                Prepared::Badges(badge.clone(), overlay::badges(&skin.rarity, skin.is_legacy))
+            }
+        });
+    }
+    Ok(prepared)
+}
+
+/// Renders the creation's layers into its image with the submitted [`Composition`]
+async fn render_creation(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Form(form): Form<RenderForm>,
+) -> Result<impl IntoResponse, AppError> {
+    let mut creation = find_creation(&state, id)?;
+    if let Some(text) = form.layers.filter(|text| !text.trim().is_empty()) {
+        creation.layers = serde_json::from_str(&text)
+            .map_err(|err| AppError::BadRequest(format!("invalid layers: {err}")))?;
+    }
+    if let Some(text) = form.overlays.filter(|text| !text.trim().is_empty()) {
+        creation.overlays = serde_json::from_str(&text)
+            .map_err(|err| AppError::BadRequest(format!("invalid overlays: {err}")))?;
+    }
+    let composition = Composition {
+        layout: form.layout,
+        width: form.width,
+        height: form.height,
+        format: form.format,
+    };
+    composition
+        .check(creation.layers.len())
+        .map_err(AppError::BadRequest)?;
+    for layer in &creation.layers {
+        layer.check().map_err(AppError::BadRequest)?;
+    }
+    let overlays = prepare_overlays(&state, &creation.overlays).await?;
+
+    let mut sources = Vec::with_capacity(creation.layers.len());
+    for layer in &creation.layers {
+        let skin = state.cdrag.skin_by_id(layer.skin_id).ok_or_else(|| {
+            AppError::BadRequest(format!("{} isn't a known skin id", layer.skin_id))
+        })?;
+        // Focal points are found on uncentered splashes, so they don't apply to other assets
+        let focal = match layer.asset {
+            SkinAsset::UncenteredSplash => state.focal_point(skin)?.0,
+            _ => FocalPoint::default(),
+        };
+        let path = state.cdrag.local_skin_asset(skin, &layer.asset).await?;
+        sources.push((path, focal));
+    }
+    let layers = creation.layers.clone();
+    let image = tokio::task::spawn_blocking(move || {
+        let images = sources
+            .into_iter()
+            .map(|(path, focal)| {
+                let image = image::open(&path)
+                    .with_context(|| format!("couldn't read the asset at {path:?}"))?;
+                anyhow::Ok(LayerImage { image, focal })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let bytes = composition.render(&layers, &images, &overlays)?;
+        let thumbnail = imaging::thumbnail(&image::load_from_memory(&bytes)?)?;
+        anyhow::Ok(EncodedImage {
+            format: composition.format.image_format(),
+            bytes: bytes.into(),
+            thumbnail,
+        })
+    })
+    .await
+    .context("rendering panicked")??;
+
+    let draft = CreationDraft {
+        name: creation.name,
+        source_skins: creation.source_skins,
+        layers: creation.layers,
+        overlays: creation.overlays,
+    };
+    store_image(&state, id, Some(&draft), &image, Some(&composition))?;
+    Ok(Redirect::to(&mount::absolute(&format!("/creations/{id}"))))
+}
+
+async fn create_creation(
+    State(state): State<AppState>,
+    multipart: Multipart,
+) -> Result<impl IntoResponse, AppError> {
+    let mut upload = CreationUpload::parse(&state, multipart).await?;
+    if upload.name.is_none() {
+        return Err(AppError::BadRequest("a creation needs a name".into()));
+    }
+    let image = match upload.image.take() {
+        Some(bytes) => Some(read_upload(bytes).await?),
+        None => None,
+    };
+    let creation = state
+        .db
+        .create_creation(&upload.into_draft(CreationDraft::default()))?;
+    if let Some(image) = image
+        && let Err(err) = store_image(&state, creation.id, None, &image, None)
+    {
+        state.db.delete_creation(creation.id)?;
+        return Err(err);
+    }
+    Ok(Redirect::to(&mount::absolute(&format!(
+        "/creations/{}",
+        creation.id
+    ))))
+}
+
+async fn update_creation(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    multipart: Multipart,
+) -> Result<impl IntoResponse, AppError> {
+    let existing = find_creation(&state, id)?;
+    let mut upload = CreationUpload::parse(&state, multipart).await?;
+    let image = match upload.image.take() {
+        Some(bytes) => Some(read_upload(bytes).await?),
+        None => None,
+    };
+    let draft = upload.into_draft(CreationDraft {
+        name: existing.name,
+        source_skins: existing.source_skins,
+        layers: existing.layers,
+        overlays: existing.overlays,
+    });
+    match image {
+        Some(image) => store_image(&state, id, Some(&draft), &image, None)?,
+        None => {
+            state.db.update_creation(id, &draft)?;
+        }
+    }
+    Ok(Redirect::to(&mount::absolute(&format!("/creations/{id}"))))
+}
+
+/// Deletes the creation and its files, then sends htmx back to the creations page
+async fn delete_creation(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    let creation = find_creation(&state, id)?;
+    state.db.delete_creation(id)?;
+    if let Some(file_name) = creation.image_file {
+        fs::remove_file(state.creations_dir().join(file_name)).ok();
+    }
+    fs::remove_file(thumbnail_path(&state, id)).ok();
+    let mut headers = HeaderMap::new();
+    headers.insert("HX-Redirect", mount::path("/#creations").parse().unwrap());
+    Ok(headers)
+}
+
+fn serve_file(path: PathBuf) -> Result<impl IntoResponse, AppError> {
+    let bytes = fs::read(&path).map_err(|_| AppError::NotFound)?;
+    let mime = image::ImageFormat::from_path(&path)
+        .map(|format| format.to_mime_type())
+        .unwrap_or("application/octet-stream");
+    Ok(([(header::CONTENT_TYPE, mime)], bytes))
+}
+
+async fn creation_image(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    let file_name = find_creation(&state, id)?
+        .image_file
+        .ok_or(AppError::NotFound)?;
+    serve_file(state.creations_dir().join(file_name))
+}
+
+async fn creation_thumbnail(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, AppError> {
+    find_creation(&state, id)?;
+    serve_file(thumbnail_path(&state, id))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::app::fixtures::{disk_state, state};
+    use axum::{body::Body, http::Request};
+    use reqwest::StatusCode;
+    use rstest::*;
+    use std::io::Cursor;
+    use tower::ServiceExt;
+
+    fn multipart(uri: &str, fields: &[(&str, &[u8])]) -> Request<Body> {
+        let mut body = Vec::new();
+        for (name, value) in fields {
+            body.extend_from_slice(b"--BOUNDARY\r\n");
+            let disposition = match *name {
+                "image" => format!(
+                    "Content-Disposition: form-data; name=\"{name}\"; filename=\"a.png\"\r\n\r\n"
+                ),
+                _ => format!("Content-Disposition: form-data; name=\"{name}\"\r\n\r\n"),
+            };
+            body.extend_from_slice(disposition.as_bytes());
+            body.extend_from_slice(value);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(b"--BOUNDARY--\r\n");
+        Request::post(uri)
+            .header("content-type", "multipart/form-data; boundary=BOUNDARY")
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    fn png() -> Vec<u8> {
+        let mut bytes = Vec::new();
+        image::RgbImage::new(1200, 700)
+            .write_to(&mut Cursor::new(&mut bytes), image::ImageFormat::Png)
+            .unwrap();
+        bytes
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn create_with_image_then_delete(disk_state: (AppState, tempfile::TempDir)) {
+        let (state, _dir) = disk_state;
+        let app = router::<()>(state.clone());
+        let png = png();
+        let res = app
+            .clone()
+            .oneshot(multipart(
+                "/creations",
+                &[
+                    ("name", b"Angels"),
+                    ("source_skins", b"145001, 1001"),
+                    ("image", &png),
+                ],
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::SEE_OTHER);
+        assert_eq!(res.headers()[header::LOCATION], "/creations/1");
+
+        let creation = state.db.creation(1).unwrap().unwrap();
+        assert_eq!(creation.source_skins, [145001, 1001]);
+        assert_eq!(creation.layers.len(), 2);
+        let res = app
+            .clone()
+            .oneshot(
+                Request::get("/creations/1/thumbnail")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let thumbnail = image::load_from_memory(&bytes).unwrap();
+        assert_eq!(
+            (thumbnail.width(), thumbnail.height()),
+            (imaging::THUMBNAIL_WIDTH, imaging::THUMBNAIL_HEIGHT)
+        );
+
+        let res = app
+            .clone()
+            .oneshot(Request::delete("/creations/1").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(!state.creations_dir().join("1.png").exists());
+        assert!(state.db.creation(1).unwrap().is_none());
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn redirects_under_the_prefix(disk_state: (AppState, tempfile::TempDir)) {
+        let (state, _dir) = disk_state;
+        let mount = mount::Mount {
+            prefix: "/p".into(),
+            ..Default::default()
+        };
+        let app = mount::router(mount, router(state));
+        let mut request = multipart("/p/creations", &[("name", b"Angels")]);
+        request
+            .headers_mut()
+            .insert(header::HOST, "app.local".parse().unwrap());
+        let res = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(res.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            res.headers()[header::LOCATION],
+            "http://app.local/p/creations/1"
+        );
+
+        let res = app
+            .oneshot(
+                Request::delete("/p/creations/1")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.headers()["HX-Redirect"], "/p/#creations");
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn bad_uploads_save_nothing(disk_state: (AppState, tempfile::TempDir)) {
+        let (state, _dir) = disk_state;
+        let app = router::<()>(state.clone());
+        let not_an_image: &[u8] = b"\x89PNG\r\n\x1a\nnot really";
+        let res = app
+            .clone()
+            .oneshot(multipart(
+                "/creations",
+                &[("name", b"Angels"), ("image", not_an_image)],
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert!(state.db.creation(1).unwrap().is_none());
+
+        let creation = state
+            .db
+            .create_creation(&CreationDraft {
+                name: "Angels".into(),
+                ..Default::default()
+            })
+            .unwrap();
+        let res = app
+            .oneshot(multipart(
+                &format!("/creations/{}", creation.id),
+                &[("name", b"Demons"), ("image", not_an_image)],
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            state.db.creation(creation.id).unwrap().unwrap().name,
+            "Angels"
+        );
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn failed_saves_leave_the_creation_as_it_was(disk_state: (AppState, tempfile::TempDir)) {
+        let (state, _dir) = disk_state;
+        let creation = state
+            .db
+            .create_creation(&CreationDraft {
+                name: "Angels".into(),
+                ..Default::default()
+            })
+            .unwrap();
+        // Nothing can be renamed over a directory, so the image can't be saved
+        fs::create_dir_all(state.creations_dir().join(format!("{}.png", creation.id))).unwrap();
+        let res = router::<()>(state.clone())
+            .oneshot(multipart(
+                &format!("/creations/{}", creation.id),
+                &[("name", b"Demons"), ("image", &png())],
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let saved = state.db.creation(creation.id).unwrap().unwrap();
+        assert_eq!((saved.name.as_str(), saved.image_file), ("Angels", None));
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn rejects_unknown_skins(disk_state: (AppState, tempfile::TempDir)) {
+        let (state, _dir) = disk_state;
+        let res = router::<()>(state)
+            .oneshot(multipart(
+                "/creations",
+                &[("name", b"Nope"), ("source_skins", b"99")],
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn renders_layers_into_the_image(disk_state: (AppState, tempfile::TempDir)) {
+        let (state, _dir) = disk_state;
+        for id in [145001, 1001] {
+            let skin = state.cdrag.skin_by_id(id).unwrap();
+            let path = state.cdrag.data_dir.join(&skin.uncentered_splash_path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            image::RgbImage::new(320, 180).save(path).unwrap();
+        }
+        let creation = state
+            .db
+            .create_creation(&CreationDraft {
+                name: "Angels".into(),
+                source_skins: vec![145001, 1001],
+                layers: vec![
+                    Layer::new(145001, SkinAsset::UncenteredSplash),
+                    Layer::new(1001, SkinAsset::UncenteredSplash),
+                ],
+                ..Default::default()
+            })
+            .unwrap();
+
+        let render = |body: &'static str| {
+            Request::post(format!("/creations/{}/render", creation.id))
+                .header("content-type", "application/x-www-form-urlencoded")
+                .body(Body::from(body))
+                .unwrap()
+        };
+        let app = router::<()>(state.clone());
+        let res = app
+            .clone()
+            .oneshot(render("layout=versus&width=640&height=360&format=jpeg"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::SEE_OTHER);
+        let creation = state.db.creation(creation.id).unwrap().unwrap();
+        assert_eq!(creation.composition.unwrap().layout, Layout::Versus);
+        let image = image::open(state.creations_dir().join(creation.image_file.unwrap())).unwrap();
+        assert_eq!((image.width(), image.height()), (640, 360));
+
+        let res = app
+            .oneshot(render(
+                "layout=versus&width=640&height=360&format=png&layers=[]",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[rstest]
+    #[case(imaging::compose::MAX_LAYERS + 1, 0)]
+    #[case(1, overlay::MAX_OVERLAYS + 1)]
+    #[tokio::test]
+    async fn refuses_too_many_layers_or_overlays(
+        state: AppState,
+        #[case] layers: usize,
+        #[case] overlays: usize,
+    ) {
+        let creation = state
+            .db
+            .create_creation(&CreationDraft {
+                name: "Crowded".into(),
+                ..Default::default()
+            })
+            .unwrap();
+        let layers = vec![Layer::new(145001, SkinAsset::UncenteredSplash); layers];
+        let overlays = vec![serde_json::json!({"kind": "text", "text": "A"}); overlays];
+        let mut form = reqwest::Url::parse("http://localhost").unwrap();
+        form.query_pairs_mut().extend_pairs([
+            ("layout", "grid"),
+            ("width", "320"),
+            ("height", "180"),
+            ("format", "png"),
+            ("layers", &serde_json::to_string(&layers).unwrap()),
+            ("overlays", &serde_json::to_string(&overlays).unwrap()),
+        ]);
+        let res = router::<()>(state.clone())
+            .oneshot(
+                Request::post(format!("/creations/{}/render", creation.id))
+                    .header("content-type", "application/x-www-form-urlencoded")
+                    .body(Body::from(form.query().unwrap().to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert!(
+            state
+                .db
+                .creation(creation.id)
+                .unwrap()
+                .unwrap()
+                .layers
+                .is_empty()
+        );
+    }
+
+    #[rstest]
+    #[case(r#"[{"kind": "text", "text": "ANGELS", "y": 0.4}, {"kind": "rarity_badge", "skin_id": 145001}]"#, StatusCode::SEE_OTHER)]
+    #[case(
+        r#"[{"kind": "rarity_badge", "skin_id": 145999}]"#,
+        StatusCode::BAD_REQUEST
+    )]
+    #[case(r#"[{"kind": "text", "text": ""}]"#, StatusCode::BAD_REQUEST)]
+    #[case(
+        r##"[{"kind": "text", "text": "ANGELS", "stroke": {"color": "#000000", "width": 1e6}}]"##,
+        StatusCode::BAD_REQUEST
+    )]
+    #[case(
+        r#"[{"kind": "image", "path": "assets/anything/else.png", "width": 0.2}]"#,
+        StatusCode::BAD_REQUEST
+    )]
+    #[tokio::test]
+    async fn saves_overlays(
+        disk_state: (AppState, tempfile::TempDir),
+        #[case] overlays: &str,
+        #[case] expected: StatusCode,
+    ) {
+        let (state, _dir) = disk_state;
+        let skin = state.cdrag.skin_by_id(145001).unwrap();
+        let path = state.cdrag.data_dir.join(&skin.uncentered_splash_path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        image::RgbImage::new(320, 180).save(path).unwrap();
+        let creation = state
+            .db
+            .create_creation(&CreationDraft {
+                name: "Titled".into(),
+                source_skins: vec![145001],
+                layers: vec![Layer::new(145001, SkinAsset::UncenteredSplash)],
+                ..Default::default()
+            })
+            .unwrap();
+
+        let mut form = reqwest::Url::parse("http://localhost").unwrap();
+        form.query_pairs_mut().extend_pairs([
+            ("layout", "strip"),
+            ("width", "320"),
+            ("height", "180"),
+            ("format", "png"),
+            ("overlays", overlays),
+        ]);
+        let body = form.query().unwrap().to_string();
+        let res = router::<()>(state.clone())
+            .oneshot(
+                Request::post(format!("/creations/{}/render", creation.id))
+                    .header("content-type", "application/x-www-form-urlencoded")
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), expected);
+        let saved = state.db.creation(creation.id).unwrap().unwrap();
+        if expected == StatusCode::SEE_OTHER {
+            assert_eq!(saved.overlays.len(), 2);
+            let image = image::open(state.creations_dir().join(saved.image_file.unwrap()))
+                .unwrap()
+                .to_rgb8();
+            // The splash is black, so anything brighter was drawn on top of it
+            assert!(image.pixels().any(|pixel| pixel.0 == [255, 255, 255]));
+        } else {
+            assert!(saved.overlays.is_empty());
+        }
+    }
+}
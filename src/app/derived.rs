@@ -0,0 +1,136 @@
+use std::{
+    fs,
+    path::{Component, Path as FsPath},
+};
+
+use anyhow::Context;
+use axum::{
+    Router,
+    extract::{Path, State},
+    http::header,
+    response::IntoResponse,
+    routing::get,
+};
+
+use crate::imaging::derive::{self, Format, WIDTHS};
+
+use super::{AppError, AppState, caching};
+
+pub const DERIVED_DIR: &str = "derived";
+
+pub fn router<S>(state: AppState) -> Router<S> {
+    Router::new()
+        .route("/derived/{width}/{format}/{*path}", get(derived_image))
+        .with_state(state)
+}
+
+/// The asset at `path` under the data directory scaled to one of [`WIDTHS`] and encoded as
+/// `format`. Derivatives are made the first time they're asked for and served from disk after.
+/// Only [known or downloaded assets](crate::cdrag::CDragon::requested_asset) have them.
+async fn derived_image(
+    State(state): State<AppState>,
+    Path((width, format, path)): Path<(u32, Format, String)>,
+) -> Result<impl IntoResponse, AppError> {
+    if !WIDTHS.contains(&width) {
+        return Err(AppError::BadRequest(format!(
+            "derivatives come in widths of {WIDTHS:?}"
+        )));
+    }
+    let asset_path = FsPath::new(&path);
+    let is_image = image::ImageFormat::from_path(asset_path).is_ok();
+    if !is_image
+        || !asset_path
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+    {
+        return Err(AppError::NotFound);
+    }
+
+    let cached = state
+        .cdrag
+        .data_dir
+        .join(DERIVED_DIR)
+        .join(width.to_string())
+        .join(format!("{path}.{}", format.extension()));
+    let bytes = match fs::read(&cached) {
+        Ok(bytes) => bytes,
+        Err(_) => {
+            let source = state
+                .cdrag
+                .requested_asset(asset_path)
+                .await
+                .map_err(|err| {
+                    tracing::warn!("no source for derivative of {path}: {err:#}");
+                    AppError::NotFound
+                })?;
+            let bytes = tokio::task::spawn_blocking(move || {
+                let image = image::open(&source)
+                    .with_context(|| format!("couldn't read the asset at {source:?}"))?;
+                format.encode(&derive::resize(&image, width))
+            })
+            .await
+            .context("deriving the image panicked")??;
+            let dir = cached.parent().expect("derivatives live in a directory");
+            fs::create_dir_all(dir).with_context(|| format!("couldn't create {dir:?}"))?;
+            caching::write_cached(&cached, &bytes).context("couldn't cache the derivative")?;
+            bytes
+        }
+    };
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.mime_type()),
+            (header::CACHE_CONTROL, caching::ASSET_CACHE),
+        ],
+        bytes,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::app::fixtures::disk_state;
+    use axum::{body::Body, http::Request};
+    use reqwest::StatusCode;
+    use rstest::*;
+    use tower::ServiceExt;
+
+    #[rstest]
+    #[case("/derived/320/webp/assets/splash.jpg", StatusCode::OK)]
+    #[case("/derived/321/webp/assets/splash.jpg", StatusCode::BAD_REQUEST)]
+    #[case("/derived/320/gif/assets/splash.jpg", StatusCode::BAD_REQUEST)]
+    #[case("/derived/320/jpeg/assets/../../secret.jpg", StatusCode::NOT_FOUND)]
+    #[case("/derived/320/jpeg/assets/champions.json", StatusCode::NOT_FOUND)]
+    #[case("/derived/320/webp/assets/anything/else.jpg", StatusCode::NOT_FOUND)]
+    #[case("/derived/320/webp/creations/1.png", StatusCode::NOT_FOUND)]
+    #[tokio::test]
+    async fn derives_local_assets(
+        disk_state: (AppState, tempfile::TempDir),
+        #[case] uri: &str,
+        #[case] expected: StatusCode,
+    ) {
+        let (state, _dir) = disk_state;
+        let assets = state.cdrag.data_dir.join("assets");
+        fs::create_dir_all(&assets).unwrap();
+        image::RgbImage::new(640, 360)
+            .save(assets.join("splash.jpg"))
+            .unwrap();
+        let creations = state.cdrag.data_dir.join("creations");
+        fs::create_dir_all(&creations).unwrap();
+        image::RgbImage::new(640, 360)
+            .save(creations.join("1.png"))
+            .unwrap();
+
+        let res = router::<()>(state)
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), expected);
+        if expected == StatusCode::OK {
+            assert_eq!(res.headers()[header::CONTENT_TYPE], "image/webp");
+            let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert_eq!(image::load_from_memory(&bytes).unwrap().width(), 320);
+        }
+    }
+}
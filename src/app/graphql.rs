@@ -0,0 +1,455 @@
+use async_graphql::{
+    Context, EmptyMutation, EmptySubscription, Enum, Object, Schema, http::GraphiQLSource,
+};
+use axum::{
+    Json, Router,
+    extract::State,
+    response::{Html, IntoResponse},
+    routing::get,
+};
+
+use crate::{
+    cdrag::{self, Champion, Skin, SkinLine},
+    imaging::palette::Color,
+};
+
+use super::champions::ChampionQuery;
+use super::skins::DEFAULT_MAX_DISTANCE;
+use super::{AppState, mount};
+
+pub type FourthShotSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// Deepest a query can nest. Champions, skins and skin lines link to each other in a cycle,
+/// so without a limit a short query could walk it indefinitely. GraphiQL's introspection
+/// query goes about 13 deep.
+const MAX_DEPTH: usize = 16;
+/// Most work a query can ask for, where each field costs 1 and the lists that walk the cycle
+/// cost [`FAN_OUT`] times their fields
+const MAX_COMPLEXITY: usize = 2_000;
+/// About how many items a list on a champion, skin or skin line holds
+const FAN_OUT: usize = 10;
+
+pub fn schema() -> FourthShotSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+#[derive(Clone)]
+struct GraphQLState {
+    app: AppState,
+    schema: FourthShotSchema,
+}
+
+/// The GraphQL endpoint at `/graphql`, with GraphiQL served to browsers on `GET`
+pub fn router<S>(state: AppState) -> Router<S> {
+    Router::new()
+        .route("/graphql", get(graphiql).post(graphql))
+        .with_state(GraphQLState {
+            app: state,
+            schema: schema(),
+        })
+}
+
+async fn graphiql() -> impl IntoResponse {
+    Html(
+        GraphiQLSource::build()
+            .endpoint(&mount::path("/graphql"))
+            .finish(),
+    )
+}
+
+async fn graphql(
+    State(state): State<GraphQLState>,
+    Json(request): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    Json(state.schema.execute(request.data(state.app)).await)
+}
+
+#[derive(Enum, Copy, Clone, Eq, PartialEq)]
+#[graphql(remote = "cdrag::Rarity")]
+pub enum Rarity {
+    #[graphql(name = "EPIC")]
+    KEpic,
+    #[graphql(name = "LEGENDARY")]
+    KLegendary,
+    #[graphql(name = "MYTHIC")]
+    KMythic,
+    #[graphql(name = "NONE")]
+    KNoRarity,
+    #[graphql(name = "RARE")]
+    KRare,
+    #[graphql(name = "TRANSCENDENT")]
+    KTranscendent,
+    #[graphql(name = "ULTIMATE")]
+    KUltimate,
+    #[graphql(name = "EXALTED")]
+    KExalted,
+}
+
+fn app_state<'a>(ctx: &Context<'a>) -> async_graphql::Result<&'a AppState> {
+    ctx.data::<AppState>()
+}
+
+fn matches_rarity(skin: &Skin, rarity: Option<Rarity>) -> bool {
+    rarity.is_none_or(|rarity| skin.rarity == rarity.into())
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Champions filtered the same way as the champion grid
+    async fn champions(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "Case-insensitive substring of the champion's name")] name: Option<String>,
+        #[graphql(desc = "Only champions with this role, e.g. `mage`")] role: Option<String>,
+        #[graphql(desc = "Only champions with at least one skin of this rarity")] rarity: Option<
+            Rarity,
+        >,
+        #[graphql(desc = "`asc` or `desc`")] sort_order: Option<String>,
+    ) -> async_graphql::Result<Vec<ChampionObject>> {
+        let state = app_state(ctx)?;
+        let query = ChampionQuery {
+            search_term: name,
+            sort_by: None,
+            sort_order,
+        };
+        Ok(query
+            .apply(state.cdrag.champions.values())
+            .into_iter()
+            .filter(|champ| {
+                role.as_ref()
+                    .is_none_or(|role| champ.roles.iter().any(|r| r.eq_ignore_ascii_case(role)))
+            })
+            .filter(|champ| {
+                rarity.is_none() || champ.skins.iter().any(|skin| matches_rarity(skin, rarity))
+            })
+            .cloned()
+            .map(ChampionObject)
+            .collect())
+    }
+
+    /// A champion by numeric id or alias
+    async fn champion(
+        &self,
+        ctx: &Context<'_>,
+        key: String,
+    ) -> async_graphql::Result<Option<ChampionObject>> {
+        let state = app_state(ctx)?;
+        Ok(state
+            .cdrag
+            .champion_by_key(&key)
+            .cloned()
+            .map(ChampionObject))
+    }
+
+    /// Skins across every champion, ordered by id
+    async fn skins(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "Case-insensitive substring of the skin's name")] name: Option<String>,
+        rarity: Option<Rarity>,
+        legacy: Option<bool>,
+        #[graphql(desc = "Only skins with a palette color within 100 of this #rrggbb color")]
+        color: Option<String>,
+    ) -> async_graphql::Result<Vec<SkinObject>> {
+        let state = app_state(ctx)?;
+        let name = name.unwrap_or_default().to_lowercase();
+        let color = color.map(|color| color.parse::<Color>()).transpose()?;
+        let mut skins: Vec<SkinObject> = state
+            .cdrag
+            .champions
+            .values()
+            .flat_map(|champ| &champ.skins)
+            .filter(|skin| skin.name.to_lowercase().contains(&name))
+            .filter(|skin| {
+                color.is_none_or(|color| {
+                    skin.palette
+                        .iter()
+                        .any(|c| c.distance(color) <= DEFAULT_MAX_DISTANCE)
+                })
+            })
+            .filter(|skin| matches_rarity(skin, rarity))
+            .filter(|skin| legacy.is_none_or(|legacy| skin.is_legacy == legacy))
+            .cloned()
+            .map(SkinObject)
+            .collect();
+        skins.sort_by_key(|skin| skin.0.id);
+        Ok(skins)
+    }
+
+    async fn skin(&self, ctx: &Context<'_>, id: u64) -> async_graphql::Result<Option<SkinObject>> {
+        let state = app_state(ctx)?;
+        Ok(state.cdrag.skin_by_id(id).cloned().map(SkinObject))
+    }
+
+    /// Skin lines, sorted by name
+    async fn skin_lines(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "Case-insensitive substring of the skin line's name")] name: Option<
+            String,
+        >,
+    ) -> async_graphql::Result<Vec<SkinLineObject>> {
+        let state = app_state(ctx)?;
+        let name = name.unwrap_or_default().to_lowercase();
+        let mut lines: Vec<SkinLineObject> = state
+            .cdrag
+            .skin_lines
+            .values()
+            .filter(|line| line.name.to_lowercase().contains(&name))
+            .cloned()
+            .map(SkinLineObject)
+            .collect();
+        lines.sort_by(|a, b| a.0.name.cmp(&b.0.name));
+        Ok(lines)
+    }
+
+    async fn skin_line(
+        &self,
+        ctx: &Context<'_>,
+        id: u64,
+    ) -> async_graphql::Result<Option<SkinLineObject>> {
+        let state = app_state(ctx)?;
+        Ok(state.cdrag.skin_line_by_id(id).cloned().map(SkinLineObject))
+    }
+}
+
+pub struct ChampionObject(Champion);
+
+#[Object(name = "Champion")]
+impl ChampionObject {
+    async fn id(&self) -> u64 {
+        self.0.id
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn alias(&self) -> &str {
+        &self.0.alias
+    }
+
+    async fn title(&self) -> &str {
+        &self.0.title
+    }
+
+    async fn short_bio(&self) -> &str {
+        &self.0.short_bio
+    }
+
+    async fn roles(&self) -> &[String] {
+        &self.0.roles
+    }
+
+    async fn square_portrait_path(&self) -> &str {
+        &self.0.square_portrait_path
+    }
+
+    #[graphql(complexity = "FAN_OUT * child_complexity")]
+    async fn skins(&self, rarity: Option<Rarity>) -> Vec<SkinObject> {
+        self.0
+            .skins
+            .iter()
+            .filter(|skin| matches_rarity(skin, rarity))
+            .cloned()
+            .map(SkinObject)
+            .collect()
+    }
+}
+
+pub struct SkinObject(Skin);
+
+#[Object(name = "Skin")]
+impl SkinObject {
+    async fn id(&self) -> u64 {
+        self.0.id
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn is_base(&self) -> bool {
+        self.0.is_base
+    }
+
+    async fn is_legacy(&self) -> bool {
+        self.0.is_legacy
+    }
+
+    async fn rarity(&self) -> Rarity {
+        self.0.rarity.clone().into()
+    }
+
+    async fn description(&self) -> Option<&str> {
+        self.0.description.as_deref()
+    }
+
+    async fn splash_path(&self) -> &str {
+        &self.0.splash_path
+    }
+
+    async fn uncentered_splash_path(&self) -> &str {
+        &self.0.uncentered_splash_path
+    }
+
+    async fn tile_path(&self) -> &str {
+        &self.0.tile_path
+    }
+
+    /// Dominant colors of the splash as `#rrggbb`, most common first
+    async fn palette(&self) -> Vec<String> {
+        self.0.palette.iter().map(Color::to_string).collect()
+    }
+
+    async fn load_screen_path(&self) -> &str {
+        &self.0.load_screen_path
+    }
+
+    async fn champion(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<ChampionObject>> {
+        let state = app_state(ctx)?;
+        Ok(state
+            .cdrag
+            .champion_by_id(self.0.id / 1000)
+            .cloned()
+            .map(ChampionObject))
+    }
+
+    #[graphql(complexity = "FAN_OUT * child_complexity")]
+    async fn skin_lines(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<SkinLineObject>> {
+        let state = app_state(ctx)?;
+        Ok(self
+            .0
+            .skin_lines
+            .iter()
+            .filter_map(|id| state.cdrag.skin_line_by_id(*id))
+            .cloned()
+            .map(SkinLineObject)
+            .collect())
+    }
+}
+
+pub struct SkinLineObject(SkinLine);
+
+#[Object(name = "SkinLine")]
+impl SkinLineObject {
+    async fn id(&self) -> u64 {
+        self.0.id
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn description(&self) -> &str {
+        &self.0.description
+    }
+
+    /// Dominant colors across the line's skins as `#rrggbb`, most common first
+    async fn palette(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 3)] count: usize,
+    ) -> async_graphql::Result<Vec<String>> {
+        let state = app_state(ctx)?;
+        Ok(state
+            .cdrag
+            .skin_line_palette(self.0.id, count)
+            .iter()
+            .map(Color::to_string)
+            .collect())
+    }
+
+    #[graphql(complexity = "FAN_OUT * child_complexity")]
+    async fn skins(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<SkinObject>> {
+        let state = app_state(ctx)?;
+        Ok(state
+            .cdrag
+            .skins_in_line(self.0.id)
+            .into_iter()
+            .cloned()
+            .map(SkinObject)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::app::fixtures::state;
+    use rstest::*;
+
+    async fn execute(state: AppState, query: &str) -> serde_json::Value {
+        let res = schema()
+            .execute(async_graphql::Request::new(query).data(state))
+            .await;
+        assert!(res.errors.is_empty(), "{:?}", res.errors);
+        res.data.into_json().unwrap()
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn nested_skin_lines(state: AppState) {
+        let data = execute(
+            state,
+            r#"{ champion(key: "kaisa") { skins(rarity: EPIC) { skinLines { name skins { name } } } } }"#,
+        )
+        .await;
+        let line = &data["champion"]["skins"][0]["skinLines"][0];
+        assert_eq!(line["name"], "Bullet Angel");
+        assert_eq!(line["skins"][0]["name"], "Bullet Angel Annie");
+        assert_eq!(line["skins"][1]["name"], "Bullet Angel Kai'Sa");
+    }
+
+    #[rstest]
+    #[case::deep(
+        &format!(
+            "{{ __type(name: \"Skin\") {{ {}name{} }} }}",
+            "ofType { ".repeat(MAX_DEPTH),
+            " }".repeat(MAX_DEPTH)
+        ),
+        "nested too deep"
+    )]
+    #[case::fanned_out(
+        r#"{ champions { skins { skinLines { skins { skinLines { skins { name } } } } } } }"#,
+        "too complex"
+    )]
+    #[tokio::test]
+    async fn expensive_queries_are_refused(
+        state: AppState,
+        #[case] query: &str,
+        #[case] expected: &str,
+    ) {
+        let res = schema()
+            .execute(async_graphql::Request::new(query).data(state))
+            .await;
+        assert!(res.data.into_json().unwrap().is_null());
+        assert!(res.errors[0].message.contains(expected), "{:?}", res.errors);
+        assert_eq!(res.errors.len(), 1, "{:?}", res.errors);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn graphiql_can_introspect(state: AppState) {
+        let type_ref = "kind name ofType { ".repeat(8) + "kind name" + &" }".repeat(8);
+        let query = format!(
+            "{{ __schema {{ queryType {{ name }} types {{ kind name description \
+             fields(includeDeprecated: true) {{ name args {{ name type {{ {type_ref} }} }} \
+             type {{ {type_ref} }} }} }} }} }}"
+        );
+        let data = execute(state, &query).await;
+        assert_eq!(data["__schema"]["queryType"]["name"], "QueryRoot");
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn champions_by_role(state: AppState) {
+        let data = execute(state, r#"{ champions(role: "Mage") { name } }"#).await;
+        assert_eq!(data["champions"], serde_json::json!([{ "name": "Annie" }]));
+    }
+}
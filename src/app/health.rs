@@ -0,0 +1,139 @@
+use std::sync::{Arc, OnceLock};
+
+use axum::{
+    Json, Router,
+    extract::{Request, State},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use reqwest::StatusCode;
+use serde::Serialize;
+use tower::ServiceExt;
+
+use crate::cdrag::Status;
+
+use super::AppState;
+
+/// Whether the app has finished starting. The port is bound before the data is synced, so
+/// until then every page is a 503 and only the health checks answer.
+#[derive(Debug, Clone, Default)]
+pub struct Readiness {
+    app: Arc<OnceLock<(AppState, Router)>>,
+}
+
+impl Readiness {
+    /// Starts serving `app`. Only the first call has any effect.
+    pub fn ready(&self, state: AppState, app: Router) {
+        if self.app.set((state, app)).is_err() {
+            tracing::warn!("the app was already serving");
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.app.get().is_some()
+    }
+
+    /// The app's state, once it's ready
+    pub fn state(&self) -> Option<&AppState> {
+        self.app.get().map(|(state, _)| state)
+    }
+}
+
+/// `/healthz` and `/readyz`, with everything else passed to the app once it's ready
+pub fn router<S>(readiness: Readiness) -> Router<S> {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .fallback(forward)
+        .with_state(readiness)
+}
+
+/// Liveness: the process is up and answering, whether or not it's ready
+async fn healthz() -> &'static str {
+    "ok"
+}
+
+#[derive(Debug, Serialize)]
+struct ReadyReport {
+    ready: bool,
+    status: Status,
+    champions: usize,
+}
+
+/// Readiness: the champion data is loaded and pages can be served
+async fn readyz(State(readiness): State<Readiness>) -> impl IntoResponse {
+    let Some(state) = readiness.state() else {
+        let report = ReadyReport {
+            ready: false,
+            status: Status::Uninitialized,
+            champions: 0,
+        };
+        return (StatusCode::SERVICE_UNAVAILABLE, Json(report));
+    };
+    let report = ReadyReport {
+        ready: true,
+        status: state.cdrag.current_status(),
+        champions: state.cdrag.champions.len(),
+    };
+    (StatusCode::OK, Json(report))
+}
+
+async fn forward(State(readiness): State<Readiness>, request: Request) -> Response {
+    let Some((_, app)) = readiness.app.get() else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [("Retry-After", "5")],
+            "Fourth Shot is still syncing with CommunityDragon",
+        )
+            .into_response();
+    };
+    match app.clone().oneshot(request).await {
+        Ok(response) => response,
+        Err(infallible) => match infallible {},
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::app::fixtures::state;
+    use axum::body::Body;
+    use rstest::*;
+
+    async fn fetch(readiness: &Readiness, uri: &str) -> (StatusCode, String) {
+        let res = router::<()>(readiness.clone())
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = res.status();
+        let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn serves_the_app_once_ready(state: AppState) {
+        let readiness = Readiness::default();
+        assert_eq!(fetch(&readiness, "/healthz").await.0, StatusCode::OK);
+        let (status, body) = fetch(&readiness, "/readyz").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(body.contains(r#""ready":false"#), "{body}");
+        assert_eq!(
+            fetch(&readiness, "/app").await.0,
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+
+        let app = Router::new().route("/app", get(|| async { "app" }));
+        readiness.ready(state, app);
+        let (status, body) = fetch(&readiness, "/readyz").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains(r#""champions":2"#), "{body}");
+        assert_eq!(
+            fetch(&readiness, "/app").await,
+            (StatusCode::OK, "app".into())
+        );
+        assert_eq!(fetch(&readiness, "/healthz").await.0, StatusCode::OK);
+    }
+}
@@ -0,0 +1,20 @@
+use askama::Template;
+use axum::{
+    Router,
+    response::{Html, IntoResponse},
+    routing::get,
+};
+
+use super::AppError;
+
+pub fn router<S: Clone + Send + Sync + 'static>() -> Router<S> {
+    Router::new().route("/", get(home))
+}
+
+#[derive(Template)]
+#[template(path = "home.html")]
+struct IndexTemplate {}
+
+async fn home() -> Result<impl IntoResponse, AppError> {
+    Ok(Html(IndexTemplate {}.render()?))
+}
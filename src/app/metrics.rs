@@ -0,0 +1,153 @@
+use std::time::Instant;
+
+use axum::{
+    Router,
+    extract::{MatchedPath, Request, State},
+    http::{Method, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::get,
+};
+
+use crate::metrics::{METRICS, RequestLabels, RouteLabels};
+
+use super::{AppError, health::Readiness};
+
+/// `/metrics` for Prometheus. It answers while the app is still starting, without the dataset.
+pub fn router<S>(readiness: Readiness) -> Router<S> {
+    Router::new()
+        .route("/metrics", get(metrics))
+        .with_state(readiness)
+}
+
+async fn metrics(State(readiness): State<Readiness>) -> Result<impl IntoResponse, AppError> {
+    if let Some(state) = readiness.state() {
+        let cdrag = &state.cdrag;
+        let skins: usize = cdrag
+            .champions
+            .values()
+            .map(|champ| champ.skins.len())
+            .sum();
+        METRICS.champions.set(cdrag.champions.len() as i64);
+        METRICS.skins.set(skins as i64);
+        METRICS.skin_lines.set(cdrag.skin_lines.len() as i64);
+        if let Some(store) = cdrag.store() {
+            let usage = store.usage()?;
+            METRICS.asset_files.set(usage.files as i64);
+            METRICS.asset_bytes.set(usage.bytes as i64);
+        }
+    }
+    Ok((
+        [(
+            header::CONTENT_TYPE,
+            "application/openmetrics-text; version=1.0.0; charset=utf-8",
+        )],
+        METRICS.encode(),
+    ))
+}
+
+/// Counts and times every request by its route, not its URI, so there's a series per page
+/// rather than per champion
+pub async fn track_requests(request: Request, next: Next) -> Response {
+    let method = method_label(request.method()).to_string();
+    let path = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", MatchedPath::as_str)
+        .to_string();
+    let start = Instant::now();
+    let response = next.run(request).await;
+    METRICS
+        .request_duration
+        .get_or_create(&RouteLabels {
+            method: method.clone(),
+            path: path.clone(),
+        })
+        .observe(start.elapsed().as_secs_f64());
+    METRICS
+        .requests
+        .get_or_create(&RequestLabels {
+            method,
+            path,
+            status: response.status().as_u16(),
+        })
+        .inc();
+    response
+}
+
+/// The method as a label. Clients can send any token as a method, so anything nonstandard is
+/// `other` rather than a series of its own.
+fn method_label(method: &Method) -> &'static str {
+    match *method {
+        Method::GET => "GET",
+        Method::HEAD => "HEAD",
+        Method::POST => "POST",
+        Method::PUT => "PUT",
+        Method::DELETE => "DELETE",
+        Method::CONNECT => "CONNECT",
+        Method::OPTIONS => "OPTIONS",
+        Method::TRACE => "TRACE",
+        Method::PATCH => "PATCH",
+        _ => "other",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::app::{AppState, champions, fixtures::state};
+    use axum::{body::Body, middleware};
+    use rstest::*;
+    use tower::ServiceExt;
+
+    async fn scrape(readiness: &Readiness) -> String {
+        let res = router::<()>(readiness.clone())
+            .oneshot(Request::get("/metrics").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert!(res.status().is_success());
+        let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[rstest]
+    #[case(b"GET", "GET")]
+    #[case(b"PATCH", "PATCH")]
+    #[case(b"BREW", "other")]
+    #[case(b"get", "other")]
+    fn nonstandard_methods_share_a_label(#[case] method: &[u8], #[case] expected: &str) {
+        assert_eq!(method_label(&Method::from_bytes(method).unwrap()), expected);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn counts_requests_by_route(state: AppState) {
+        let readiness = Readiness::default();
+        assert!(scrape(&readiness).await.contains("# EOF"));
+
+        let app = champions::router::<()>(state.clone()).layer(middleware::from_fn(track_requests));
+        for uri in ["/champions/kaisa", "/champions/annie"] {
+            app.clone()
+                .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+        }
+        readiness.ready(state, Router::new());
+
+        let text = scrape(&readiness).await;
+        let requests = text
+            .lines()
+            .find(|line| {
+                line.starts_with("fourth_shot_http_requests_total{")
+                    && line.contains(r#"path="/champions/{key}""#)
+            })
+            .unwrap_or_else(|| panic!("{text}"));
+        // Other tests run in the same process, so only a lower bound is certain
+        let count: f64 = requests.rsplit(' ').next().unwrap().parse().unwrap();
+        assert!(count >= 2.0, "{requests}");
+        assert!(text.contains("fourth_shot_champions 2"), "{text}");
+        assert!(text.contains("fourth_shot_skins 4"), "{text}");
+    }
+}
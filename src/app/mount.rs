@@ -0,0 +1,252 @@
+use axum::{
+    Router,
+    extract::{Request, State},
+    http::{HeaderMap, header},
+    middleware::{self, Next},
+    response::{Redirect, Response},
+    routing::get,
+};
+
+use crate::config::Config;
+
+/// Where the app is served from, as browsers see it
+#[derive(Debug, Clone, Default)]
+pub struct Mount {
+    /// e.g. `/fourth-shot`, or `""` at the root
+    pub prefix: String,
+    /// Whether to believe `Forwarded` and `X-Forwarded-*`
+    pub behind_proxy: bool,
+    /// Whether this process terminates TLS itself
+    pub https: bool,
+}
+
+impl From<&Config> for Mount {
+    fn from(config: &Config) -> Self {
+        Mount {
+            prefix: config.prefix().to_string(),
+            behind_proxy: config.behind_proxy,
+            https: config.tls.is_some(),
+        }
+    }
+}
+
+/// The request's external location, for [`path`] and [`absolute`]
+#[derive(Debug)]
+struct External {
+    /// e.g. `https://example.com`, when the request said which host it was for
+    origin: Option<String>,
+    prefix: String,
+}
+
+tokio::task_local! {
+    static EXTERNAL: External;
+}
+
+/// Serves `app` under the mount's prefix, with [`path`] and [`absolute`] available to its
+/// handlers and templates. The root redirects to the app's home page, at the prefix.
+pub fn router(mount: Mount, app: Router) -> Router {
+    let router = if mount.prefix.is_empty() {
+        app
+    } else {
+        // A nested service, unlike a nested router, gets `/` for both `/prefix` and
+        // `/prefix/`, and keeps the fallbacks serving `/assets` and `/cdrag-assets`
+        Router::new()
+            .nest_service(&mount.prefix, app)
+            .route("/", get(|| async { Redirect::temporary(&absolute("/")) }))
+    };
+    router.layer(middleware::from_fn_with_state(mount, scope))
+}
+
+async fn scope(State(mount): State<Mount>, request: Request, next: Next) -> Response {
+    let external = External {
+        origin: origin(&mount, &request),
+        prefix: mount.prefix,
+    };
+    EXTERNAL.scope(external, next.run(request)).await
+}
+
+/// `path` under the prefix the app is mounted at, e.g. `/fourth-shot/collections` for
+/// `/collections`. Outside a request it's `path` unchanged.
+pub fn path(path: &str) -> String {
+    EXTERNAL
+        .try_with(|external| format!("{}{path}", external.prefix))
+        .unwrap_or_else(|_| path.to_string())
+}
+
+/// [`path`] with the scheme and host the request was made to, for redirects and documents
+/// used away from the page, like the OpenAPI spec
+pub fn absolute(path: &str) -> String {
+    EXTERNAL
+        .try_with(|external| {
+            let origin = external.origin.as_deref().unwrap_or_default();
+            format!("{origin}{}{path}", external.prefix)
+        })
+        .unwrap_or_else(|_| path.to_string())
+}
+
+/// The scheme and host the client used. Behind a proxy they come from the RFC 7239
+/// `Forwarded` header, or the older `X-Forwarded-Proto` and `X-Forwarded-Host`; either way
+/// the proxy nearest the client, the first listed, is the one that counts.
+fn origin(mount: &Mount, request: &Request) -> Option<String> {
+    let headers = request.headers();
+    let (mut scheme, mut host) = (None, None);
+    if mount.behind_proxy {
+        (scheme, host) = forwarded(headers);
+        scheme = scheme.or_else(|| first(headers, "x-forwarded-proto"));
+        host = host.or_else(|| first(headers, "x-forwarded-host"));
+    }
+    let scheme = scheme
+        .map(|scheme| scheme.to_ascii_lowercase())
+        .filter(|scheme| matches!(scheme.as_str(), "http" | "https"))
+        .unwrap_or_else(|| if mount.https { "https" } else { "http" }.to_string());
+    let host = host
+        .filter(|host| is_host(host))
+        .or_else(|| {
+            request
+                .uri()
+                .authority()
+                .map(|authority| authority.to_string())
+        })
+        .or_else(|| first(headers, header::HOST.as_str()))
+        .filter(|host| is_host(host))?;
+    Some(format!("{scheme}://{host}"))
+}
+
+/// `proto` and `host` from the first element of `Forwarded`, e.g.
+/// `for=192.0.2.60;proto=https;host=example.com, for=198.51.100.17`
+fn forwarded(headers: &HeaderMap) -> (Option<String>, Option<String>) {
+    let (mut proto, mut host) = (None, None);
+    let Some(element) = first(headers, header::FORWARDED.as_str()) else {
+        return (proto, host);
+    };
+    for pair in element.split(';') {
+        let Some((name, value)) = pair.split_once('=') else {
+            continue;
+        };
+        let value = value.trim().trim_matches('"').to_string();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "proto" => proto = Some(value),
+            "host" => host = Some(value),
+            _ => {}
+        }
+    }
+    (proto, host)
+}
+
+/// The first comma-separated value of the first `name` header
+fn first(headers: &HeaderMap, name: &str) -> Option<String> {
+    let value = headers.get(name)?.to_str().ok()?;
+    let first = value.split(',').next()?.trim();
+    (!first.is_empty()).then(|| first.to_string())
+}
+
+/// Whether `host` is a hostname or IP address with an optional port, and nothing that could
+/// smuggle a path or another header into a URL
+fn is_host(host: &str) -> bool {
+    !host.is_empty()
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | ':' | '[' | ']'))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use axum::{body::Body, http::StatusCode};
+    use rstest::*;
+    use tower::ServiceExt;
+
+    fn app(mount: Mount) -> Router {
+        let links = || async { format!("{} {}", path("/collections"), absolute("/collections")) };
+        let app = Router::new()
+            .route("/", get(|| async { "home" }))
+            .route("/links", get(links));
+        router(mount, app)
+    }
+
+    async fn send(app: Router, request: Request) -> (StatusCode, String) {
+        let res = app.oneshot(request).await.unwrap();
+        let status = res.status();
+        if let Some(location) = res.headers().get(header::LOCATION) {
+            return (status, location.to_str().unwrap().to_string());
+        }
+        let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[rstest]
+    #[case(false, &[], "http://app.local/p/collections")]
+    #[case(false, &[("x-forwarded-proto", "https")], "http://app.local/p/collections")]
+    #[case(true, &[("x-forwarded-proto", "https")], "https://app.local/p/collections")]
+    #[case(
+        true,
+        &[("x-forwarded-proto", "https, http"), ("x-forwarded-host", "example.com")],
+        "https://example.com/p/collections"
+    )]
+    #[case(
+        true,
+        &[
+            ("forwarded", "for=192.0.2.60;Proto=https;host=\"example.com:8443\", proto=http"),
+            ("x-forwarded-host", "ignored.example"),
+        ],
+        "https://example.com:8443/p/collections"
+    )]
+    #[case(true, &[("x-forwarded-host", "evil.example/phish?")], "http://app.local/p/collections")]
+    #[tokio::test]
+    async fn links_follow_the_proxy(
+        #[case] behind_proxy: bool,
+        #[case] headers: &[(&str, &str)],
+        #[case] expected: &str,
+    ) {
+        let mount = Mount {
+            prefix: "/p".into(),
+            behind_proxy,
+            https: false,
+        };
+        let mut request = Request::get("/p/links").header(header::HOST, "app.local");
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        let (status, body) = send(app(mount), request.body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, format!("/p/collections {expected}"));
+    }
+
+    #[rstest]
+    #[case(
+        "",
+        "/links",
+        StatusCode::OK,
+        "/collections http://app.local/collections"
+    )]
+    #[case("/p", "/links", StatusCode::NOT_FOUND, "")]
+    #[case("/p", "/", StatusCode::TEMPORARY_REDIRECT, "http://app.local/p/")]
+    #[case("/p", "/p", StatusCode::OK, "home")]
+    #[case("/p", "/p/", StatusCode::OK, "home")]
+    #[case("/p", "/pp/links", StatusCode::NOT_FOUND, "")]
+    #[tokio::test]
+    async fn serves_under_the_prefix(
+        #[case] prefix: &str,
+        #[case] uri: &str,
+        #[case] status: StatusCode,
+        #[case] expected: &str,
+    ) {
+        let mount = Mount {
+            prefix: prefix.into(),
+            ..Default::default()
+        };
+        let request = Request::get(uri)
+            .header(header::HOST, "app.local")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(send(app(mount), request).await, (status, expected.into()));
+    }
+
+    #[test]
+    fn paths_are_unchanged_outside_requests() {
+        assert_eq!(path("/assets/main.css"), "/assets/main.css");
+        assert_eq!(absolute("/graphql"), "/graphql");
+    }
+}
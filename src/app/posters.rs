@@ -0,0 +1,171 @@
+use anyhow::Context;
+use axum::{
+    Router,
+    extract::{Path, Query, State},
+    http::header,
+    response::IntoResponse,
+    routing::get,
+};
+use serde::Deserialize;
+
+use crate::{
+    cdrag::SkinAsset,
+    imaging::{
+        focal::FocalPoint,
+        overlay,
+        poster::{Poster, PosterCell, PosterFormat},
+    },
+};
+
+use super::{AppError, AppState};
+
+/// Wide enough for most skin names at a readable size
+const DEFAULT_CELL_WIDTH: u32 = 320;
+
+pub fn router<S>(state: AppState) -> Router<S> {
+    Router::new()
+        .route("/champions/{key}/poster", get(champion_poster))
+        .with_state(state)
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PosterQuery {
+    asset: Option<SkinAsset>,
+    columns: Option<u32>,
+    cell_width: Option<u32>,
+    format: Option<PosterFormat>,
+}
+
+/// Every skin of a champion with the asset, on one sheet. Missing assets are downloaded.
+async fn champion_poster(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+    Query(query): Query<PosterQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let champ = state
+        .cdrag
+        .champion_by_key(&key)
+        .ok_or(AppError::NotFound)?;
+    let poster = Poster {
+        title: champ.name.clone(),
+        subtitle: champ.title.clone(),
+        asset: query.asset.unwrap_or(SkinAsset::Tile),
+        columns: query.columns,
+        cell_width: query.cell_width.unwrap_or(DEFAULT_CELL_WIDTH),
+        format: query.format.unwrap_or_default(),
+    };
+
+    let mut sources = vec![];
+    for skin in &champ.skins {
+        let asset_path = state.cdrag.skin_path_of(skin, &poster.asset)?;
+        if asset_path.as_os_str().is_empty() {
+            continue;
+        }
+        let focal = match poster.asset {
+            SkinAsset::UncenteredSplash => state.focal_point(skin)?.0,
+            _ => FocalPoint::default(),
+        };
+        sources.push((skin, asset_path, focal));
+    }
+    poster.check(sources.len()).map_err(AppError::BadRequest)?;
+
+    let mut cells = Vec::with_capacity(sources.len());
+    for (skin, asset_path, focal) in sources {
+        let path = state.cdrag.local_asset(&asset_path).await?;
+        let badges = overlay::badges(&skin.rarity, skin.is_legacy);
+        cells.push((path, focal, skin.name.clone(), badges));
+    }
+    let render = poster.clone();
+    let bytes = tokio::task::spawn_blocking(move || {
+        let cells = cells
+            .into_iter()
+            .map(|(path, focal, name, badges)| {
+                let image = image::open(&path)
+                    .with_context(|| format!("couldn't read the asset at {path:?}"))?;
+                anyhow::Ok(PosterCell {
+                    image,
+                    focal,
+                    name,
+                    badges,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        render.render(&cells)
+    })
+    .await
+    .context("rendering the poster panicked")??;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, poster.format.mime_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "inline; filename=\"{}-poster.{}\"",
+                    champ.slug(),
+                    poster.format.extension()
+                ),
+            ),
+        ],
+        bytes,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::app::fixtures::disk_state;
+    use axum::{body::Body, http::Request};
+    use reqwest::StatusCode;
+    use rstest::*;
+    use std::fs;
+    use tower::ServiceExt;
+
+    #[rstest]
+    #[case(
+        "/champions/kaisa/poster?asset=uncentered_splash&cell_width=160",
+        StatusCode::OK,
+        "image/png"
+    )]
+    #[case(
+        "/champions/kaisa/poster?asset=uncentered_splash&cell_width=160&format=pdf",
+        StatusCode::OK,
+        "application/pdf"
+    )]
+    #[case("/champions/kaisa/poster", StatusCode::BAD_REQUEST, "")]
+    #[case(
+        "/champions/kaisa/poster?asset=uncentered_splash&cell_width=4000",
+        StatusCode::BAD_REQUEST,
+        ""
+    )]
+    #[case("/champions/teemo/poster", StatusCode::NOT_FOUND, "")]
+    #[tokio::test]
+    async fn lays_out_a_champions_skins(
+        disk_state: (AppState, tempfile::TempDir),
+        #[case] uri: &str,
+        #[case] expected: StatusCode,
+        #[case] content_type: &str,
+    ) {
+        let (state, _dir) = disk_state;
+        // Only the Bullet Angel skin has a splash in the fixture, and none have tiles
+        let skin = state.cdrag.skin_by_id(145001).unwrap();
+        let path = state.cdrag.data_dir.join(&skin.uncentered_splash_path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        image::RgbImage::from_pixel(320, 180, image::Rgb([200, 40, 40]))
+            .save(path)
+            .unwrap();
+
+        let res = router::<()>(state)
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), expected);
+        if expected == StatusCode::OK {
+            assert_eq!(res.headers()[header::CONTENT_TYPE], content_type);
+            let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert!(!bytes.is_empty());
+        }
+    }
+}
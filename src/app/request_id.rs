@@ -0,0 +1,71 @@
+use axum::{extract::Request, middleware::Next, response::Response};
+use tower_http::request_id::RequestId;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Makes the request's `X-Request-Id`, set by tower-http's `SetRequestIdLayer`, available to
+/// [`current`] for as long as the request is handled
+pub async fn scope(request: Request, next: Next) -> Response {
+    let id = request
+        .extensions()
+        .get::<RequestId>()
+        .and_then(|id| id.header_value().to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    REQUEST_ID.scope(id, next.run(request)).await
+}
+
+/// The id of the request being handled, if there is one
+pub fn current() -> Option<String> {
+    REQUEST_ID
+        .try_with(Clone::clone)
+        .ok()
+        .filter(|id| !id.is_empty())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::app::AppError;
+    use axum::{Router, body::Body, middleware, routing::get};
+    use rstest::*;
+    use tower::ServiceExt;
+    use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+
+    async fn fail() -> Result<(), AppError> {
+        Err(anyhow::anyhow!("upstream broke").into())
+    }
+
+    #[rstest]
+    #[case(Some("abc-123"))]
+    #[case(None)]
+    #[tokio::test]
+    async fn error_pages_show_the_request_id(#[case] sent: Option<&str>) {
+        let app = Router::new()
+            .route("/fail", get(fail))
+            .layer(middleware::from_fn(scope))
+            .layer(PropagateRequestIdLayer::x_request_id())
+            .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid));
+        let mut request = Request::get("/fail");
+        if let Some(sent) = sent {
+            request = request.header("x-request-id", sent);
+        }
+        let res = app
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let id = res.headers()["x-request-id"].to_str().unwrap().to_string();
+        assert!(!id.is_empty());
+        if let Some(sent) = sent {
+            assert_eq!(id, sent);
+        }
+        let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains(&id), "{body}");
+        assert_eq!(current(), None);
+    }
+}
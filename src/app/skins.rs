@@ -0,0 +1,257 @@
+use askama::Template;
+use axum::{
+    Router,
+    extract::{Query, State},
+    middleware,
+    response::{Html, IntoResponse},
+    routing::get,
+};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::{
+    cdrag::{Skin, SkinLine},
+    imaging::{palette::Color, phash::NEAR_DUPLICATE_DISTANCE},
+};
+
+use super::{AppError, AppState, caching};
+
+/// How many colors a skin line's card shows
+const SKIN_LINE_COLORS: usize = 3;
+/// Distance (see [`Color::distance`]) within which a skin matches a searched color by default
+pub const DEFAULT_MAX_DISTANCE: f32 = 100.0;
+
+pub fn router<S>(state: AppState) -> Router<S> {
+    Router::new()
+        .route("/skin-lines", get(skin_lines_grid))
+        .route("/skins", get(skins_by_color))
+        .route("/skins/duplicates", get(duplicates))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            caching::revalidate,
+        ))
+        .with_state(state)
+}
+
+/// Search parameters for finding skins by one of their palette colors, shared by the skins
+/// grid and the JSON API
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ColorQuery {
+    /// `#rrggbb`, with or without the `#`
+    pub color: String,
+    /// How far a palette color may be from `color`, from 0 (identical) to about 765. Defaults
+    /// to 100.
+    pub max_distance: Option<f32>,
+}
+
+impl ColorQuery {
+    /// Skins with a palette color close to the searched one, closest first
+    pub fn apply<'a>(&self, state: &'a AppState) -> Result<Vec<&'a Skin>, AppError> {
+        let color: Color = self.color.parse().map_err(AppError::BadRequest)?;
+        let max_distance = self.max_distance.unwrap_or(DEFAULT_MAX_DISTANCE);
+        Ok(state.cdrag.skins_by_color(color, max_distance))
+    }
+}
+
+/// A skin line resolved for its card on the home page
+struct SkinLineCard {
+    skin_line: SkinLine,
+    champions: Vec<String>,
+    colors: Vec<Color>,
+    image_path: String,
+}
+
+#[derive(Template)]
+#[template(path = "skin_lines_grid.html")]
+struct SkinLinesGridTemplate {
+    skin_lines: Vec<SkinLineCard>,
+}
+
+async fn skin_lines_grid(State(state): State<AppState>) -> Result<impl IntoResponse, AppError> {
+    let mut skin_lines: Vec<SkinLineCard> = state
+        .cdrag
+        .skin_lines
+        .values()
+        .filter_map(|skin_line| {
+            let skins = state.cdrag.skins_in_line(skin_line.id);
+            let mut champions: Vec<String> = skins
+                .iter()
+                .filter_map(|skin| state.cdrag.champion_by_id(skin.id / 1000))
+                .map(|champ| champ.name.clone())
+                .collect();
+            champions.sort();
+            champions.dedup();
+            Some(SkinLineCard {
+                image_path: skins.first()?.uncentered_splash_path.clone(),
+                colors: state
+                    .cdrag
+                    .skin_line_palette(skin_line.id, SKIN_LINE_COLORS),
+                champions,
+                skin_line: skin_line.clone(),
+            })
+        })
+        .collect();
+    skin_lines.sort_by(|a, b| a.skin_line.name.cmp(&b.skin_line.name));
+    Ok(Html(SkinLinesGridTemplate { skin_lines }.render()?))
+}
+
+/// A skin along with the champion page it links to
+struct SkinCard<'a> {
+    skin: &'a Skin,
+    champion_slug: String,
+}
+
+#[derive(Template)]
+#[template(path = "skins_grid.html")]
+struct SkinsGridTemplate<'a> {
+    color: Color,
+    skins: Vec<SkinCard<'a>>,
+}
+
+async fn skins_by_color(
+    State(state): State<AppState>,
+    Query(query): Query<ColorQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let color = query.color.parse().map_err(AppError::BadRequest)?;
+    let skins = query
+        .apply(&state)?
+        .into_iter()
+        .filter_map(|skin| {
+            let champ = state.cdrag.champion_by_id(skin.id / 1000)?;
+            Some(SkinCard {
+                skin,
+                champion_slug: champ.slug(),
+            })
+        })
+        .collect();
+    Ok(Html(SkinsGridTemplate { color, skins }.render()?))
+}
+
+#[derive(Debug, Deserialize)]
+struct DuplicatesQuery {
+    /// See [`crate::imaging::phash::PerceptualHash::distance`]
+    max_distance: Option<u32>,
+}
+
+/// Skins sharing one splash, and how far apart the least alike two of them are
+struct DuplicateGroup<'a> {
+    skins: Vec<SkinCard<'a>>,
+    spread: u32,
+}
+
+#[derive(Template)]
+#[template(path = "duplicates.html")]
+struct DuplicatesTemplate<'a> {
+    max_distance: u32,
+    groups: Vec<DuplicateGroup<'a>>,
+}
+
+/// Groups of skins whose uncentered splashes are the same art, such as every champion in a
+/// group splash
+async fn duplicates(
+    State(state): State<AppState>,
+    Query(query): Query<DuplicatesQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let max_distance = query
+        .max_distance
+        .unwrap_or(NEAR_DUPLICATE_DISTANCE)
+        .min(64);
+    let groups = state
+        .cdrag
+        .duplicate_groups(max_distance)
+        .into_iter()
+        .map(|skins| {
+            let hashes: Vec<_> = skins.iter().filter_map(|skin| skin.phash).collect();
+            let spread = hashes
+                .iter()
+                .flat_map(|a| hashes.iter().map(|b| a.distance(*b)))
+                .max()
+                .unwrap_or_default();
+            let skins = skins
+                .into_iter()
+                .filter_map(|skin| {
+                    let champ = state.cdrag.champion_by_id(skin.id / 1000)?;
+                    Some(SkinCard {
+                        skin,
+                        champion_slug: champ.slug(),
+                    })
+                })
+                .collect();
+            DuplicateGroup { skins, spread }
+        })
+        .collect();
+    Ok(Html(
+        DuplicatesTemplate {
+            max_distance,
+            groups,
+        }
+        .render()?,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::app::fixtures::state;
+    use axum::{body::Body, http::Request};
+    use reqwest::StatusCode;
+    use rstest::*;
+    use tower::ServiceExt;
+
+    async fn get(state: AppState, uri: &str) -> (StatusCode, String) {
+        let res = router::<()>(state)
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = res.status();
+        let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(bytes.to_vec()).unwrap())
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn skin_line_cards_use_computed_colors(state: AppState) {
+        let (status, body) = get(state, "/skin-lines").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("Annie, Kai&#39;Sa") || body.contains("Annie, Kai&#x27;Sa"));
+        assert!(body.contains("background-color: #e02020"));
+    }
+
+    #[rstest]
+    #[case("/skins?color=%23ff0000", Some("Bullet Angel Annie"))]
+    #[case("/skins?color=2040a8", Some("Bullet Angel Kai"))]
+    #[case("/skins?color=%2300ff00", None)]
+    #[tokio::test]
+    async fn finds_skins_by_color(
+        state: AppState,
+        #[case] uri: &str,
+        #[case] expected: Option<&str>,
+    ) {
+        let (status, body) = get(state, uri).await;
+        assert_eq!(status, StatusCode::OK);
+        match expected {
+            Some(name) => {
+                assert_eq!(body.matches("<h3").count(), 1);
+                assert!(body.contains(name));
+            }
+            None => assert!(body.contains("No skins close to #00ff00")),
+        }
+    }
+
+    #[rstest]
+    #[case("/skins/duplicates", 1)]
+    #[case("/skins/duplicates?max_distance=0", 0)]
+    #[tokio::test]
+    async fn groups_shared_splashes(state: AppState, #[case] uri: &str, #[case] groups: usize) {
+        let (status, body) = get(state, uri).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.matches("data-duplicate-group").count(), groups);
+        if groups > 0 {
+            assert!(body.contains("Bullet Angel Annie"));
+            assert!(body.contains("Bullet Angel Kai"));
+        }
+    }
+}
@@ -0,0 +1,242 @@
+use std::{
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::PathBuf,
+};
+
+use anyhow::Context;
+use axum::{
+    Router,
+    extract::{Path, Query, State},
+    http::header,
+    response::IntoResponse,
+    routing::get,
+};
+use serde::Deserialize;
+
+use crate::{
+    cdrag::Skin,
+    imaging::slideshow::{self, AnimationFormat, Slide, Slideshow, Transition},
+};
+
+use super::{AppError, AppState, caching};
+
+pub const SLIDESHOWS_DIR: &str = "slideshows";
+
+pub fn router<S>(state: AppState) -> Router<S> {
+    Router::new()
+        .route("/champions/{key}/slideshow", get(champion_slideshow))
+        .route("/skin-lines/{id}/slideshow", get(skin_line_slideshow))
+        .with_state(state)
+}
+
+/// A [`Slideshow`] with every field optional, falling back to its defaults
+#[derive(Debug, Default, Deserialize)]
+struct SlideshowQuery {
+    format: Option<AnimationFormat>,
+    transition: Option<Transition>,
+    #[serde(rename = "w")]
+    width: Option<u32>,
+    #[serde(rename = "h")]
+    height: Option<u32>,
+    hold_ms: Option<u32>,
+    fade_ms: Option<u32>,
+}
+
+impl SlideshowQuery {
+    fn slideshow(&self) -> Slideshow {
+        let default = Slideshow::default();
+        Slideshow {
+            width: self.width.unwrap_or(default.width),
+            height: self.height.unwrap_or(default.height),
+            transition: self.transition.unwrap_or(default.transition),
+            format: self.format.unwrap_or(default.format),
+            hold_ms: self.hold_ms.unwrap_or(default.hold_ms),
+            fade_ms: self.fade_ms.unwrap_or(default.fade_ms),
+        }
+    }
+}
+
+async fn champion_slideshow(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+    Query(query): Query<SlideshowQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let champ = state
+        .cdrag
+        .champion_by_key(&key)
+        .ok_or(AppError::NotFound)?;
+    let skins: Vec<&Skin> = champ.skins.iter().collect();
+    let name = champ.slug();
+    serve(
+        &state,
+        "champions",
+        champ.id,
+        &name,
+        &skins,
+        query.slideshow(),
+    )
+    .await
+}
+
+async fn skin_line_slideshow(
+    State(state): State<AppState>,
+    Path(id): Path<u64>,
+    Query(query): Query<SlideshowQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let skin_line = state.cdrag.skin_line_by_id(id).ok_or(AppError::NotFound)?;
+    let skins = state.cdrag.skins_in_line(id);
+    let name = crate::cdrag::slug(&skin_line.name);
+    serve(&state, "skin-lines", id, &name, &skins, query.slideshow()).await
+}
+
+/// Renders a slideshow of whichever of the skins have their uncentered splash in `data_dir`,
+/// or serves it from the cache if those splashes, their focal points and the settings haven't
+/// changed since it was last rendered. Nothing is downloaded.
+async fn serve(
+    state: &AppState,
+    kind: &str,
+    id: u64,
+    name: &str,
+    skins: &[&Skin],
+    slideshow: Slideshow,
+) -> Result<impl IntoResponse + use<>, AppError> {
+    let mut sources = vec![];
+    for skin in skins {
+        let path = state.cdrag.data_dir.join(&skin.uncentered_splash_path);
+        if !skin.uncentered_splash_path.is_empty() && path.is_file() {
+            sources.push((path, state.focal_point(skin)?.0));
+        }
+    }
+    slideshow
+        .check(sources.len())
+        .map_err(AppError::BadRequest)?;
+
+    let mut hasher = DefaultHasher::new();
+    for (path, focal) in &sources {
+        (path, focal.x.to_bits(), focal.y.to_bits()).hash(&mut hasher);
+    }
+    format!("{slideshow:?}").hash(&mut hasher);
+    let cached: PathBuf = state
+        .cdrag
+        .data_dir
+        .join(SLIDESHOWS_DIR)
+        .join(kind)
+        .join(format!(
+            "{id}_{:016x}.{}",
+            hasher.finish(),
+            slideshow.format.extension()
+        ));
+
+    let bytes = match fs::read(&cached) {
+        Ok(bytes) => bytes,
+        Err(_) => {
+            let bytes = tokio::task::spawn_blocking(move || {
+                let slides = sources
+                    .into_iter()
+                    .map(|(path, focal)| {
+                        let image = image::open(&path)
+                            .with_context(|| format!("couldn't read the splash at {path:?}"))?;
+                        anyhow::Ok(Slide {
+                            image: slideshow::shrink(image, &slideshow),
+                            focal,
+                        })
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                slideshow.render(&slides)
+            })
+            .await
+            .context("rendering the slideshow panicked")??;
+            let dir = cached.parent().expect("slideshows live in a directory");
+            fs::create_dir_all(dir).with_context(|| format!("couldn't create {dir:?}"))?;
+            // A previous render for the same champion or skin line is stale now
+            if let Ok(entries) = fs::read_dir(dir) {
+                let prefix = format!("{id}_");
+                for entry in entries.flatten() {
+                    if entry.file_name().to_string_lossy().starts_with(&prefix) {
+                        fs::remove_file(entry.path()).ok();
+                    }
+                }
+            }
+            caching::write_cached(&cached, &bytes).context("couldn't cache the slideshow")?;
+            bytes
+        }
+    };
+    Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                slideshow.format.mime_type().to_string(),
+            ),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "inline; filename=\"{name}-slideshow.{}\"",
+                    slideshow.format.extension()
+                ),
+            ),
+        ],
+        bytes,
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::app::fixtures::disk_state;
+    use axum::{body::Body, http::Request};
+    use reqwest::StatusCode;
+    use rstest::*;
+    use tower::ServiceExt;
+
+    #[rstest]
+    #[case("/champions/kaisa/slideshow?w=160&h=90", StatusCode::OK)]
+    #[case(
+        "/skin-lines/7/slideshow?format=apng&transition=ken_burns&w=64&h=64&hold_ms=300",
+        StatusCode::OK
+    )]
+    #[case("/champions/kaisa/slideshow?w=4000", StatusCode::BAD_REQUEST)]
+    #[case("/champions/kaisa/slideshow?format=webp", StatusCode::BAD_REQUEST)]
+    #[case("/champions/annie/slideshow", StatusCode::BAD_REQUEST)]
+    #[case("/skin-lines/8/slideshow", StatusCode::NOT_FOUND)]
+    #[tokio::test]
+    async fn animates_downloaded_splashes(
+        disk_state: (AppState, tempfile::TempDir),
+        #[case] uri: &str,
+        #[case] expected: StatusCode,
+    ) {
+        let (state, _dir) = disk_state;
+        // Only Kai'Sa's splash is downloaded, so Annie has nothing to show
+        let skin = state.cdrag.skin_by_id(145001).unwrap();
+        let path = state.cdrag.data_dir.join(&skin.uncentered_splash_path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        image::RgbImage::from_pixel(320, 180, image::Rgb([200, 40, 40]))
+            .save(path)
+            .unwrap();
+
+        let app = router::<()>(state.clone());
+        for attempt in 0..2 {
+            let res = app
+                .clone()
+                .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(res.status(), expected);
+            if expected != StatusCode::OK {
+                return;
+            }
+            let bytes = axum::body::to_bytes(res.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            assert!(matches!(
+                image::guess_format(&bytes).unwrap(),
+                image::ImageFormat::Gif | image::ImageFormat::Png
+            ));
+            let cached = fs::read_dir(state.cdrag.data_dir.join(SLIDESHOWS_DIR))
+                .unwrap()
+                .flat_map(|kind| fs::read_dir(kind.unwrap().path()).unwrap())
+                .count();
+            assert_eq!(cached, 1, "attempt {attempt}");
+        }
+    }
+}
@@ -0,0 +1,368 @@
+use std::{
+    fs,
+    path::{Path as FsPath, PathBuf},
+};
+
+use anyhow::Context;
+use askama::Template;
+use axum::{
+    Form, Router,
+    extract::{Path, Query, State},
+    http::{HeaderMap, header},
+    response::{Html, IntoResponse, Redirect},
+    routing::get,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::{
+    cdrag::{Skin, SkinAsset, slug},
+    imaging::{self, MAX_EDGE, MIN_EDGE, focal::FocalPoint, wallpaper::Preset},
+};
+
+use super::{AppError, AppState, caching, mount};
+
+pub const WALLPAPERS_DIR: &str = "wallpapers";
+
+pub fn router<S>(state: AppState) -> Router<S> {
+    Router::new()
+        .route("/champions/{key}/skins/{skin_id}/wallpaper", get(wallpaper))
+        .route(
+            "/champions/{key}/skins/{skin_id}/focal-point",
+            get(focal_point_page)
+                .post(set_focal_point)
+                .delete(reset_focal_point),
+        )
+        .with_state(state)
+}
+
+/// Where a skin's focal point came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema, strum::Display)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum FocalSource {
+    /// Picked by the user
+    Manual,
+    /// Estimated from the splash
+    Estimated,
+    /// The center, for splashes that haven't been analyzed
+    Default,
+}
+
+impl AppState {
+    /// The point crops of the skin's uncentered splash are centered on: the user's override if
+    /// there is one, otherwise the estimate
+    pub fn focal_point(&self, skin: &Skin) -> anyhow::Result<(FocalPoint, FocalSource)> {
+        if let Some(focal) = self.db.focal_override(skin.id)? {
+            return Ok((focal, FocalSource::Manual));
+        }
+        Ok(match skin.focal_point {
+            Some(focal) => (focal, FocalSource::Estimated),
+            None => (FocalPoint::default(), FocalSource::Default),
+        })
+    }
+}
+
+/// Finds a skin through its champion, like every other `/champions/{key}/skins/...` URL
+fn find_skin<'a>(state: &'a AppState, key: &str, skin_id: u64) -> Result<&'a Skin, AppError> {
+    state
+        .cdrag
+        .champion_by_key(key)
+        .and_then(|champ| champ.skins.iter().find(|skin| skin.id == skin_id))
+        .ok_or(AppError::NotFound)
+}
+
+/// Either an explicit `w`x`h`, or a `preset` shape sized by whichever of `w` and `h` is given.
+/// Without either, a 16:9 desktop wallpaper.
+#[derive(Debug, Default, Deserialize)]
+struct WallpaperQuery {
+    w: Option<u32>,
+    h: Option<u32>,
+    preset: Option<Preset>,
+    /// Focal point to keep in frame, in fractions of the splash's width and height. Defaults to
+    /// the skin's [`AppState::focal_point`].
+    fx: Option<f32>,
+    fy: Option<f32>,
+}
+
+impl WallpaperQuery {
+    fn size(&self) -> Result<(u32, u32), AppError> {
+        let (width, height) = self.preset.unwrap_or(Preset::Desktop).size(self.w, self.h);
+        let edges = MIN_EDGE..=MAX_EDGE;
+        if !edges.contains(&width) || !edges.contains(&height) {
+            return Err(AppError::BadRequest(format!(
+                "wallpapers must be between {MIN_EDGE} and {MAX_EDGE} pixels on each side"
+            )));
+        }
+        Ok((width, height))
+    }
+
+    /// Whether this is one of the presets at its default size, centered on the skin's own focal
+    /// point. Only those are cached, since any other size or focal point could be asked for.
+    fn is_cacheable(&self) -> bool {
+        self.w.is_none() && self.h.is_none() && self.fx.is_none() && self.fy.is_none()
+    }
+
+    /// The focal point rounded to the precision it's cached with
+    fn focal_point(&self, default: FocalPoint) -> Result<FocalPoint, AppError> {
+        let [x, y] = [self.fx.unwrap_or(default.x), self.fy.unwrap_or(default.y)].map(|v| {
+            (0.0..=1.0)
+                .contains(&v)
+                .then_some((v * 1000.0).round() / 1000.0)
+                .ok_or_else(|| AppError::BadRequest("fx and fy must be between 0 and 1".into()))
+        });
+        Ok(FocalPoint { x: x?, y: y? })
+    }
+}
+
+fn wallpaper_path(
+    state: &AppState,
+    skin_id: u64,
+    (width, height): (u32, u32),
+    focal: FocalPoint,
+) -> PathBuf {
+    state
+        .cdrag
+        .data_dir
+        .join(WALLPAPERS_DIR)
+        .join(skin_id.to_string())
+        .join(format!(
+            "{width}x{height}_{:.3}_{:.3}.jpg",
+            focal.x, focal.y
+        ))
+}
+
+/// A JPEG of the skin's uncentered splash cropped around the focal point to the requested
+/// size. [Presets](WallpaperQuery::is_cacheable) are rendered once and then served from disk;
+/// anything else is rendered every time.
+async fn wallpaper(
+    State(state): State<AppState>,
+    Path((key, skin_id)): Path<(String, u64)>,
+    Query(query): Query<WallpaperQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let skin = find_skin(&state, &key, skin_id)?;
+    let size = query.size()?;
+    let focal = query.focal_point(state.focal_point(skin)?.0)?;
+
+    let path = query
+        .is_cacheable()
+        .then(|| wallpaper_path(&state, skin_id, size, focal));
+    let bytes = match path.as_deref().map(fs::read) {
+        Some(Ok(bytes)) => bytes,
+        _ => {
+            let splash = state
+                .cdrag
+                .local_skin_asset(skin, &SkinAsset::UncenteredSplash)
+                .await?;
+            let bytes = tokio::task::spawn_blocking(move || {
+                let image = image::open(&splash)
+                    .with_context(|| format!("couldn't read the splash at {splash:?}"))?;
+                let (width, height) = size;
+                imaging::encode_jpeg(&imaging::wallpaper::crop_to_fill(
+                    &image, width, height, focal,
+                ))
+            })
+            .await
+            .context("wallpaper rendering panicked")??;
+            if let Some(path) = path {
+                cache(&path, size, &bytes)?;
+            }
+            bytes
+        }
+    };
+    let file_name = format!("{}-{}x{}.jpg", slug(&skin.name), size.0, size.1);
+    Ok((
+        [
+            (header::CONTENT_TYPE, "image/jpeg".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("inline; filename=\"{file_name}\""),
+            ),
+        ],
+        bytes,
+    ))
+}
+
+/// Caches a preset wallpaper, replacing the one of the same size made for an earlier focal
+/// point, so each skin keeps at most one per preset
+fn cache(path: &FsPath, (width, height): (u32, u32), bytes: &[u8]) -> anyhow::Result<()> {
+    let dir = path.parent().expect("wallpapers live in a directory");
+    fs::create_dir_all(dir).with_context(|| format!("couldn't create {dir:?}"))?;
+    if let Ok(entries) = fs::read_dir(dir) {
+        let prefix = format!("{width}x{height}_");
+        for entry in entries.flatten() {
+            if entry.file_name().to_string_lossy().starts_with(&prefix) {
+                fs::remove_file(entry.path()).ok();
+            }
+        }
+    }
+    caching::write_cached(path, bytes).context("couldn't cache the wallpaper")
+}
+
+#[derive(Template)]
+#[template(path = "focal_point.html")]
+struct FocalPointTemplate<'a> {
+    skin: &'a Skin,
+    champion_slug: String,
+    focal: FocalPoint,
+    source: FocalSource,
+}
+
+/// Shows the skin's splash with its focal point, for picking a better one
+async fn focal_point_page(
+    State(state): State<AppState>,
+    Path((key, skin_id)): Path<(String, u64)>,
+) -> Result<impl IntoResponse, AppError> {
+    let skin = find_skin(&state, &key, skin_id)?;
+    let champion_slug = slug(
+        &state
+            .cdrag
+            .champion_by_key(&key)
+            .ok_or(AppError::NotFound)?
+            .name,
+    );
+    let (focal, source) = state.focal_point(skin)?;
+    Ok(Html(
+        FocalPointTemplate {
+            skin,
+            champion_slug,
+            focal,
+            source,
+        }
+        .render()?,
+    ))
+}
+
+async fn set_focal_point(
+    State(state): State<AppState>,
+    Path((key, skin_id)): Path<(String, u64)>,
+    Form(focal): Form<FocalPoint>,
+) -> Result<impl IntoResponse, AppError> {
+    find_skin(&state, &key, skin_id)?;
+    if !focal.is_valid() {
+        return Err(AppError::BadRequest(
+            "focal points must be between 0 and 1".into(),
+        ));
+    }
+    state.db.set_focal_override(skin_id, focal)?;
+    Ok(Redirect::to(&mount::absolute(&format!(
+        "/champions/{key}/skins/{skin_id}/focal-point"
+    ))))
+}
+
+/// Drops the override so the estimate is used again, and has htmx reload the page
+async fn reset_focal_point(
+    State(state): State<AppState>,
+    Path((key, skin_id)): Path<(String, u64)>,
+) -> Result<impl IntoResponse, AppError> {
+    find_skin(&state, &key, skin_id)?;
+    state.db.clear_focal_override(skin_id)?;
+    let mut headers = HeaderMap::new();
+    headers.insert("HX-Refresh", "true".parse().unwrap());
+    Ok(headers)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::app::fixtures::disk_state;
+    use axum::{body::Body, http::Request};
+    use reqwest::StatusCode;
+    use rstest::*;
+    use tower::ServiceExt;
+
+    #[rstest]
+    #[case(
+        "/champions/kaisa/skins/145001/wallpaper?preset=21:9",
+        StatusCode::OK,
+        true
+    )]
+    #[case(
+        "/champions/kaisa/skins/145001/wallpaper?preset=9:19.5&w=90",
+        StatusCode::OK,
+        false
+    )]
+    #[case(
+        "/champions/145/skins/145001/wallpaper?w=300&h=100&fx=0.2",
+        StatusCode::OK,
+        false
+    )]
+    #[case(
+        "/champions/annie/skins/145001/wallpaper",
+        StatusCode::NOT_FOUND,
+        false
+    )]
+    #[case(
+        "/champions/kaisa/skins/145001/wallpaper?w=10&h=10",
+        StatusCode::BAD_REQUEST,
+        false
+    )]
+    #[case(
+        "/champions/kaisa/skins/145001/wallpaper?fy=2",
+        StatusCode::BAD_REQUEST,
+        false
+    )]
+    #[tokio::test]
+    async fn caches_only_presets(
+        disk_state: (AppState, tempfile::TempDir),
+        #[case] uri: &str,
+        #[case] expected: StatusCode,
+        #[case] cached: bool,
+    ) {
+        let (state, _dir) = disk_state;
+        let skin = state.cdrag.skin_by_id(145001).unwrap();
+        let splash = state.cdrag.data_dir.join(&skin.uncentered_splash_path);
+        fs::create_dir_all(splash.parent().unwrap()).unwrap();
+        image::RgbImage::new(320, 180).save(&splash).unwrap();
+
+        let res = router::<()>(state.clone())
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(res.status(), expected);
+        let dir = state.cdrag.data_dir.join(WALLPAPERS_DIR).join("145001");
+        assert_eq!(dir.exists(), cached);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn overrides_replace_the_estimate(disk_state: (AppState, tempfile::TempDir)) {
+        let (state, _dir) = disk_state;
+        let skin = state.cdrag.skin_by_id(145001).unwrap().clone();
+        assert_eq!(state.focal_point(&skin).unwrap().1, FocalSource::Estimated);
+
+        let res = router::<()>(state.clone())
+            .oneshot(
+                Request::post("/champions/kaisa/skins/145001/focal-point")
+                    .header("content-type", "application/x-www-form-urlencoded")
+                    .body(Body::from("x=0.25&y=0.4"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::SEE_OTHER);
+        assert_eq!(
+            state.focal_point(&skin).unwrap(),
+            (FocalPoint { x: 0.25, y: 0.4 }, FocalSource::Manual)
+        );
+
+        let splash = state.cdrag.data_dir.join(&skin.uncentered_splash_path);
+        fs::create_dir_all(splash.parent().unwrap()).unwrap();
+        image::RgbImage::new(320, 180).save(&splash).unwrap();
+        router::<()>(state.clone())
+            .oneshot(
+                Request::get("/champions/kaisa/skins/145001/wallpaper")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let cached = wallpaper_path(
+            &state,
+            145001,
+            Preset::Desktop.default_size(),
+            FocalPoint { x: 0.25, y: 0.4 },
+        );
+        assert!(cached.exists());
+    }
+}
@@ -1,26 +1,42 @@
-use anyhow::{Context, Result, anyhow};
+use anyhow::{Context, Result, anyhow, bail};
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     fmt::Display,
     fs::{self, File, create_dir_all},
     io::{self, BufReader},
-    path::PathBuf,
-    u64,
+    path::{Path, PathBuf},
+    time::Instant,
 };
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::Value;
+use sha2::{Digest, Sha256};
 use strum::Display;
 use tokio::task::JoinHandle;
+use utoipa::ToSchema;
+
+use crate::{
+    cdrag::{prefetch::PrefetchPolicy, store::AssetStore},
+    config::{Config, DEFAULT_CDRAGON_URL},
+    imaging::{
+        focal::{self, FocalPoint},
+        palette::{self, Color, PALETTE_SIZE},
+        phash::{self, PerceptualHash},
+    },
+    metrics::{Fetch, FetchLabels, METRICS},
+};
+
+pub mod archive;
+pub mod prefetch;
+pub mod store;
 
-const GAME_DATA_URL: &str =
-    "https://raw.communitydragon.org/latest/plugins/rcp-be-lol-game-data/global/default";
+/// Where `rcp-be-lol-game-data` lives under the CDragon URL
+const GAME_DATA_PATH: &str = "latest/plugins/rcp-be-lol-game-data/global/default";
 const V1: &str = "v1";
 const ASSETS: &str = "assets";
-const PROJ_NAME: &str = "fourth-shot";
 
-#[derive(Debug, Default, Display)]
+#[derive(Debug, Default, Display, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
 pub enum Status {
     #[default]
     Uninitialized,
@@ -32,6 +48,7 @@ pub enum Status {
 enum CacheFile {
     Plugins,
     Champions,
+    SkinLines,
 }
 
 impl Display for CacheFile {
@@ -39,73 +56,487 @@ impl Display for CacheFile {
         let s = match self {
             Self::Plugins => "plugins.json",
             Self::Champions => "champions.json",
+            Self::SkinLines => "skinlines.json",
         };
         f.write_str(s)
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct CDragon {
     http_client: reqwest::Client,
+    /// The CommunityDragon server, e.g. [`DEFAULT_CDRAGON_URL`]
+    cdragon_url: String,
     cache_dir: PathBuf,
     pub data_dir: PathBuf,
-    config_dir: PathBuf,
     status: Status,
     pub plugins: Vec<Plugin>,
     pub champions: HashMap<u64, Champion>,
+    pub skin_lines: HashMap<u64, SkinLine>,
+    /// [`slug`]s of champion names to champion ids
+    name_index: HashMap<String, u64>,
+    /// [`slug`]s of champion aliases to champion ids
+    alias_index: HashMap<String, u64>,
+    /// The [Normalized Paths](Skin) of every champion and skin asset in the data
+    asset_index: HashSet<PathBuf>,
+    /// Tracks downloads for the quota. Without one, downloads are kept forever.
+    store: Option<AssetStore>,
+    /// Whether to stay off the network, using only what's cached and downloaded
+    offline: bool,
+}
+
+impl Default for CDragon {
+    fn default() -> Self {
+        CDragon {
+            http_client: reqwest::Client::new(),
+            cdragon_url: DEFAULT_CDRAGON_URL.into(),
+            cache_dir: PathBuf::new(),
+            data_dir: PathBuf::new(),
+            status: Status::Uninitialized,
+            plugins: vec![],
+            champions: HashMap::new(),
+            skin_lines: HashMap::new(),
+            name_index: HashMap::new(),
+            alias_index: HashMap::new(),
+            asset_index: HashSet::new(),
+            store: None,
+            offline: false,
+        }
+    }
 }
 
 impl CDragon {
+    /// [Opens](CDragon::open) the default [`Config`]'s directories and updates them
     pub async fn new() -> anyhow::Result<Self> {
-        let proj_dirs = directories::ProjectDirs::from("", "", PROJ_NAME)
-            .with_context(|| "failed to find the project directory")
-            .unwrap();
-        let cache_dir = proj_dirs.cache_dir().to_path_buf();
-        let data_dir = proj_dirs.data_dir().to_path_buf();
-        let config_dir = proj_dirs.config_dir().to_path_buf();
+        let mut cdrag = Self::open(&Config::default());
+        cdrag.update().await?;
+        Ok(cdrag)
+    }
+
+    /// Loads whatever was cached by the last [`CDragon::update`] without touching the network.
+    /// Nothing is loaded on the first run, and the status stays [`Status::Uninitialized`].
+    pub fn open(config: &Config) -> Self {
         let mut cdrag = Self {
-            status: Status::Uninitialized,
-            http_client: reqwest::Client::new(),
-            cache_dir,
-            data_dir,
-            config_dir,
+            cdragon_url: config.cdragon_url.trim_end_matches('/').into(),
+            cache_dir: config.cache_dir.clone(),
+            data_dir: config.data_dir.clone(),
+            offline: config.offline,
             ..Default::default()
         };
-        cdrag.plugins = cdrag
-            .load_obj(CacheFile::Plugins)
-            .unwrap_or(cdrag.fetch_plugins().await?);
-        cdrag.champions = cdrag
-            .load_obj(CacheFile::Champions)
-            .unwrap_or(cdrag.fetch_all_champions().await?);
-        cdrag.update().await?;
-        Ok(cdrag)
+        cdrag.plugins = cdrag.load_obj(CacheFile::Plugins).unwrap_or_default();
+        let champions = cdrag.load_obj(CacheFile::Champions).unwrap_or_default();
+        cdrag.set_champions(champions);
+        cdrag.skin_lines = cdrag.load_obj(CacheFile::SkinLines).unwrap_or_default();
+        // The cache was written by the last refresh, however long ago that was
+        let cached = fs::metadata(cdrag.cache_dir.join(CacheFile::Plugins.to_string()))
+            .and_then(|metadata| metadata.modified());
+        if let Ok(cached) = cached {
+            METRICS
+                .last_refresh
+                .set(DateTime::<Utc>::from(cached).timestamp());
+        }
+        cdrag
+    }
+
+    /// Everything the web app needs before it starts: [updates](CDragon::update) the data,
+    /// downloads what the policy selects, then analyzes and deduplicates new uncentered splashes.
+    /// Offline, only the cached data is used, and there has to be some.
+    pub async fn sync(&mut self, policy: &PrefetchPolicy) -> anyhow::Result<()> {
+        if self.offline {
+            if self.champions.is_empty() {
+                bail!(
+                    "nothing is cached in {:?} to run offline with, so import an archive first",
+                    self.cache_dir
+                );
+            }
+            tracing::info!("offline, so using the cached data");
+        } else {
+            self.update().await?;
+            let plan = self.plan_prefetch(policy, false).await?;
+            tracing::info!("prefetching {plan}");
+            self.prefetch(&plan).await?;
+        }
+        self.analyze_splashes().await?;
+        let freed = self.dedupe_splashes().await?;
+        if freed > 0 {
+            tracing::info!("freed {freed} bytes by linking duplicate splashes");
+        }
+        if let Some(store) = &self.store {
+            store.reindex(self.known_assets())?;
+            let eviction = store.enforce_quota(None)?;
+            if eviction.files > 0 {
+                tracing::info!(
+                    "evicted {} assets ({}) to stay within the quota",
+                    eviction.files,
+                    store::format_bytes(eviction.bytes)
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Tracks downloads in the store and keeps them within its quota
+    pub fn with_store(mut self, store: AssetStore) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    pub fn store(&self) -> Option<&AssetStore> {
+        self.store.as_ref()
+    }
+
+    /// Every asset the data refers to, and whether it's pinned in the store. Champion icons are
+    /// on every page, so they're pinned.
+    fn known_assets(&self) -> Vec<(PathBuf, bool)> {
+        let mut known: Vec<(PathBuf, bool)> = vec![];
+        for champ in self.champions.values() {
+            known.push((champ.square_portrait_path.clone().into(), true));
+            for skin in &champ.skins {
+                for path in [
+                    &skin.splash_path,
+                    &skin.uncentered_splash_path,
+                    &skin.tile_path,
+                    &skin.load_screen_path,
+                ] {
+                    known.push((path.into(), false));
+                }
+            }
+        }
+        known.retain(|(path, _)| !path.as_os_str().is_empty());
+        known
     }
 
-    pub fn champion_by_name<'a, N: Into<String> + Copy>(&'a self, name: N) -> Option<&'a Champion> {
+    fn is_icon(&self, asset_path: &Path) -> bool {
         self.champions
-            .iter()
-            .find(|champ| champ.1.name == name.into())
-            .map(|champ| champ.1)
+            .values()
+            .any(|champ| Path::new(&champ.square_portrait_path) == asset_path)
+    }
+
+    /// Records a use of the asset, if there's a store. A failure to record shouldn't fail
+    /// whatever needed the asset, so it's only logged.
+    pub fn touch_asset(&self, asset_path: &Path) {
+        if let Some(Err(err)) = self.store.as_ref().map(|store| store.touch(asset_path)) {
+            tracing::warn!("couldn't record a use of {asset_path:?}: {err:#}");
+        }
+    }
+
+    /// Records a download and evicts whatever no longer fits, if there's a store
+    fn record_download(&self, asset_path: &Path) -> anyhow::Result<()> {
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+        store.record(asset_path, self.is_icon(asset_path))?;
+        let eviction = store.enforce_quota(Some(asset_path))?;
+        if eviction.files > 0 {
+            tracing::debug!(
+                "evicted {} assets ({}) to make room for {asset_path:?}",
+                eviction.files,
+                store::format_bytes(eviction.bytes)
+            );
+        }
+        Ok(())
+    }
+
+    /// Replaces the loaded champions and rebuilds the name and alias indexes over them
+    pub fn set_champions(&mut self, champions: HashMap<u64, Champion>) {
+        self.name_index = champions
+            .values()
+            .map(|champ| (champ.slug(), champ.id))
+            .collect();
+        self.alias_index = champions
+            .values()
+            .map(|champ| (slug(&champ.alias), champ.id))
+            .collect();
+        self.asset_index = champions
+            .values()
+            .flat_map(|champ| {
+                let skins = champ.skins.iter().flat_map(|skin| {
+                    [
+                        &skin.splash_path,
+                        &skin.uncentered_splash_path,
+                        &skin.tile_path,
+                        &skin.load_screen_path,
+                    ]
+                });
+                std::iter::once(&champ.square_portrait_path).chain(skins)
+            })
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+            .collect();
+        self.champions = champions;
+    }
+
+    /// Finds a [`Champion`] by name, ignoring case and punctuation
+    pub fn champion_by_name<N: Into<String> + Copy>(&self, name: N) -> Option<&Champion> {
+        let id = self.name_index.get(&slug(&name.into()))?;
+        self.champion_by_id(*id)
+    }
+
+    /// Finds a [`Champion`] by alias (e.g. `MonkeyKing` for Wukong), ignoring case and punctuation
+    pub fn champion_by_alias(&self, alias: &str) -> Option<&Champion> {
+        let id = self.alias_index.get(&slug(alias))?;
+        self.champion_by_id(*id)
     }
 
     pub fn champion_by_id(&self, id: u64) -> Option<&Champion> {
         self.champions.get(&id)
     }
 
-    pub fn clean_up(&self) -> anyhow::Result<()> {
+    /// Finds a [`Champion`] by any of the keys that appear in URLs: its numeric id, its name's
+    /// [`slug`] (`kaisa`, `Kai'Sa`) or its alias (`MonkeyKing`).
+    pub fn champion_by_key(&self, key: &str) -> Option<&Champion> {
+        match key.parse::<u64>() {
+            Ok(id) => self.champion_by_id(id),
+            Err(_) => self
+                .champion_by_name(key)
+                .or_else(|| self.champion_by_alias(key)),
+        }
+    }
+
+    /// Skin ids are the owning champion's id times 1000 plus the skin number, so the champion
+    /// can be found without scanning every skin.
+    pub fn skin_by_id(&self, id: u64) -> Option<&Skin> {
+        self.champion_by_id(id / 1000)?
+            .skins
+            .iter()
+            .find(|skin| skin.id == id)
+    }
+
+    pub fn skin_line_by_id(&self, id: u64) -> Option<&SkinLine> {
+        self.skin_lines.get(&id)
+    }
+
+    /// Every [`Skin`] that belongs to the skin line with the given id, ordered by skin id
+    pub fn skins_in_line(&self, skin_line_id: u64) -> Vec<&Skin> {
+        let mut skins: Vec<&Skin> = self
+            .champions
+            .values()
+            .flat_map(|champ| &champ.skins)
+            .filter(|skin| skin.skin_lines.contains(&skin_line_id))
+            .collect();
+        skins.sort_by_key(|skin| skin.id);
+        skins
+    }
+
+    /// Skins with a palette color within `max_distance` (see [`Color::distance`]) of `color`,
+    /// closest first
+    pub fn skins_by_color(&self, color: Color, max_distance: f32) -> Vec<&Skin> {
+        let mut matches: Vec<(f32, &Skin)> = self
+            .champions
+            .values()
+            .flat_map(|champ| &champ.skins)
+            .filter_map(|skin| {
+                let closest = skin
+                    .palette
+                    .iter()
+                    .map(|c| c.distance(color))
+                    .min_by(f32::total_cmp)?;
+                (closest <= max_distance).then_some((closest, skin))
+            })
+            .collect();
+        matches.sort_by(|(a, a_skin), (b, b_skin)| a.total_cmp(b).then(a_skin.id.cmp(&b_skin.id)));
+        matches.into_iter().map(|(_, skin)| skin).collect()
+    }
+
+    /// The dominant colors across a skin line, from the palettes of its skins. Each skin's
+    /// leading colors count for more than its trailing ones.
+    pub fn skin_line_palette(&self, skin_line_id: u64, n: usize) -> Vec<Color> {
+        let colors = self
+            .skins_in_line(skin_line_id)
+            .into_iter()
+            .flat_map(|skin| {
+                skin.palette.iter().enumerate().flat_map(|(rank, color)| {
+                    std::iter::repeat_n(color.0, PALETTE_SIZE.saturating_sub(rank))
+                })
+            })
+            .collect();
+        palette::median_cut(colors, n)
+    }
+
+    /// Groups of skins whose uncentered splashes are the same art, or close enough (within
+    /// `max_distance`, see [`PerceptualHash::distance`]) to be crops or re-encodes of it. The
+    /// largest groups come first, and skins within a group are ordered by id.
+    pub fn duplicate_groups(&self, max_distance: u32) -> Vec<Vec<&Skin>> {
+        let mut hashed: Vec<(&Skin, PerceptualHash)> = self
+            .champions
+            .values()
+            .flat_map(|champ| &champ.skins)
+            .filter_map(|skin| Some((skin, skin.phash?)))
+            .collect();
+        hashed.sort_by_key(|(skin, _)| skin.id);
+
+        // Union-find over the skins, joining every pair that's close enough
+        let mut parents: Vec<usize> = (0..hashed.len()).collect();
+        fn root(parents: &mut [usize], mut i: usize) -> usize {
+            while parents[i] != i {
+                parents[i] = parents[parents[i]];
+                i = parents[i];
+            }
+            i
+        }
+        for i in 0..hashed.len() {
+            for j in i + 1..hashed.len() {
+                if hashed[i].1.distance(hashed[j].1) <= max_distance {
+                    let (a, b) = (root(&mut parents, i), root(&mut parents, j));
+                    parents[a.max(b)] = a.min(b);
+                }
+            }
+        }
+        let mut groups: HashMap<usize, Vec<&Skin>> = HashMap::new();
+        for (i, (skin, _)) in hashed.iter().enumerate() {
+            groups.entry(root(&mut parents, i)).or_default().push(skin);
+        }
+        let mut groups: Vec<Vec<&Skin>> = groups
+            .into_values()
+            .filter(|group| group.len() > 1)
+            .collect();
+        groups.sort_by(|a, b| b.len().cmp(&a.len()).then(a[0].id.cmp(&b[0].id)));
+        groups
+    }
+
+    /// Replaces byte-for-byte identical uncentered splashes in `data_dir` with hard links to
+    /// one copy. Group splashes are downloaded once per champion in them, so this adds up.
+    /// Returns how many bytes were freed.
+    pub async fn dedupe_splashes(&self) -> anyhow::Result<u64> {
+        let mut by_hash: HashMap<PerceptualHash, Vec<PathBuf>> = HashMap::new();
+        for skin in self.champions.values().flat_map(|champ| &champ.skins) {
+            if let Some(hash) = skin.phash {
+                by_hash
+                    .entry(hash)
+                    .or_default()
+                    .push(self.data_dir.join(&skin.uncentered_splash_path));
+            }
+        }
+        let candidates: Vec<Vec<PathBuf>> = by_hash
+            .into_values()
+            .filter(|paths| paths.len() > 1)
+            .collect();
+        tokio::task::spawn_blocking(move || {
+            candidates
+                .iter()
+                .try_fold(0, |freed, paths| Ok(freed + link_identical(paths)?))
+        })
+        .await
+        .context("deduplicating splashes panicked")?
+    }
+
+    /// Computes the palette, focal point and perceptual hash of every skin whose uncentered
+    /// splash is on disk but hasn't been analyzed yet, then re-caches the champions. Returns
+    /// how many splashes were analyzed.
+    pub async fn analyze_splashes(&mut self) -> anyhow::Result<usize> {
+        let pending: Vec<(u64, PathBuf)> = self
+            .champions
+            .values()
+            .flat_map(|champ| &champ.skins)
+            .filter(|skin| {
+                skin.palette.is_empty() || skin.focal_point.is_none() || skin.phash.is_none()
+            })
+            .filter_map(|skin| {
+                let path = self.data_dir.join(&skin.uncentered_splash_path);
+                (!skin.uncentered_splash_path.is_empty() && path.is_file())
+                    .then_some((skin.id, path))
+            })
+            .collect();
+        if pending.is_empty() {
+            return Ok(0);
+        }
+
+        let analyses = tokio::task::spawn_blocking(move || {
+            let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+            let chunk_size = pending.len().div_ceil(threads);
+            std::thread::scope(|scope| {
+                let workers: Vec<_> = pending
+                    .chunks(chunk_size)
+                    .map(|chunk| {
+                        scope.spawn(move || {
+                            chunk
+                                .iter()
+                                .filter_map(|(id, path)| {
+                                    let image = image::open(path)
+                                        .inspect_err(|err| {
+                                            tracing::warn!("couldn't read {path:?}: {err}")
+                                        })
+                                        .ok()?;
+                                    let analysis = SplashAnalysis {
+                                        palette: palette::palette(&image),
+                                        focal_point: focal::estimate(&image),
+                                        phash: phash::hash(&image),
+                                    };
+                                    Some((*id, analysis))
+                                })
+                                .collect::<Vec<_>>()
+                        })
+                    })
+                    .collect();
+                workers
+                    .into_iter()
+                    .flat_map(|worker| worker.join().expect("splash analysis panicked"))
+                    .collect::<HashMap<u64, SplashAnalysis>>()
+            })
+        })
+        .await
+        .context("splash analysis panicked")?;
+
+        for skin in self
+            .champions
+            .values_mut()
+            .flat_map(|champ| &mut champ.skins)
+        {
+            if let Some(analysis) = analyses.get(&skin.id) {
+                skin.palette = analysis.palette.clone();
+                skin.focal_point = Some(analysis.focal_point);
+                skin.phash = Some(analysis.phash);
+            }
+        }
+        self.cache_obj(&self.champions, CacheFile::Champions)
+            .with_context(|| "failed to cache the champions with their splash analyses")?;
+        Ok(analyses.len())
+    }
+
+    /// A short hash of the champions and skin lines, which changes whenever the data does, e.g.
+    /// for ETags of pages rendered from it
+    pub fn dataset_version(&self) -> String {
+        let mut champions: Vec<&Champion> = self.champions.values().collect();
+        champions.sort_by_key(|champ| champ.id);
+        let mut skin_lines: Vec<&SkinLine> = self.skin_lines.values().collect();
+        skin_lines.sort_by_key(|line| line.id);
+        let json = serde_json::to_vec(&(champions, skin_lines))
+            .expect("the data serializes, since it's cached as JSON");
+        let hash = format!("{:x}", Sha256::digest(json));
+        hash[..16].to_string()
+    }
+
+    pub fn current_status(&self) -> Status {
+        self.status
+    }
+
+    /// Deletes the cache directory, the downloaded assets and the `caches` in `data_dir`,
+    /// which are all fetched or rendered again when needed. The config file, the database
+    /// and everything else in `data_dir` are the user's, so they stay.
+    pub fn clean_up(&self, caches: &[&str]) -> anyhow::Result<()> {
         fs::remove_dir_all(&self.cache_dir).ok();
-        fs::remove_dir_all(&self.data_dir).ok();
-        fs::remove_dir_all(&self.config_dir).ok();
+        for dir in std::iter::once(&ASSETS).chain(caches) {
+            fs::remove_dir_all(self.data_dir.join(dir)).ok();
+        }
         Ok(())
     }
 
+    fn game_data_url(&self) -> String {
+        format!("{}/{GAME_DATA_PATH}", self.cdragon_url)
+    }
+
+    /// Where the asset at a [Normalized Path](Skin) is downloaded from
+    fn asset_url(&self, asset_path: &Path) -> String {
+        format!("{}/{}", self.game_data_url(), asset_path.to_string_lossy())
+    }
+
     async fn cached_plugin_updated_date(&self, name: &PluginName) -> Option<DateTime<Utc>> {
         let plugins: Result<Vec<Plugin>, anyhow::Error> = self.load_obj(CacheFile::Plugins);
         plugins.map_or(None, |plugs| {
             plugs
                 .iter()
                 .find(|plug| plug.name == *name)
-                .map_or(None, |p| Some(p.mtime))
+                .map(|p| p.mtime)
         })
     }
 
@@ -121,14 +552,37 @@ impl CDragon {
                         format!("failed to check when {plugin_name} was last updated")
                     })?;
                 if cached_date < fetched {
-                    return Ok(Status::OutOfDate);
+                    Ok(Status::OutOfDate)
                 } else {
-                    return Ok(Status::UpToDate);
+                    Ok(Status::UpToDate)
                 }
             }
         }
     }
 
+    /// Compares every cached [`Plugin`] against CDragon with a single request. Plugins that
+    /// were never cached are [`Status::OutOfDate`].
+    pub async fn plugin_statuses(&self) -> anyhow::Result<Vec<(PluginName, Status)>> {
+        let fetched = self
+            .fetch_plugins()
+            .await
+            .with_context(|| "failed to check when the plugins were last updated")?;
+        Ok(fetched
+            .into_iter()
+            .map(|plugin| {
+                let cached = self
+                    .plugins
+                    .iter()
+                    .find(|cached| cached.name == plugin.name);
+                let status = match cached {
+                    Some(cached) if !plugin.updated_since(cached.mtime) => Status::UpToDate,
+                    _ => Status::OutOfDate,
+                };
+                (plugin.name, status)
+            })
+            .collect())
+    }
+
     /// Saves an object to $HOME/.cache/[`file_name`].
     ///
     /// When the $HOME/.cache/ directory doesn't exist, try to create it.
@@ -147,9 +601,7 @@ impl CDragon {
     fn cache_obj(&self, obj: &impl Serialize, cache_file: CacheFile) -> anyhow::Result<()> {
         let ser = serde_json::to_string_pretty(obj)?;
         let mut file_path = self.cache_dir.clone();
-        if file_path.try_exists().is_err()
-            || file_path.try_exists().is_ok_and(|exists| exists == false)
-        {
+        if file_path.try_exists().is_err() || file_path.try_exists().is_ok_and(|exists| !exists) {
             create_dir_all(&file_path)?;
         }
         file_path.push(cache_file.to_string());
@@ -185,10 +637,20 @@ impl CDragon {
     /// [`Status::UpToDate`]
     ///
     /// The fetched data is stored in fields of the [`CDragon`] struct. Currently
-    /// only the [`Plugin`]s and [`Champion`]s are stored.
-    ///
+    /// only the [`Plugin`]s, [`Champion`]s and [`SkinLine`]s are stored.
     ///
+    /// Successes and failures are recorded in the [`METRICS`] for alerting.
     pub async fn update(&mut self) -> anyhow::Result<()> {
+        if let Err(err) = self.refresh().await {
+            METRICS.refresh_failures.inc();
+            return Err(err);
+        }
+        METRICS.last_refresh.set(Utc::now().timestamp());
+        self.status = Status::UpToDate;
+        Ok(())
+    }
+
+    async fn refresh(&mut self) -> anyhow::Result<()> {
         let plugins = self
             .fetch_plugins()
             .await
@@ -197,34 +659,30 @@ impl CDragon {
             .with_context(|| "failed to cache the updated plugins")?;
         self.plugins = plugins;
 
-        let champions = self
+        let mut champions = self
             .fetch_all_champions()
             .await
             .with_context(|| "failed to update champions")?;
+        carry_over_analyses(&self.champions, &mut champions);
         self.cache_obj(&champions, CacheFile::Champions)
             .with_context(|| "failed to cache the updated champions")?;
-        self.champions = champions;
+        self.set_champions(champions);
 
-        for champ in &self.champions {
-            self.download_champion_icon(*champ.0).await?;
-        }
-
-        self.status = Status::UpToDate;
+        let skin_lines = self
+            .fetch_skin_lines()
+            .await
+            .with_context(|| "failed to update skin lines")?;
+        self.cache_obj(&skin_lines, CacheFile::SkinLines)
+            .with_context(|| "failed to cache the updated skin lines")?;
+        self.skin_lines = skin_lines;
         Ok(())
     }
 
     /// Fetches the latest [`Plugin`]s from the CDragon API
     pub async fn fetch_plugins(&self) -> anyhow::Result<Vec<Plugin>> {
-        let res = self
-            .http_client
-            .get(format!(
-                "https://raw.communitydragon.org/json/latest/plugins/"
-            ))
-            .send()
-            .await?
-            .text()
-            .await?;
-        let plugins: Vec<Plugin> = serde_json::from_str(&res)?;
+        let url = format!("{}/json/latest/plugins/", self.cdragon_url);
+        let res = fetch(&self.http_client, &url, Fetch::Plugins).await?;
+        let plugins: Vec<Plugin> = serde_json::from_slice(&res)?;
         Ok(plugins)
     }
 
@@ -251,14 +709,9 @@ impl CDragon {
     }
 
     pub async fn fetch_champion_ids(&self) -> anyhow::Result<Vec<u64>> {
-        let res = self
-            .http_client
-            .get(format!("{GAME_DATA_URL}/{V1}/champion-summary.json"))
-            .send()
-            .await?
-            .text()
-            .await?;
-        let obj: Vec<Value> = serde_json::from_str(&res)?;
+        let url = format!("{}/{V1}/champion-summary.json", self.game_data_url());
+        let res = fetch(&self.http_client, &url, Fetch::ChampionSummary).await?;
+        let obj: Vec<Value> = serde_json::from_slice(&res)?;
         let champ_ids: Vec<u64> = obj
             .iter()
             .skip(1)
@@ -267,29 +720,23 @@ impl CDragon {
         Ok(champ_ids)
     }
 
+    #[tracing::instrument(skip_all, fields(champion = id))]
     pub async fn fetch_champion(&self, id: u64) -> anyhow::Result<Champion> {
-        let res = self
-            .http_client
-            .get(format!("{GAME_DATA_URL}/{V1}/champions/{id}.json"))
-            .send()
-            .await?
-            .text()
-            .await?;
-        let champion = serde_json::from_str(&res)?;
+        let url = format!("{}/{V1}/champions/{id}.json", self.game_data_url());
+        let res = fetch(&self.http_client, &url, Fetch::Champion).await?;
+        let champion = serde_json::from_slice(&res)?;
         Ok(champion)
     }
 
+    #[tracing::instrument(name = "fetch_champion", skip_all, fields(champion = id))]
     async fn fetch_champion_parallel(
         http_client: reqwest::Client,
+        game_data_url: String,
         id: u64,
     ) -> anyhow::Result<Champion> {
-        let res = http_client
-            .get(format!("{GAME_DATA_URL}/{V1}/champions/{id}.json"))
-            .send()
-            .await?
-            .text()
-            .await?;
-        let champion = serde_json::from_str(&res)?;
+        let url = format!("{game_data_url}/{V1}/champions/{id}.json");
+        let res = fetch(&http_client, &url, Fetch::Champion).await?;
+        let champion = serde_json::from_slice(&res)?;
         Ok(champion)
     }
 
@@ -298,17 +745,33 @@ impl CDragon {
         let mut tasks: Vec<JoinHandle<_>> = Vec::with_capacity(champ_ids.len());
         for id in champ_ids {
             let client = self.http_client.clone();
-            let task = tokio::spawn(Self::fetch_champion_parallel(client, id));
+            let task = tokio::spawn(Self::fetch_champion_parallel(
+                client,
+                self.game_data_url(),
+                id,
+            ));
             tasks.push(task);
         }
         let mut champions = HashMap::with_capacity(tasks.len());
         for task in tasks {
             let champ = task.await??;
-            champions.insert(champ.id.clone(), champ);
+            champions.insert(champ.id, champ);
         }
         Ok(champions)
     }
 
+    /// Fetches every [`SkinLine`], skipping the unnamed placeholder with id 0
+    pub async fn fetch_skin_lines(&self) -> anyhow::Result<HashMap<u64, SkinLine>> {
+        let url = format!("{}/{V1}/skinlines.json", self.game_data_url());
+        let res = fetch(&self.http_client, &url, Fetch::SkinLines).await?;
+        let skin_lines: Vec<SkinLine> = serde_json::from_slice(&res)?;
+        Ok(skin_lines
+            .into_iter()
+            .filter(|line| line.id != 0)
+            .map(|line| (line.id, line))
+            .collect())
+    }
+
     pub async fn download_champion_icon(&self, champ_id: u64) -> anyhow::Result<()> {
         let icon_path: PathBuf = self
             .champions
@@ -317,61 +780,76 @@ impl CDragon {
             .square_portrait_path
             .clone()
             .into();
-
-        let file_path = self.data_dir.join(&icon_path);
-
-        if file_path.try_exists().is_ok_and(|v| v == true) {
-            return Ok(());
-        }
-
-        let icon_url = format!("{GAME_DATA_URL}/{}", icon_path.to_str().unwrap());
-        let bytes = self
-            .http_client
-            .get(icon_url)
-            .send()
+        self.local_asset(&icon_path)
             .await
-            .with_context(|| format!("couldn't download champion icon for {champ_id}"))?
-            .bytes()
-            .await
-            .with_context(|| {
-                format!("couldn't get the bytes of the champion icon for {champ_id}")
-            })?;
+            .with_context(|| format!("couldn't download champion icon for {champ_id}"))?;
+        Ok(())
+    }
 
-        let file_path = self.data_dir.join(icon_path);
-        let mut file_dir = file_path.clone();
-        file_dir.pop();
-        create_dir_all(&file_dir)
-            .with_context(|| format!("couldn't create the path for {:?}", &file_dir))?;
-        let mut file =
-            File::create(file_path).with_context(|| "couldn't create champ icon file")?;
-        io::copy(&mut bytes.as_ref(), &mut file)
-            .with_context(|| "couldn't copy champ icon bytes to file")?;
+    /// Downloads the given asset of every skin that doesn't have it on disk yet
+    pub async fn download_skin_assets(&self, asset: SkinAsset) -> anyhow::Result<()> {
+        for champ in self.champions.values() {
+            for skin in &champ.skins {
+                self.download_skin_asset(skin, &asset).await?;
+            }
+        }
         Ok(())
     }
 
     pub async fn download_skin_asset(&self, skin: &Skin, asset: &SkinAsset) -> anyhow::Result<()> {
-        let asset_path = self.skin_path_of(skin, asset)?;
-        let file_path = self.data_dir.join(&asset_path);
+        self.local_skin_asset(skin, asset).await?;
+        Ok(())
+    }
 
-        if file_path.try_exists().is_ok_and(|it| it == true) {
-            return Ok(());
+    /// Where the asset is on disk, downloading it first if it isn't there yet
+    pub async fn local_skin_asset(
+        &self,
+        skin: &Skin,
+        asset: &SkinAsset,
+    ) -> anyhow::Result<PathBuf> {
+        self.local_asset(&self.skin_path_of(skin, asset)?).await
+    }
+
+    /// Where the asset at a [Normalized Path](Skin) is on disk, downloading it first if it isn't
+    /// there yet
+    pub async fn local_asset(&self, asset_path: &Path) -> anyhow::Result<PathBuf> {
+        let file_path = self.data_dir.join(asset_path);
+
+        if file_path.try_exists().is_ok_and(|it| it) {
+            self.touch_asset(asset_path);
+            return Ok(file_path);
+        }
+        if self.offline {
+            bail!("{asset_path:?} isn't downloaded, and this is offline");
         }
 
-        let asset_url = format!("{GAME_DATA_URL}/{}", asset_path.to_str().unwrap());
-        let bytes = self
-            .http_client
-            .get(asset_url)
-            .send()
+        let asset_url = self.asset_url(asset_path);
+        let bytes = fetch(&self.http_client, &asset_url, Fetch::Asset)
             .await
-            .with_context(|| "couldn't download asset")?
-            .bytes()
-            .await?;
+            .with_context(|| format!("couldn't download the asset at {asset_path:?}"))?;
         let mut file_dir = file_path.clone();
         file_dir.pop();
         create_dir_all(&file_dir)?;
-        let mut file = File::create(file_path).with_context(|| "couldn't create skin file")?;
-        io::copy(&mut bytes.as_ref(), &mut file).with_context(|| "couldn't copy bytes")?;
-        Ok(())
+        // Renders may be reading it already, so it has to appear whole
+        crate::app::caching::write_cached(&file_path, &bytes)
+            .with_context(|| format!("couldn't save the asset at {asset_path:?}"))?;
+        self.record_download(asset_path)?;
+        Ok(file_path)
+    }
+
+    /// Where an asset a client asked for is on disk. Only the champion and skin assets in the
+    /// data are downloaded; any other path must already be in `data_dir/assets`, so requests
+    /// can't fetch arbitrary files from CommunityDragon onto the disk.
+    pub async fn requested_asset(&self, asset_path: &Path) -> anyhow::Result<PathBuf> {
+        if self.asset_index.contains(asset_path) {
+            return self.local_asset(asset_path).await;
+        }
+        let file_path = self.data_dir.join(asset_path);
+        if !asset_path.starts_with(ASSETS) || !file_path.is_file() {
+            bail!("{asset_path:?} isn't a champion or skin asset");
+        }
+        self.touch_asset(asset_path);
+        Ok(file_path)
     }
 
     pub fn skin_path_of(&self, skin: &Skin, asset: &SkinAsset) -> anyhow::Result<PathBuf> {
@@ -385,7 +863,7 @@ impl CDragon {
     }
 }
 
-#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone)]
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct TactialInfo {
     pub style: u64,
@@ -393,7 +871,7 @@ pub struct TactialInfo {
     pub damage_type: String,
 }
 
-#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone)]
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct PlaystyleInfo {
     pub damage: u64,
@@ -403,7 +881,9 @@ pub struct PlaystyleInfo {
     pub utility: u64,
 }
 
-#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone, strum::Display)]
+#[derive(
+    Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone, strum::Display, ToSchema,
+)]
 #[serde(rename_all = "camelCase")]
 pub enum Rarity {
     #[strum(to_string = "Epic")]
@@ -425,7 +905,7 @@ pub enum Rarity {
     KExalted,
 }
 
-#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone)]
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone, ToSchema)]
 pub enum SkinType {
     Ultimate,
     #[default]
@@ -451,7 +931,7 @@ pub enum SkinType {
 /// ```
 /// let cdragon = CDragon::new().await.unwrap();
 /// let akshan_skin_splash = cdragon.champions
-/// format!("{GAME_DATA_URL}/{ASSETS}/")
+/// format!("{DEFAULT_CDRAGON_URL}/{GAME_DATA_PATH}/{ASSETS}/")
 /// ```
 ///
 /// For example:
@@ -465,7 +945,7 @@ pub enum SkinType {
 ///     assets/characters/akshan/skins/base/images/akshan_splash_uncentered_0.jpg
 ///
 ///
-#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone)]
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Clone, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Skin {
     pub id: u64,
@@ -485,15 +965,128 @@ pub struct Skin {
     #[serde(deserialize_with = "deserialize_skin_lines")]
     pub skin_lines: Vec<u64>,
     pub description: Option<String>,
+    /// Dominant colors of the uncentered splash, most common first. Empty until the splash has
+    /// been downloaded and [`CDragon::analyze_splashes`] has run.
+    #[serde(default)]
+    pub palette: Vec<Color>,
+    /// Estimated center of interest of the uncentered splash, once analyzed like the palette
+    #[serde(default)]
+    pub focal_point: Option<FocalPoint>,
+    /// Perceptual hash of the uncentered splash, once analyzed like the palette. Skins sharing
+    /// a group splash have the same or nearby hashes; see [`CDragon::duplicate_groups`].
+    #[serde(default)]
+    pub phash: Option<PerceptualHash>,
 }
 
+/// What [`CDragon::analyze_splashes`] learns from one splash
+struct SplashAnalysis {
+    palette: Vec<Color>,
+    focal_point: FocalPoint,
+    phash: PerceptualHash,
+}
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize, ToSchema, clap::ValueEnum,
+)]
+#[serde(rename_all = "snake_case")]
 pub enum SkinAsset {
     Splash,
     UncenteredSplash,
     Tile,
+    #[value(alias = "loadscreen")]
     LoadScreen,
 }
 
+/// Keeps the analyses of skins whose splash hasn't changed since they were analyzed
+fn carry_over_analyses(old: &HashMap<u64, Champion>, new: &mut HashMap<u64, Champion>) {
+    let analyzed: HashMap<u64, &Skin> = old
+        .values()
+        .flat_map(|champ| &champ.skins)
+        .map(|skin| (skin.id, skin))
+        .collect();
+    for skin in new.values_mut().flat_map(|champ| &mut champ.skins) {
+        if let Some(old) = analyzed.get(&skin.id)
+            && old.uncentered_splash_path == skin.uncentered_splash_path
+        {
+            skin.palette = old.palette.clone();
+            skin.focal_point = old.focal_point;
+            skin.phash = old.phash;
+        }
+    }
+}
+
+/// Hard links every file in `paths` with the same contents as the first one to it, returning
+/// how many bytes that freed. Files that are missing, differ, or are already linked are left be.
+fn link_identical(paths: &[PathBuf]) -> io::Result<u64> {
+    let Some((original, rest)) = paths.split_first() else {
+        return Ok(0);
+    };
+    let Ok(contents) = fs::read(original) else {
+        return Ok(0);
+    };
+    let mut freed = 0;
+    for path in rest {
+        if path == original || is_same_file(original, path)? {
+            continue;
+        }
+        match fs::read(path) {
+            Ok(other) if other == contents => {}
+            _ => continue,
+        }
+        // Link beside the duplicate and swap it in, so it's never missing
+        let link = path.with_extension("link");
+        fs::remove_file(&link).ok();
+        fs::hard_link(original, &link)?;
+        fs::rename(&link, path)?;
+        freed += contents.len() as u64;
+    }
+    Ok(freed)
+}
+
+#[cfg(unix)]
+fn is_same_file(a: &Path, b: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+    let (a, b) = (fs::metadata(a)?, fs::metadata(b));
+    Ok(b.is_ok_and(|b| (a.dev(), a.ino()) == (b.dev(), b.ino())))
+}
+
+/// Without inodes to compare, identical files are relinked on every run, which is harmless
+#[cfg(not(unix))]
+fn is_same_file(_: &Path, _: &Path) -> io::Result<bool> {
+    Ok(false)
+}
+
+/// GETs `url` from CommunityDragon, counting the request, whether it failed and how many bytes
+/// it brought back in the [`METRICS`]. Each fetch gets a span, inside whatever asked for it, and
+/// logs how long it took.
+#[tracing::instrument(name = "cdragon_fetch", skip(client))]
+async fn fetch(client: &reqwest::Client, url: &str, kind: Fetch) -> anyhow::Result<Vec<u8>> {
+    let labels = FetchLabels { kind };
+    METRICS.fetches.get_or_create(&labels).inc();
+    let start = Instant::now();
+    let fetched = async {
+        let res = client.get(url).send().await?.error_for_status()?;
+        anyhow::Ok(res.bytes().await?)
+    }
+    .await;
+    let duration_ms = start.elapsed().as_millis() as u64;
+    match fetched {
+        Ok(bytes) => {
+            tracing::debug!(duration_ms, bytes = bytes.len(), "fetched");
+            METRICS
+                .fetched_bytes
+                .get_or_create(&labels)
+                .inc_by(bytes.len() as u64);
+            Ok(bytes.into())
+        }
+        Err(err) => {
+            tracing::warn!(duration_ms, error = %err, "fetch failed");
+            METRICS.fetch_errors.get_or_create(&labels).inc();
+            Err(err.context(format!("couldn't fetch {url}")))
+        }
+    }
+}
+
 fn deserialize_asset_path<'de, D>(deserializer: D) -> Result<String, D::Error>
 where
     D: Deserializer<'de>,
@@ -510,11 +1103,16 @@ where
 {
     let mut res = vec![];
     let value = serde_json::Value::deserialize(deserializer)?;
-    if value.to_string() == "null" {
+    if value.is_null() {
         return Ok(res);
     }
     // TODO: I don't love these errors, but I haven't quite figured out how to properly map them.
     for j_struct in value.as_array().unwrap() {
+        // The cache stores the bare ids this returns
+        if let Some(id) = j_struct.as_u64() {
+            res.push(id);
+            continue;
+        }
         let v = j_struct
             .as_object()
             .ok_or(serde::de::Error::custom("that's not an object"))?
@@ -527,14 +1125,15 @@ where
     Ok(res)
 }
 
-#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone, ToSchema)]
 pub struct SkinLine {
-    id: u64,
-    name: String,
-    description: String,
+    pub id: u64,
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
 }
 
-#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Eq, Clone)]
+#[derive(Debug, Default, Deserialize, Serialize, PartialEq, Clone, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct Champion {
     pub id: u64,
@@ -550,6 +1149,22 @@ pub struct Champion {
     pub skins: Vec<Skin>,
 }
 
+impl Champion {
+    /// The champion's name as it appears in canonical URLs, e.g. `kaisa` for Kai'Sa
+    pub fn slug(&self) -> String {
+        slug(&self.name)
+    }
+}
+
+/// Lowercases a name and drops everything but letters and digits, so `Kai'Sa`, `kaisa` and
+/// `KaiSa` all produce `kaisa`, and `Nunu & Willump` produces `nunuwillump`.
+pub fn slug(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
 fn deserialize_icon_path<'de, D>(deserializer: D) -> Result<String, D::Error>
 where
     D: Deserializer<'de>,
@@ -560,7 +1175,7 @@ where
     Ok(path)
 }
 
-#[derive(Debug, Display, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[derive(Debug, Display, Default, Deserialize, Serialize, PartialEq, Eq, Clone, ToSchema)]
 #[serde(rename_all = "kebab-case")]
 pub enum PluginName {
     #[default]
@@ -615,7 +1230,7 @@ pub enum PluginName {
     PluginManifest,
 }
 
-#[derive(Display, Debug, Serialize, Deserialize)]
+#[derive(Display, Debug, Serialize, Deserialize, Clone, ToSchema)]
 enum PluginType {
     #[serde(rename = "file")]
     File,
@@ -623,12 +1238,13 @@ enum PluginType {
     Directory,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
 pub struct Plugin {
     name: PluginName,
     #[serde(rename = "type")]
     ty: PluginType,
     #[serde(with = "mtime_format")]
+    #[schema(value_type = String, example = "Tue, 08 Jul 2025 17:39:36 GMT")]
     mtime: DateTime<Utc>,
     size: Option<i32>,
 }
@@ -643,7 +1259,7 @@ mod mtime_format {
     use chrono::{DateTime, NaiveDateTime, Utc};
     use serde::{self, Deserialize, Deserializer, Serializer};
 
-    const FORMAT: &'static str = "%a, %d %b %Y %H:%M:%S %Z";
+    const FORMAT: &str = "%a, %d %b %Y %H:%M:%S %Z";
 
     pub fn serialize<S>(date: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -669,6 +1285,54 @@ mod test {
     use chrono::{Datelike, Local};
     use rstest::*;
 
+    #[rstest]
+    #[case("Kai'Sa", "kaisa")]
+    #[case("Nunu & Willump", "nunuwillump")]
+    #[case("Dr. Mundo", "drmundo")]
+    #[case("MonkeyKing", "monkeyking")]
+    fn slugs(#[case] name: &str, #[case] expected: &str) {
+        assert_eq!(slug(name), expected);
+    }
+
+    #[rstest]
+    #[case("62")]
+    #[case("wukong")]
+    #[case("Wukong")]
+    #[case("MonkeyKing")]
+    #[case("monkey-king")]
+    fn champion_keys(#[case] key: &str) {
+        let mut cdrag = CDragon::default();
+        let wukong = Champion {
+            id: 62,
+            name: "Wukong".into(),
+            alias: "MonkeyKing".into(),
+            ..Default::default()
+        };
+        cdrag.set_champions([(wukong.id, wukong)].into());
+        assert!(
+            cdrag
+                .champion_by_key(key)
+                .is_some_and(|champ| champ.id == 62)
+        );
+    }
+
+    #[test]
+    fn links_identical_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths: Vec<PathBuf> = ["a.jpg", "b.jpg", "c.jpg", "missing.jpg"]
+            .iter()
+            .map(|name| dir.path().join(name))
+            .collect();
+        fs::write(&paths[0], b"group splash").unwrap();
+        fs::write(&paths[1], b"group splash").unwrap();
+        fs::write(&paths[2], b"other splash").unwrap();
+
+        assert_eq!(link_identical(&paths).unwrap(), 12);
+        assert_eq!(link_identical(&paths).unwrap(), 0, "already linked");
+        assert_eq!(fs::read(&paths[1]).unwrap(), b"group splash");
+        assert_eq!(fs::read(&paths[2]).unwrap(), b"other splash");
+    }
+
     #[tokio::test]
     async fn get_plugs() {
         let res = CDragon::default().fetch_plugins().await;
@@ -681,6 +1345,7 @@ mod test {
     }
 
     #[tokio::test]
+    #[allow(clippy::len_zero)]
     async fn get_champ_ids() {
         let res = CDragon::default().fetch_champion_ids().await;
         assert!(res.is_ok_and(|ids| ids.len() > 0))
@@ -715,6 +1380,7 @@ mod test {
 
     #[rstest]
     #[tokio::test]
+    #[allow(clippy::len_zero)]
     async fn all_champs(#[future] cdrag_instance: anyhow::Result<CDragon>) -> anyhow::Result<()> {
         let cdrag = cdrag_instance.await?;
         assert!(cdrag.champions.len() > 0);
@@ -733,14 +1399,16 @@ mod test {
     #[tokio::test]
     async fn cleanup(#[future] cdrag_instance: anyhow::Result<CDragon>) -> anyhow::Result<()> {
         let cdrag = cdrag_instance.await?;
-        cdrag.clean_up()?;
+        fs::create_dir_all(cdrag.data_dir.join("derived"))?;
+        fs::write(cdrag.data_dir.join("fourth-shot.db"), "")?;
+        cdrag.clean_up(&["derived"])?;
 
         let cache_exists = cdrag.cache_dir.try_exists().unwrap_or(false);
         assert!(!cache_exists);
-        let config_exists = cdrag.config_dir.try_exists().unwrap_or(false);
-        assert!(!config_exists);
-        let data_exists = cdrag.data_dir.try_exists().unwrap_or(false);
-        assert!(!data_exists);
+        for dir in [ASSETS, "derived"] {
+            assert!(!cdrag.data_dir.join(dir).try_exists().unwrap_or(false));
+        }
+        assert!(cdrag.data_dir.join("fourth-shot.db").is_file());
 
         Ok(())
     }
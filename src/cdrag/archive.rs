@@ -0,0 +1,465 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{ASSETS, CDragon, CacheFile, PluginName, store::format_bytes};
+
+/// Bumped whenever the layout changes. Archives of any other format are refused.
+pub const ARCHIVE_FORMAT: u32 = 1;
+/// The first entry of every archive
+const MANIFEST: &str = "manifest.json";
+/// Archive directories holding `cache_dir` and `data_dir` files
+const CACHE: &str = "cache";
+const DATA: &str = "data";
+const CACHE_FILES: [CacheFile; 3] = [
+    CacheFile::Plugins,
+    CacheFile::Champions,
+    CacheFile::SkinLines,
+];
+const ZSTD_LEVEL: i32 = 3;
+
+/// Describes an archive made by [`CDragon::export`]: a `.tar.zst` holding this as
+/// `manifest.json`, then the cached JSON under `cache/` and the downloaded assets under
+/// `data/assets/`. The database isn't included.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// [`ARCHIVE_FORMAT`] of the fourth-shot that made it
+    pub format: u32,
+    pub created_at: DateTime<Utc>,
+    /// When CommunityDragon last updated the game data, which it does every patch
+    pub game_data_updated: Option<DateTime<Utc>>,
+    pub files: Vec<ArchivedFile>,
+}
+
+/// A file in an archive, checked against its hash on import
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchivedFile {
+    /// Within the archive, e.g. `cache/champions.json`
+    pub path: String,
+    pub bytes: u64,
+    /// Lowercase hex
+    pub sha256: String,
+}
+
+impl Manifest {
+    pub fn bytes(&self) -> u64 {
+        self.files.iter().map(|file| file.bytes).sum()
+    }
+}
+
+impl Display for Manifest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} files, {}",
+            self.files.len(),
+            format_bytes(self.bytes())
+        )?;
+        if let Some(updated) = self.game_data_updated {
+            write!(f, ", of game data from {}", updated.format("%Y-%m-%d"))?;
+        }
+        Ok(())
+    }
+}
+
+impl CDragon {
+    /// Writes the cached data and every downloaded asset to a `.tar.zst` archive at `dest`, for
+    /// [`CDragon::import`] on a machine that can't reach CommunityDragon
+    pub fn export(&self, dest: &Path) -> anyhow::Result<Manifest> {
+        let mut sources = vec![];
+        for cache_file in CACHE_FILES {
+            let path = self.cache_dir.join(cache_file.to_string());
+            if !path.is_file() {
+                bail!(
+                    "{cache_file} isn't cached in {:?}, so sync first",
+                    self.cache_dir
+                );
+            }
+            sources.push((format!("{CACHE}/{cache_file}"), path));
+        }
+        let mut assets = vec![];
+        walk(&self.data_dir.join(ASSETS), &mut assets)?;
+        assets.sort();
+        for path in assets {
+            let relative = path.strip_prefix(&self.data_dir)?;
+            let name = relative
+                .components()
+                .map(|part| part.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            sources.push((format!("{DATA}/{name}"), path));
+        }
+
+        let mut files = vec![];
+        for (name, path) in &sources {
+            let file = File::open(path).with_context(|| format!("couldn't read {path:?}"))?;
+            let (bytes, sha256) = copy_hashed(file, io::sink())?;
+            files.push(ArchivedFile {
+                path: name.clone(),
+                bytes,
+                sha256,
+            });
+        }
+        let manifest = Manifest {
+            format: ARCHIVE_FORMAT,
+            created_at: Utc::now(),
+            game_data_updated: self
+                .plugins
+                .iter()
+                .find(|plugin| plugin.name == PluginName::RcpBeLolGameData)
+                .map(|plugin| plugin.mtime),
+            files,
+        };
+
+        let written = write_archive(dest, &manifest, &sources);
+        if written.is_err() {
+            fs::remove_file(dest).ok();
+        }
+        written.with_context(|| format!("couldn't write the archive {dest:?}"))?;
+        Ok(manifest)
+    }
+
+    /// Unpacks an archive made by [`CDragon::export`] into `cache_dir` and `data_dir`, then
+    /// loads the data. Every file is checked against the manifest before anything is replaced,
+    /// so a corrupt archive changes nothing. Assets already downloaded here are kept.
+    pub fn import(&mut self, archive: &Path) -> anyhow::Result<Manifest> {
+        let file = File::open(archive).with_context(|| format!("couldn't open {archive:?}"))?;
+        let mut tar = tar::Archive::new(zstd::Decoder::new(file)?);
+        let mut entries = tar.entries()?;
+        let manifest: Manifest = match entries.next() {
+            Some(entry) => {
+                let entry = entry?;
+                if entry.path()? != Path::new(MANIFEST) {
+                    bail!("{archive:?} doesn't start with a {MANIFEST}");
+                }
+                serde_json::from_reader(entry)
+                    .with_context(|| format!("{archive:?} has an unreadable {MANIFEST}"))?
+            }
+            None => bail!("{archive:?} is empty"),
+        };
+        if manifest.format != ARCHIVE_FORMAT {
+            bail!(
+                "{archive:?} is in archive format {}, but this fourth-shot only reads format \
+                 {ARCHIVE_FORMAT}",
+                manifest.format
+            );
+        }
+
+        let mut staged = vec![];
+        let unpacked = (|| {
+            let mut expected: HashMap<&str, &ArchivedFile> = manifest
+                .files
+                .iter()
+                .map(|file| (file.path.as_str(), file))
+                .collect();
+            for entry in entries {
+                let mut entry = entry?;
+                let name = entry.path()?.to_string_lossy().into_owned();
+                let Some(file) = expected.remove(name.as_str()) else {
+                    bail!("{name} isn't in the manifest");
+                };
+                if !entry.header().entry_type().is_file() {
+                    bail!("{name} isn't a regular file");
+                }
+                let dest = self.destination(&name)?;
+                let temp = importing(&dest);
+                fs::create_dir_all(dest.parent().unwrap_or(Path::new("")))?;
+                let out =
+                    File::create(&temp).with_context(|| format!("couldn't write {temp:?}"))?;
+                staged.push((temp, dest));
+                let (bytes, sha256) = copy_hashed(&mut entry, out)?;
+                if bytes != file.bytes || sha256 != file.sha256 {
+                    bail!("{name} doesn't match the manifest, so the archive is corrupt");
+                }
+            }
+            if let Some(missing) = expected.keys().min() {
+                bail!("{missing} is in the manifest but not the archive");
+            }
+            anyhow::Ok(())
+        })();
+        if let Err(err) = unpacked {
+            for (temp, _) in &staged {
+                fs::remove_file(temp).ok();
+            }
+            return Err(err.context(format!("couldn't import {archive:?}")));
+        }
+        for (temp, dest) in &staged {
+            fs::rename(temp, dest).with_context(|| format!("couldn't replace {dest:?}"))?;
+        }
+
+        self.plugins = self.load_obj(CacheFile::Plugins)?;
+        let champions = self.load_obj(CacheFile::Champions)?;
+        self.set_champions(champions);
+        self.skin_lines = self.load_obj(CacheFile::SkinLines)?;
+        if let Some(store) = &self.store {
+            store.reindex(self.known_assets())?;
+        }
+        Ok(manifest)
+    }
+
+    /// Where a file in an archive goes. Anything but the cache files and assets is refused,
+    /// as is any path that could escape `data_dir`.
+    fn destination(&self, name: &str) -> anyhow::Result<PathBuf> {
+        if let Some(cache_file) = name.strip_prefix(&format!("{CACHE}/")) {
+            if CACHE_FILES
+                .iter()
+                .any(|file| file.to_string() == cache_file)
+            {
+                return Ok(self.cache_dir.join(cache_file));
+            }
+        } else if let Some(asset) = name.strip_prefix(&format!("{DATA}/")) {
+            let asset = Path::new(asset);
+            if asset.starts_with(ASSETS)
+                && asset
+                    .components()
+                    .all(|part| matches!(part, Component::Normal(_)))
+            {
+                return Ok(self.data_dir.join(asset));
+            }
+        }
+        bail!("{name} doesn't belong in an archive")
+    }
+}
+
+/// Where a file is unpacked before the whole archive checks out
+fn importing(dest: &Path) -> PathBuf {
+    let mut name = dest.file_name().unwrap_or_default().to_os_string();
+    name.push(".importing");
+    dest.with_file_name(name)
+}
+
+fn write_archive(
+    dest: &Path,
+    manifest: &Manifest,
+    sources: &[(String, PathBuf)],
+) -> anyhow::Result<()> {
+    let file = File::create(dest)?;
+    let mut tar = tar::Builder::new(zstd::Encoder::new(file, ZSTD_LEVEL)?);
+    let mtime = manifest.created_at.timestamp().try_into().unwrap_or(0);
+    let mut append = |name: &str, bytes: u64, data: &mut dyn Read| {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(bytes);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+        tar.append_data(&mut header, name, data)
+    };
+
+    let json = serde_json::to_vec_pretty(manifest)?;
+    append(MANIFEST, json.len() as u64, &mut json.as_slice())?;
+    for ((name, path), file) in sources.iter().zip(&manifest.files) {
+        // Read no more than was hashed, in case the file grew since
+        let mut data = File::open(path)?.take(file.bytes);
+        append(name, file.bytes, &mut data)?;
+    }
+    tar.into_inner()?.finish()?.sync_all()?;
+    Ok(())
+}
+
+/// Copies everything, returning how many bytes there were and their hash
+fn copy_hashed(mut reader: impl Read, mut writer: impl Write) -> io::Result<(u64, String)> {
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+    let mut bytes = 0;
+    loop {
+        let read = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        hasher.update(&buf[..read]);
+        writer.write_all(&buf[..read])?;
+        bytes += read as u64;
+    }
+    writer.flush()?;
+    Ok((bytes, format!("{:x}", hasher.finalize())))
+}
+
+/// Collects every file under `dir`, which might not exist
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> anyhow::Result<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err).with_context(|| format!("couldn't list {dir:?}")),
+    };
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() {
+            walk(&path, files)?;
+        } else if path.is_file() && path.extension().is_none_or(|ext| ext != "importing") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        app::fixtures::offline_cdrag,
+        cdrag::{prefetch::PrefetchPolicy, store::AssetStore},
+        db::Db,
+    };
+    use rstest::*;
+    use std::sync::Arc;
+
+    const SPLASH: &str = "assets/characters/kaisa/skins/skin01/kaisa_splash_uncentered_1.jpg";
+
+    /// A machine that has never synced
+    fn machine(dir: &Path) -> CDragon {
+        CDragon {
+            cache_dir: dir.join("cache"),
+            data_dir: dir.join("data"),
+            ..Default::default()
+        }
+    }
+
+    /// A machine with the offline data cached and one asset downloaded
+    fn synced(dir: &Path) -> CDragon {
+        let mut cdrag = offline_cdrag();
+        cdrag.cache_dir = dir.join("cache");
+        cdrag.data_dir = dir.join("data");
+        cdrag.cache_obj(&cdrag.plugins, CacheFile::Plugins).unwrap();
+        cdrag
+            .cache_obj(&cdrag.champions, CacheFile::Champions)
+            .unwrap();
+        cdrag
+            .cache_obj(&cdrag.skin_lines, CacheFile::SkinLines)
+            .unwrap();
+        let splash = cdrag.data_dir.join(SPLASH);
+        fs::create_dir_all(splash.parent().unwrap()).unwrap();
+        fs::write(splash, b"splash").unwrap();
+        cdrag
+    }
+
+    fn archived(path: &str, data: &[u8]) -> ArchivedFile {
+        let (bytes, sha256) = copy_hashed(data, io::sink()).unwrap();
+        ArchivedFile {
+            path: path.into(),
+            bytes,
+            sha256,
+        }
+    }
+
+    #[test]
+    fn round_trips_to_an_empty_machine() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("fourth-shot.tar.zst");
+        let exported = synced(&dir.path().join("online")).export(&archive).unwrap();
+        assert_eq!(exported.files.len(), 4);
+        assert_eq!(
+            exported.files[3],
+            archived(&format!("data/{SPLASH}"), b"splash")
+        );
+
+        let offline = dir.path().join("offline");
+        let cdrag = machine(&offline);
+        let store = AssetStore::new(
+            Arc::new(Db::open_in_memory().unwrap()),
+            cdrag.data_dir.clone(),
+            None,
+        );
+        let mut cdrag = cdrag.with_store(store);
+        let imported = cdrag.import(&archive).unwrap();
+        assert_eq!(imported, exported);
+        assert_eq!(cdrag.champions.len(), 2);
+        assert_eq!(
+            fs::read(offline.join("data").join(SPLASH)).unwrap(),
+            b"splash"
+        );
+        assert_eq!(cdrag.store().unwrap().usage().unwrap().files, 1);
+    }
+
+    #[tokio::test]
+    async fn offline_sync_needs_an_import() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("fourth-shot.tar.zst");
+        synced(&dir.path().join("online")).export(&archive).unwrap();
+        let mut cdrag = CDragon {
+            offline: true,
+            ..machine(&dir.path().join("offline"))
+        };
+        let policy = PrefetchPolicy::default();
+        let err = cdrag.sync(&policy).await.unwrap_err();
+        assert!(err.to_string().contains("import"), "{err}");
+
+        cdrag.import(&archive).unwrap();
+        cdrag.sync(&policy).await.unwrap();
+        assert!(cdrag.local_asset(Path::new(SPLASH)).await.is_ok());
+        assert!(
+            cdrag
+                .local_asset(Path::new("assets/never-downloaded.jpg"))
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn nothing_to_export_before_a_sync() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("fourth-shot.tar.zst");
+        let err = machine(dir.path()).export(&archive).unwrap_err();
+        assert!(err.to_string().contains("sync first"), "{err}");
+        assert!(!archive.exists());
+    }
+
+    const A: (&str, &str) = ("data/assets/a.jpg", "a");
+
+    #[rstest]
+    #[case::newer_format(ARCHIVE_FORMAT + 1, &[A], &[A], "archive format")]
+    #[case::corrupt(ARCHIVE_FORMAT, &[A], &[("data/assets/a.jpg", "tampered")], "corrupt")]
+    #[case::unlisted(ARCHIVE_FORMAT, &[A], &[A, ("data/assets/b.jpg", "b")], "isn't in the manifest")]
+    #[case::escaping(ARCHIVE_FORMAT, &[("data/assets/../../x", "x")], &[("data/assets/../../x", "x")], "doesn't belong")]
+    #[case::missing(ARCHIVE_FORMAT, &[A], &[], "not the archive")]
+    fn refuses_bad_archives(
+        #[case] format: u32,
+        #[case] listed: &[(&str, &str)],
+        #[case] packed: &[(&str, &str)],
+        #[case] expected: &str,
+    ) {
+        let manifest = Manifest {
+            format,
+            created_at: Utc::now(),
+            game_data_updated: None,
+            files: listed
+                .iter()
+                .map(|(path, data)| archived(path, data.as_bytes()))
+                .collect(),
+        };
+        let mut tar = tar::Builder::new(Vec::new());
+        let json = serde_json::to_vec(&manifest).unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_size(json.len() as u64);
+        tar.append_data(&mut header, MANIFEST, json.as_slice())
+            .unwrap();
+        for (path, data) in packed {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            // `append_data` refuses `..`, so the name is set by hand
+            header.as_old_mut().name[..path.len()].copy_from_slice(path.as_bytes());
+            header.set_cksum();
+            tar.append(&header, data.as_bytes()).unwrap();
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("bad.tar.zst");
+        let tar = tar.into_inner().unwrap();
+        fs::write(&archive, zstd::encode_all(tar.as_slice(), 0).unwrap()).unwrap();
+
+        let mut cdrag = machine(&dir.path().join("machine"));
+        let err = format!("{:#}", cdrag.import(&archive).unwrap_err());
+        assert!(err.contains(expected), "{err}");
+        let a = cdrag.data_dir.join(ASSETS).join("a.jpg");
+        assert!(!a.exists());
+        assert!(!importing(&a).exists());
+    }
+}
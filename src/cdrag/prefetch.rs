@@ -0,0 +1,326 @@
+use std::{
+    collections::HashSet,
+    fmt::Display,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{Context, bail};
+use serde::{Deserialize, Serialize};
+use tokio::{sync::Semaphore, task::JoinSet};
+
+use super::{CDragon, Champion, SkinAsset, store::format_bytes};
+use crate::metrics::{Fetch, FetchLabels, METRICS};
+
+/// How many size checks are in flight at once
+const CONCURRENT_HEADS: usize = 16;
+
+/// Which assets `sync` downloads ahead of time. Anything left out is still downloaded the first
+/// time a page needs it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PrefetchPolicy {
+    /// Skin assets to download for every selected skin
+    pub assets: Vec<SkinAsset>,
+    /// Whether to download the selected champions' square portraits
+    pub icons: bool,
+    /// Names, aliases or ids. Empty means every champion.
+    pub champions: Vec<String>,
+    /// Skin line ids. Empty means every skin, in a line or not; otherwise only skins in these
+    /// lines, of the selected champions.
+    pub skin_lines: Vec<u64>,
+    /// Whether to include skins that are no longer sold
+    pub legacy: bool,
+    /// Stop adding files once they'd take up this many bytes in total
+    pub max_bytes: Option<u64>,
+}
+
+impl Default for PrefetchPolicy {
+    fn default() -> Self {
+        PrefetchPolicy {
+            assets: vec![SkinAsset::UncenteredSplash],
+            icons: true,
+            champions: vec![],
+            skin_lines: vec![],
+            legacy: true,
+            max_bytes: None,
+        }
+    }
+}
+
+/// One file a [`PrefetchPlan`] would download
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedFile {
+    /// [Normalized Path](super::Skin) of the asset
+    pub path: PathBuf,
+    /// `None` until measured, or if CDragon doesn't say
+    pub bytes: Option<u64>,
+}
+
+/// What a [`PrefetchPolicy`] comes to against the current data and `data_dir`
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PrefetchPlan {
+    pub files: Vec<PlannedFile>,
+    /// Selected files that are already downloaded
+    pub present: usize,
+    /// Selected files left out to stay within `max_bytes`
+    pub over_budget: usize,
+}
+
+impl PrefetchPlan {
+    /// Total size of the files whose size is known
+    pub fn bytes(&self) -> u64 {
+        self.files.iter().filter_map(|file| file.bytes).sum()
+    }
+
+    /// Drops files, in order, once the running total would pass `max_bytes`. Files of unknown
+    /// size are kept.
+    fn apply_budget(&mut self, max_bytes: u64) {
+        let mut total = 0;
+        let before = self.files.len();
+        self.files.retain(|file| {
+            let bytes = file.bytes.unwrap_or(0);
+            if total + bytes > max_bytes {
+                return false;
+            }
+            total += bytes;
+            true
+        });
+        self.over_budget = before - self.files.len();
+    }
+}
+
+impl Display for PrefetchPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let unknown = self
+            .files
+            .iter()
+            .filter(|file| file.bytes.is_none())
+            .count();
+        write!(
+            f,
+            "{} files, {}",
+            self.files.len(),
+            format_bytes(self.bytes())
+        )?;
+        if unknown > 0 {
+            write!(f, " ({unknown} of unknown size)")?;
+        }
+        write!(f, "; {} already downloaded", self.present)?;
+        if self.over_budget > 0 {
+            write!(
+                f,
+                "; {} left out to stay within max_bytes",
+                self.over_budget
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl CDragon {
+    /// The [Normalized Paths](super::Skin) the policy selects, icons first, in a stable order
+    fn prefetch_candidates(&self, policy: &PrefetchPolicy) -> anyhow::Result<Vec<PathBuf>> {
+        let mut champions: Vec<&Champion> = if policy.champions.is_empty() {
+            self.champions.values().collect()
+        } else {
+            policy
+                .champions
+                .iter()
+                .map(|key| {
+                    self.champion_by_key(key)
+                        .with_context(|| format!("prefetch: no champion is called {key:?}"))
+                })
+                .collect::<anyhow::Result<_>>()?
+        };
+        champions.sort_by_key(|champ| champ.id);
+        champions.dedup_by_key(|champ| champ.id);
+        for id in &policy.skin_lines {
+            if self.skin_line_by_id(*id).is_none() {
+                bail!("prefetch: there's no skin line {id}");
+            }
+        }
+
+        let mut paths = vec![];
+        if policy.icons {
+            paths.extend(
+                champions
+                    .iter()
+                    .map(|champ| &champ.square_portrait_path)
+                    .cloned(),
+            );
+        }
+        for champ in &champions {
+            let skins = champ.skins.iter().filter(|skin| {
+                (policy.legacy || !skin.is_legacy)
+                    && (policy.skin_lines.is_empty()
+                        || skin
+                            .skin_lines
+                            .iter()
+                            .any(|id| policy.skin_lines.contains(id)))
+            });
+            for skin in skins {
+                for asset in &policy.assets {
+                    paths.push(self.skin_path_of(skin, asset)?.to_string_lossy().into());
+                }
+            }
+        }
+        let mut seen = HashSet::new();
+        Ok(paths
+            .into_iter()
+            .filter(|path: &String| !path.is_empty() && seen.insert(path.clone()))
+            .map(PathBuf::from)
+            .collect())
+    }
+
+    /// Works out which files the policy would download. With `measure`, or a `max_bytes`, each
+    /// file's size is asked of CDragon first, which takes a request per file.
+    pub async fn plan_prefetch(
+        &self,
+        policy: &PrefetchPolicy,
+        measure: bool,
+    ) -> anyhow::Result<PrefetchPlan> {
+        let mut plan = PrefetchPlan::default();
+        for path in self.prefetch_candidates(policy)? {
+            if self.data_dir.join(&path).is_file() {
+                plan.present += 1;
+            } else {
+                plan.files.push(PlannedFile { path, bytes: None });
+            }
+        }
+        if measure || policy.max_bytes.is_some() {
+            self.measure(&mut plan.files).await?;
+        }
+        if let Some(max_bytes) = policy.max_bytes {
+            plan.apply_budget(max_bytes);
+        }
+        Ok(plan)
+    }
+
+    /// Fills in the size of each file from a HEAD request
+    async fn measure(&self, files: &mut [PlannedFile]) -> anyhow::Result<()> {
+        let permits = Arc::new(Semaphore::new(CONCURRENT_HEADS));
+        let mut heads = JoinSet::new();
+        for (i, file) in files.iter().enumerate() {
+            let (client, url, permits) = (
+                self.http_client.clone(),
+                self.asset_url(&file.path),
+                permits.clone(),
+            );
+            heads.spawn(async move {
+                let _permit = permits.acquire_owned().await?;
+                let labels = FetchLabels {
+                    kind: Fetch::AssetSize,
+                };
+                METRICS.fetches.get_or_create(&labels).inc();
+                let res = match client
+                    .head(&url)
+                    .send()
+                    .await
+                    .and_then(|res| res.error_for_status())
+                {
+                    Ok(res) => res,
+                    Err(err) => {
+                        METRICS.fetch_errors.get_or_create(&labels).inc();
+                        return Err(err.into());
+                    }
+                };
+                // `Response::content_length` is the body's, and a HEAD response has none
+                let bytes = res
+                    .headers()
+                    .get(reqwest::header::CONTENT_LENGTH)
+                    .and_then(|len| len.to_str().ok()?.parse().ok());
+                anyhow::Ok((i, bytes))
+            });
+        }
+        while let Some(head) = heads.join_next().await {
+            let (i, bytes) = head.context("checking an asset's size panicked")??;
+            files[i].bytes = bytes;
+        }
+        Ok(())
+    }
+
+    /// Downloads every file in the plan
+    pub async fn prefetch(&self, plan: &PrefetchPlan) -> anyhow::Result<()> {
+        for file in &plan.files {
+            self.local_asset(Path::new(&file.path)).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::app::fixtures::offline_cdrag;
+    use rstest::*;
+
+    fn planned(bytes: &[Option<u64>]) -> PrefetchPlan {
+        PrefetchPlan {
+            files: bytes
+                .iter()
+                .enumerate()
+                .map(|(i, bytes)| PlannedFile {
+                    path: format!("{i}.jpg").into(),
+                    bytes: *bytes,
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[rstest]
+    #[case(PrefetchPolicy::default(), &["annie_splash", "kaisa_splash"])]
+    #[case(PrefetchPolicy { legacy: false, ..Default::default() }, &["kaisa_splash"])]
+    #[case(
+        PrefetchPolicy { champions: vec!["annie".into()], ..Default::default() },
+        &["annie_splash"]
+    )]
+    #[case(
+        PrefetchPolicy { skin_lines: vec![7], legacy: false, ..Default::default() },
+        &["kaisa_splash"]
+    )]
+    #[case(PrefetchPolicy { assets: vec![SkinAsset::Tile], ..Default::default() }, &[])]
+    fn selects_skins_by_policy(#[case] policy: PrefetchPolicy, #[case] expected: &[&str]) {
+        let paths = offline_cdrag().prefetch_candidates(&policy).unwrap();
+        assert_eq!(paths.len(), expected.len(), "{paths:?}");
+        for (path, expected) in paths.iter().zip(expected) {
+            assert!(path.to_string_lossy().contains(expected), "{path:?}");
+        }
+    }
+
+    #[rstest]
+    #[case(PrefetchPolicy { champions: vec!["teemo".into()], ..Default::default() })]
+    #[case(PrefetchPolicy { skin_lines: vec![8], ..Default::default() })]
+    fn unknown_selections_are_errors(#[case] policy: PrefetchPolicy) {
+        assert!(offline_cdrag().prefetch_candidates(&policy).is_err());
+    }
+
+    #[test]
+    fn budget_drops_what_does_not_fit() {
+        let mut plan = planned(&[Some(600), Some(500), None, Some(400)]);
+        plan.apply_budget(1000);
+        let kept: Vec<_> = plan.files.iter().map(|file| file.bytes).collect();
+        assert_eq!(kept, [Some(600), None, Some(400)]);
+        assert_eq!(plan.over_budget, 1);
+        assert_eq!(plan.bytes(), 1000);
+    }
+
+    #[tokio::test]
+    async fn downloaded_files_are_not_planned() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cdrag = offline_cdrag();
+        cdrag.data_dir = dir.path().to_path_buf();
+        let skin = cdrag.skin_by_id(145001).unwrap();
+        let path = cdrag.data_dir.join(&skin.uncentered_splash_path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, b"splash").unwrap();
+
+        let plan = cdrag
+            .plan_prefetch(&PrefetchPolicy::default(), false)
+            .await
+            .unwrap();
+        assert_eq!(plan.present, 1);
+        assert_eq!(plan.files.len(), 1);
+    }
+}
@@ -0,0 +1,264 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+
+use crate::db::Db;
+
+/// Keeps track of the assets downloaded into `data_dir`, their sizes and when each was last
+/// used, and keeps them under a quota by deleting the least recently used. Pinned assets, like
+/// champion icons, count toward the quota but are never deleted.
+#[derive(Debug, Clone)]
+pub struct AssetStore {
+    db: Arc<Db>,
+    data_dir: PathBuf,
+    /// Bytes, unlimited if `None`
+    quota: Option<u64>,
+}
+
+/// What's stored against the quota
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct StoreUsage {
+    pub files: u64,
+    pub bytes: u64,
+    pub pinned_files: u64,
+    pub pinned_bytes: u64,
+    pub quota: Option<u64>,
+}
+
+impl StoreUsage {
+    /// How full the store is, as a whole percentage of the quota
+    pub fn percent(&self) -> Option<u64> {
+        self.quota
+            .map(|quota| (self.bytes * 100).checked_div(quota).unwrap_or(100))
+    }
+}
+
+impl std::fmt::Display for StoreUsage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} in {} files ({} pinned)",
+            format_bytes(self.bytes),
+            self.files,
+            self.pinned_files
+        )?;
+        if let (Some(quota), Some(percent)) = (self.quota, self.percent()) {
+            write!(f, " of a {} quota, {percent}% used", format_bytes(quota))?;
+        }
+        Ok(())
+    }
+}
+
+/// What [`AssetStore::enforce_quota`] deleted
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Eviction {
+    pub files: u64,
+    pub bytes: u64,
+}
+
+/// `1234567` as `1.2 MB`
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1000 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64;
+    let mut unit = "";
+    for next in UNITS {
+        size /= 1000.0;
+        unit = next;
+        if size < 1000.0 {
+            break;
+        }
+    }
+    format!("{size:.1} {unit}")
+}
+
+impl AssetStore {
+    pub fn new(db: Arc<Db>, data_dir: PathBuf, quota: Option<u64>) -> Self {
+        AssetStore {
+            db,
+            data_dir,
+            quota,
+        }
+    }
+
+    fn key(asset_path: &Path) -> String {
+        asset_path.to_string_lossy().into_owned()
+    }
+
+    /// Records that the asset at the [Normalized Path](super::Skin) was just downloaded
+    pub fn record(&self, asset_path: &Path, pinned: bool) -> anyhow::Result<()> {
+        let file = self.data_dir.join(asset_path);
+        let bytes = fs::metadata(&file)
+            .with_context(|| format!("couldn't read the size of {file:?}"))?
+            .len();
+        self.db
+            .record_asset(&Self::key(asset_path), bytes, pinned, Utc::now())
+    }
+
+    /// Records that the asset was just used. Assets downloaded before the store tracked them
+    /// are picked up by [`AssetStore::reindex`] instead.
+    pub fn touch(&self, asset_path: &Path) -> anyhow::Result<()> {
+        self.db.touch_asset(&Self::key(asset_path), Utc::now())?;
+        Ok(())
+    }
+
+    /// Brings the index in line with the disk: adds the `known` assets that are downloaded but
+    /// not indexed, using their modification time as their last access, and forgets indexed
+    /// assets that were deleted.
+    pub fn reindex(&self, known: impl IntoIterator<Item = (PathBuf, bool)>) -> anyhow::Result<()> {
+        for (asset_path, pinned) in known {
+            let Ok(metadata) = fs::metadata(self.data_dir.join(&asset_path)) else {
+                continue;
+            };
+            let modified: DateTime<Utc> =
+                metadata.modified().map_or_else(|_| Utc::now(), Into::into);
+            self.db.record_found_asset(
+                &Self::key(&asset_path),
+                metadata.len(),
+                pinned,
+                modified,
+            )?;
+        }
+        for path in self.db.asset_paths()? {
+            if !self.data_dir.join(&path).is_file() {
+                self.db.forget_asset(&path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Deletes the least recently used unpinned assets until the store fits its quota. `keep`,
+    /// usually what was just downloaded, is never deleted.
+    pub fn enforce_quota(&self, keep: Option<&Path>) -> anyhow::Result<Eviction> {
+        let mut eviction = Eviction::default();
+        let Some(quota) = self.quota else {
+            return Ok(eviction);
+        };
+        let mut used = self.db.asset_totals()?.bytes;
+        let keep = keep.map(Self::key);
+        for (path, bytes) in self.db.evictable_assets()? {
+            if used <= quota {
+                break;
+            }
+            if keep.as_ref() == Some(&path) {
+                continue;
+            }
+            let file = self.data_dir.join(&path);
+            match fs::remove_file(&file) {
+                Ok(()) => {
+                    eviction.files += 1;
+                    eviction.bytes += bytes;
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err).with_context(|| format!("couldn't evict {file:?}")),
+            }
+            self.db.forget_asset(&path)?;
+            used = used.saturating_sub(bytes);
+        }
+        Ok(eviction)
+    }
+
+    pub fn usage(&self) -> anyhow::Result<StoreUsage> {
+        let totals = self.db.asset_totals()?;
+        Ok(StoreUsage {
+            files: totals.files,
+            bytes: totals.bytes,
+            pinned_files: totals.pinned_files,
+            pinned_bytes: totals.pinned_bytes,
+            quota: self.quota,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rstest::*;
+
+    fn store(quota: Option<u64>) -> (AssetStore, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = AssetStore::new(
+            Arc::new(Db::open_in_memory().unwrap()),
+            dir.path().to_path_buf(),
+            quota,
+        );
+        (store, dir)
+    }
+
+    fn download(store: &AssetStore, path: &str, bytes: usize, pinned: bool) {
+        let file = store.data_dir.join(path);
+        fs::create_dir_all(file.parent().unwrap()).unwrap();
+        fs::write(file, vec![0; bytes]).unwrap();
+        store.record(Path::new(path), pinned).unwrap();
+        // Distinct access times, so the order is the order of the calls
+        std::thread::sleep(std::time::Duration::from_millis(2));
+    }
+
+    #[test]
+    fn evicts_least_recently_used_first() {
+        let (store, _dir) = store(Some(1000));
+        download(&store, "assets/icon.png", 300, true);
+        download(&store, "assets/old.jpg", 400, false);
+        download(&store, "assets/used.jpg", 400, false);
+        download(&store, "assets/new.jpg", 400, false);
+        store.touch(Path::new("assets/used.jpg")).unwrap();
+
+        let eviction = store
+            .enforce_quota(Some(Path::new("assets/new.jpg")))
+            .unwrap();
+        assert_eq!(
+            eviction,
+            Eviction {
+                files: 2,
+                bytes: 800
+            }
+        );
+        assert!(!store.data_dir.join("assets/old.jpg").exists());
+        assert!(store.data_dir.join("assets/icon.png").exists());
+        assert!(store.data_dir.join("assets/new.jpg").exists());
+        let usage = store.usage().unwrap();
+        assert_eq!((usage.files, usage.bytes), (2, 700));
+        assert_eq!(usage.percent(), Some(70));
+    }
+
+    #[test]
+    fn pinned_assets_are_never_evicted() {
+        let (store, _dir) = store(Some(100));
+        download(&store, "assets/icon.png", 300, true);
+        assert_eq!(store.enforce_quota(None).unwrap(), Eviction::default());
+        assert_eq!(store.usage().unwrap().percent(), Some(300));
+    }
+
+    #[test]
+    fn reindex_matches_the_disk() {
+        let (store, _dir) = store(None);
+        download(&store, "assets/gone.jpg", 10, false);
+        fs::remove_file(store.data_dir.join("assets/gone.jpg")).unwrap();
+        fs::create_dir_all(store.data_dir.join("assets")).unwrap();
+        fs::write(store.data_dir.join("assets/found.jpg"), [0; 20]).unwrap();
+
+        store
+            .reindex([
+                (PathBuf::from("assets/found.jpg"), false),
+                (PathBuf::from("assets/never-downloaded.jpg"), false),
+            ])
+            .unwrap();
+        let usage = store.usage().unwrap();
+        assert_eq!((usage.files, usage.bytes), (1, 20));
+    }
+
+    #[rstest]
+    #[case(999, "999 B")]
+    #[case(1_234_567, "1.2 MB")]
+    #[case(5_000_000_000, "5.0 GB")]
+    fn formats_sizes(#[case] bytes: u64, #[case] expected: &str) {
+        assert_eq!(format_bytes(bytes), expected);
+    }
+}
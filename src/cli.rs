@@ -0,0 +1,317 @@
+use std::{net::IpAddr, path::PathBuf, sync::Arc};
+
+use anyhow::{Context, bail};
+use clap::{Args, Parser, Subcommand};
+
+use crate::{
+    app,
+    cdrag::{CDragon, Champion, SkinAsset, store::AssetStore},
+    config::{Config, LogFormat, Tls},
+    db::Db,
+};
+
+#[derive(Debug, Parser)]
+#[command(
+    version,
+    about = "Browse, download and remix League of Legends skin art"
+)]
+pub struct Cli {
+    /// Defaults to `serve`
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    #[command(flatten)]
+    pub overrides: Overrides,
+}
+
+/// Settings that take precedence over the config file, from flags or environment variables
+#[derive(Debug, Default, Args)]
+pub struct Overrides {
+    /// Read this config file instead of `config.toml` in the config directory
+    #[arg(long, global = true, env = "FOURTH_SHOT_CONFIG")]
+    pub config: Option<PathBuf>,
+    #[arg(long, global = true, env = "FOURTH_SHOT_BIND")]
+    pub bind: Option<IpAddr>,
+    #[arg(long, short, global = true, env = "FOURTH_SHOT_PORT")]
+    pub port: Option<u16>,
+    /// Serve HTTPS with this PEM certificate chain. Needs `--tls-key`.
+    #[arg(
+        long,
+        global = true,
+        requires = "tls_key",
+        env = "FOURTH_SHOT_TLS_CERT"
+    )]
+    pub tls_cert: Option<PathBuf>,
+    #[arg(
+        long,
+        global = true,
+        requires = "tls_cert",
+        env = "FOURTH_SHOT_TLS_KEY"
+    )]
+    pub tls_key: Option<PathBuf>,
+    /// Serve the app under this path, e.g. `/fourth-shot`
+    #[arg(long, global = true, env = "FOURTH_SHOT_PATH_PREFIX")]
+    pub path_prefix: Option<String>,
+    /// Trust `Forwarded` and `X-Forwarded-*` headers from a reverse proxy
+    #[arg(long, global = true, env = "FOURTH_SHOT_BEHIND_PROXY")]
+    pub behind_proxy: bool,
+    #[arg(long, global = true, env = "FOURTH_SHOT_DATA_DIR")]
+    pub data_dir: Option<PathBuf>,
+    #[arg(long, global = true, env = "FOURTH_SHOT_CACHE_DIR")]
+    pub cache_dir: Option<PathBuf>,
+    #[arg(long, global = true, env = "FOURTH_SHOT_CDRAGON_URL")]
+    pub cdragon_url: Option<String>,
+    /// Skin assets to download on sync, comma separated. The rest of the prefetch policy is
+    /// only in the config file.
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        value_delimiter = ',',
+        env = "FOURTH_SHOT_PREFETCH"
+    )]
+    pub prefetch: Option<Vec<SkinAsset>>,
+    /// Most bytes of downloaded assets to keep
+    #[arg(long, global = true, env = "FOURTH_SHOT_ASSET_QUOTA")]
+    pub asset_quota: Option<u64>,
+    /// Never contact CommunityDragon
+    #[arg(long, global = true, env = "FOURTH_SHOT_OFFLINE")]
+    pub offline: bool,
+    /// Log filter, e.g. `fourth_shot=info`
+    #[arg(long, global = true, env = "RUST_LOG")]
+    pub log: Option<String>,
+    #[arg(long, global = true, value_enum, env = "FOURTH_SHOT_LOG_FORMAT")]
+    pub log_format: Option<LogFormat>,
+}
+
+impl Overrides {
+    /// Loads the config file and applies the overrides on top, then validates the result
+    pub fn config(self) -> anyhow::Result<Config> {
+        let mut config = Config::load(self.config.as_deref())?;
+        if let Some(bind) = self.bind {
+            config.bind = bind;
+        }
+        if let Some(port) = self.port {
+            config.port = port;
+        }
+        if let (Some(cert), Some(key)) = (self.tls_cert, self.tls_key) {
+            config.tls = Some(Tls { cert, key });
+        }
+        if let Some(path_prefix) = self.path_prefix {
+            config.path_prefix = path_prefix;
+        }
+        if self.behind_proxy {
+            config.behind_proxy = true;
+        }
+        if let Some(data_dir) = self.data_dir {
+            config.data_dir = data_dir;
+        }
+        if let Some(cache_dir) = self.cache_dir {
+            config.cache_dir = cache_dir;
+        }
+        if let Some(cdragon_url) = self.cdragon_url {
+            config.cdragon_url = cdragon_url;
+        }
+        if let Some(assets) = self.prefetch {
+            config.prefetch.assets = assets;
+        }
+        if let Some(asset_quota) = self.asset_quota {
+            config.asset_quota = Some(asset_quota);
+        }
+        if self.offline {
+            config.offline = true;
+        }
+        if let Some(log) = self.log {
+            config.log = log;
+        }
+        if let Some(log_format) = self.log_format {
+            config.log_format = log_format;
+        }
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+#[derive(Debug, Default, Subcommand)]
+pub enum Command {
+    /// Sync with CommunityDragon, then serve the web app
+    #[default]
+    Serve,
+    /// Update the cached data and download what the prefetch policy selects, without serving
+    Sync {
+        /// Only report how many files and bytes would be downloaded
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Print a champion's data as JSON
+    Champion {
+        /// Name, alias or id, e.g. `kaisa`, `MonkeyKing` or `145`
+        key: String,
+    },
+    /// Download an asset of every skin that doesn't have it on disk yet
+    Download {
+        /// Only this champion's skins, by name, alias or id
+        #[arg(long)]
+        champion: Option<String>,
+        #[arg(long, value_enum)]
+        asset: SkinAsset,
+    },
+    /// Print whether each CommunityDragon plugin has changed since it was cached
+    Status,
+    /// Delete the cached data, downloaded assets and rendered images, but not the config file,
+    /// the database or creations
+    Clean,
+    /// Write the cached data and downloaded assets to a `.tar.zst` archive
+    Export {
+        /// e.g. `fourth-shot.tar.zst`
+        path: PathBuf,
+    },
+    /// Unpack an archive made by `export`, to run offline
+    Import { path: PathBuf },
+}
+
+impl Command {
+    /// Runs any command but `serve`, which needs the web app
+    pub async fn run(self, config: &Config) -> anyhow::Result<()> {
+        match self {
+            Command::Serve => bail!("serve is run by the binary"),
+            Command::Sync { dry_run: true } => {
+                let cdrag = loaded(config).await?;
+                let plan = cdrag.plan_prefetch(&config.prefetch, true).await?;
+                println!("would download {plan}");
+            }
+            Command::Sync { dry_run: false } => {
+                let mut cdrag = open(config)?;
+                cdrag.sync(&config.prefetch).await?;
+                println!(
+                    "synced {} champions and {} skin lines",
+                    cdrag.champions.len(),
+                    cdrag.skin_lines.len()
+                );
+            }
+            Command::Champion { key } => {
+                let cdrag = loaded(config).await?;
+                let champ = find_champion(&cdrag, &key)?;
+                println!("{}", serde_json::to_string_pretty(champ)?);
+            }
+            Command::Download { champion, asset } => {
+                let cdrag = loaded(config).await?;
+                let champions: Vec<&Champion> = match &champion {
+                    Some(key) => vec![find_champion(&cdrag, key)?],
+                    None => cdrag.champions.values().collect(),
+                };
+                let mut skins = 0;
+                for champ in champions {
+                    for skin in &champ.skins {
+                        cdrag
+                            .download_skin_asset(skin, &asset)
+                            .await
+                            .with_context(|| format!("couldn't download {}", skin.name))?;
+                        skins += 1;
+                    }
+                }
+                println!("{skins} skins have their {asset:?} in {:?}", cdrag.data_dir);
+            }
+            Command::Status => {
+                let cdrag = open(config)?;
+                for (name, status) in cdrag.plugin_statuses().await? {
+                    println!("{name:<32} {status}");
+                }
+                if let Some(store) = cdrag.store() {
+                    println!("\nassets: {}", store.usage()?);
+                }
+            }
+            Command::Clean => {
+                CDragon::open(config).clean_up(&app::RENDER_CACHES)?;
+                println!("removed the cached data, downloaded assets and rendered images");
+            }
+            Command::Export { path } => {
+                let manifest = CDragon::open(config).export(&path)?;
+                println!("exported {manifest} to {path:?}");
+            }
+            Command::Import { path } => {
+                let manifest = open(config)?.import(&path)?;
+                println!("imported {manifest}");
+                if !config.offline {
+                    println!("serve with --offline to stay off the network");
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The cached data, tracking downloads in the asset store
+fn open(config: &Config) -> anyhow::Result<CDragon> {
+    let db = Arc::new(Db::open(&config.data_dir)?);
+    let store = AssetStore::new(db, config.data_dir.clone(), config.asset_quota);
+    Ok(CDragon::open(config).with_store(store))
+}
+
+/// The cached data, or freshly fetched data if nothing has been cached yet
+async fn loaded(config: &Config) -> anyhow::Result<CDragon> {
+    let mut cdrag = open(config)?;
+    if cdrag.champions.is_empty() {
+        cdrag.update().await?;
+    }
+    Ok(cdrag)
+}
+
+fn find_champion<'a>(cdrag: &'a CDragon, key: &str) -> anyhow::Result<&'a Champion> {
+    cdrag
+        .champion_by_key(key)
+        .with_context(|| format!("no champion is called {key:?}"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use clap::CommandFactory;
+    use rstest::*;
+
+    #[test]
+    fn cli_is_well_formed() {
+        Cli::command().debug_assert();
+    }
+
+    #[rstest]
+    #[case(&[], "0.0.0.0:3000")]
+    #[case(&["serve", "--bind", "127.0.0.1", "-p", "8080"], "127.0.0.1:8080")]
+    #[case(&["-p", "8080", "sync"], "0.0.0.0:8080")]
+    fn flags_override_the_config_file(#[case] args: &[&str], #[case] addr: &str) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "port = 3000\n").unwrap();
+        let path = path.to_str().unwrap();
+        let cli =
+            Cli::try_parse_from(["fourth-shot", "--config", path].iter().chain(args)).unwrap();
+        let config = cli.overrides.config().unwrap();
+        assert_eq!(config.addr(), addr.parse().unwrap());
+    }
+
+    #[test]
+    fn invalid_overrides_are_refused() {
+        let cli = Cli::try_parse_from([
+            "fourth-shot",
+            "--config",
+            "/nonexistent/config.toml",
+            "sync",
+        ])
+        .unwrap();
+        assert!(cli.overrides.config().is_err());
+        let cli = Cli::try_parse_from(["fourth-shot", "--cdragon-url", "not a url"]).unwrap();
+        let err = cli.overrides.config().unwrap_err().to_string();
+        assert!(err.contains("cdragon_url"), "{err}");
+    }
+
+    #[rstest]
+    #[case("loadscreen", SkinAsset::LoadScreen)]
+    #[case("load-screen", SkinAsset::LoadScreen)]
+    #[case("tile", SkinAsset::Tile)]
+    fn parses_asset_names(#[case] name: &str, #[case] expected: SkinAsset) {
+        let cli = Cli::try_parse_from(["fourth-shot", "download", "--asset", name]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Download { asset, champion: None }) if asset == expected
+        ));
+    }
+}
@@ -0,0 +1,275 @@
+use std::{
+    fs,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::cdrag::prefetch::PrefetchPolicy;
+
+const PROJ_NAME: &str = "fourth-shot";
+const CONFIG_FILE: &str = "config.toml";
+pub const DEFAULT_CDRAGON_URL: &str = "https://raw.communitydragon.org";
+
+/// Settings read from `config.toml` in the config directory. Every field is optional in the
+/// file; environment variables and CLI flags override it (see [`crate::cli::Overrides`]).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub bind: IpAddr,
+    pub port: u16,
+    /// Serve HTTPS with this certificate instead of plain HTTP
+    pub tls: Option<Tls>,
+    /// Where the app is mounted, e.g. `/fourth-shot`, for serving it under a path of another
+    /// site. The proxy in front must pass the path on unchanged.
+    pub path_prefix: String,
+    /// Trust `Forwarded` and `X-Forwarded-*` headers for the scheme and host of absolute URLs.
+    /// Only set it when every request comes through a proxy that overwrites them.
+    pub behind_proxy: bool,
+    /// Where downloaded assets, the database and rendered images live
+    pub data_dir: PathBuf,
+    /// Where the CDragon JSON is cached between runs
+    pub cache_dir: PathBuf,
+    /// The CommunityDragon server, without the `/latest/...` part
+    pub cdragon_url: String,
+    /// What `sync` downloads ahead of time
+    pub prefetch: PrefetchPolicy,
+    /// Most bytes of downloaded assets to keep. The least recently used are deleted past it.
+    pub asset_quota: Option<u64>,
+    /// Never contact CommunityDragon: serve only what's cached or was `import`ed
+    pub offline: bool,
+    /// An [`EnvFilter`](tracing_subscriber::EnvFilter) directive
+    pub log: String,
+    pub log_format: LogFormat,
+    /// The file this was loaded from, if there was one
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        let dirs = directories::ProjectDirs::from("", "", PROJ_NAME);
+        let dir = |pick: fn(&directories::ProjectDirs) -> &Path, fallback: &str| {
+            dirs.as_ref()
+                .map(|dirs| pick(dirs).to_path_buf())
+                .unwrap_or_else(|| PathBuf::from(PROJ_NAME).join(fallback))
+        };
+        Config {
+            bind: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            port: 3000,
+            tls: None,
+            path_prefix: String::new(),
+            behind_proxy: false,
+            data_dir: dir(directories::ProjectDirs::data_dir, "data"),
+            cache_dir: dir(directories::ProjectDirs::cache_dir, "cache"),
+            cdragon_url: DEFAULT_CDRAGON_URL.into(),
+            prefetch: PrefetchPolicy::default(),
+            asset_quota: None,
+            offline: false,
+            log: format!(
+                "{}=debug,tower_http=debug,axum::rejection=trace",
+                env!("CARGO_CRATE_NAME")
+            ),
+            log_format: LogFormat::Text,
+            source: None,
+        }
+    }
+}
+
+/// PEM files, reloaded when they change so renewed certificates are picked up without a restart
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Tls {
+    /// The certificate chain, leaf first
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+/// How log lines are written to stdout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// For people
+    Text,
+    /// One JSON object per line, with the fields of every enclosing span, for log collectors
+    Json,
+}
+
+impl Config {
+    /// `config.toml` in the platform's config directory, e.g. `~/.config/fourth-shot/`
+    pub fn default_path() -> PathBuf {
+        directories::ProjectDirs::from("", "", PROJ_NAME)
+            .map(|dirs| dirs.config_dir().to_path_buf())
+            .unwrap_or_else(|| PathBuf::from(PROJ_NAME))
+            .join(CONFIG_FILE)
+    }
+
+    /// Reads the config at `path`, or at [`Config::default_path`] if there isn't one. Only an
+    /// explicitly given file has to exist; otherwise the defaults are used.
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Config> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => (Config::default_path(), false),
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound && !required => {
+                return Ok(Config::default());
+            }
+            Err(err) => {
+                return Err(err).with_context(|| format!("couldn't read the config at {path:?}"));
+            }
+        };
+        let mut config: Config =
+            toml::from_str(&text).with_context(|| format!("invalid config at {path:?}"))?;
+        config.source = Some(path);
+        Ok(config)
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        SocketAddr::new(self.bind, self.port)
+    }
+
+    /// [`Config::path_prefix`] without a trailing slash, so `""` when the app is at the root
+    pub fn prefix(&self) -> &str {
+        self.path_prefix.trim_end_matches('/')
+    }
+
+    /// Checks what TOML's types can't, listing every problem at once
+    pub fn validate(&self) -> anyhow::Result<()> {
+        let mut problems = vec![];
+        match reqwest::Url::parse(&self.cdragon_url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+            Ok(_) => problems.push(format!(
+                "cdragon_url must be an http(s) URL, not {:?}",
+                self.cdragon_url
+            )),
+            Err(err) => problems.push(format!("cdragon_url {:?}: {err}", self.cdragon_url)),
+        }
+        if let Err(err) = tracing_subscriber::EnvFilter::try_new(&self.log) {
+            problems.push(format!("log {:?}: {err}", self.log));
+        }
+        for (name, dir) in [("data_dir", &self.data_dir), ("cache_dir", &self.cache_dir)] {
+            if dir.as_os_str().is_empty() {
+                problems.push(format!("{name} can't be empty"));
+            } else if dir.is_file() {
+                problems.push(format!("{name} {dir:?} is a file"));
+            }
+        }
+        let prefix = self.prefix();
+        if !prefix.is_empty()
+            && (!prefix.starts_with('/')
+                || prefix.contains(['?', '#', '{', '}', '*'])
+                || prefix.contains(char::is_whitespace))
+        {
+            problems.push(format!(
+                "path_prefix must be a path like \"/fourth-shot\", not {:?}",
+                self.path_prefix
+            ));
+        }
+        if let Some(tls) = &self.tls {
+            for (name, file) in [("tls.cert", &tls.cert), ("tls.key", &tls.key)] {
+                if !file.is_file() {
+                    problems.push(format!("{name} {file:?} isn't a file"));
+                }
+            }
+        }
+        if problems.is_empty() {
+            return Ok(());
+        }
+        let source = self
+            .source
+            .as_ref()
+            .map_or("defaults and overrides".into(), |path| format!("{path:?}"));
+        bail!(
+            "invalid config from {source}:\n  - {}",
+            problems.join("\n  - ")
+        )
+    }
+
+    /// The config as it would be written to `config.toml`
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(self).expect("every config field is representable in TOML")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cdrag::SkinAsset;
+    use rstest::*;
+
+    #[test]
+    fn missing_fields_take_their_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        fs::write(
+            &path,
+            "port = 8080\n[prefetch]\nassets = [\"tile\", \"load_screen\"]\n",
+        )
+        .unwrap();
+        let config = Config::load(Some(&path)).unwrap();
+        assert_eq!(config.port, 8080);
+        assert_eq!(
+            config.prefetch.assets,
+            [SkinAsset::Tile, SkinAsset::LoadScreen]
+        );
+        assert!(config.prefetch.legacy);
+        assert_eq!(config.cdragon_url, DEFAULT_CDRAGON_URL);
+        assert_eq!(config.source, Some(path));
+        config.validate().unwrap();
+    }
+
+    #[rstest]
+    #[case("prot = 8080", "unknown field `prot`")]
+    #[case("port = \"eighty\"", "invalid type")]
+    fn explains_unreadable_files(#[case] text: &str, #[case] expected: &str) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        fs::write(&path, text).unwrap();
+        let err = format!("{:#}", Config::load(Some(&path)).unwrap_err());
+        assert!(err.contains(expected), "{err}");
+    }
+
+    #[test]
+    fn lists_every_problem() {
+        let config = Config {
+            cdragon_url: "ftp://example.com".into(),
+            log: "fourth_shot=loud".into(),
+            data_dir: PathBuf::new(),
+            ..Default::default()
+        };
+        let err = config.validate().unwrap_err().to_string();
+        assert!(err.contains("cdragon_url"), "{err}");
+        assert!(err.contains("log"), "{err}");
+        assert!(err.contains("data_dir"), "{err}");
+    }
+
+    #[rstest]
+    #[case("", Some(""))]
+    #[case("/fourth-shot/", Some("/fourth-shot"))]
+    #[case("/apps/fourth-shot", Some("/apps/fourth-shot"))]
+    #[case("fourth-shot", None)]
+    #[case("/{key}", None)]
+    fn checks_the_path_prefix(#[case] path_prefix: &str, #[case] expected: Option<&str>) {
+        let config = Config {
+            path_prefix: path_prefix.into(),
+            ..Default::default()
+        };
+        match expected {
+            Some(prefix) => {
+                config.validate().unwrap();
+                assert_eq!(config.prefix(), prefix);
+            }
+            None => assert!(config.validate().is_err()),
+        }
+    }
+
+    #[test]
+    fn round_trips_through_toml() {
+        let config = Config::default();
+        assert_eq!(toml::from_str::<Config>(&config.to_toml()).unwrap(), config);
+    }
+}
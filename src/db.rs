@@ -0,0 +1,106 @@
+use std::{
+    fs::create_dir_all,
+    path::Path,
+    sync::{Mutex, MutexGuard},
+};
+
+use anyhow::{Context, anyhow};
+use rusqlite::Connection;
+
+pub mod assets;
+pub mod collections;
+pub mod creations;
+pub mod focal_points;
+
+const DB_FILE: &str = "fourth-shot.db";
+
+/// Schema changes, applied in order. `PRAGMA user_version` records how many have run, so only
+/// ever append to this list.
+const MIGRATIONS: &[&str] = &[
+    "
+    CREATE TABLE collections (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+    CREATE TABLE collection_items (
+        collection_id INTEGER NOT NULL REFERENCES collections(id) ON DELETE CASCADE,
+        kind TEXT NOT NULL,
+        item_id INTEGER NOT NULL,
+        position INTEGER NOT NULL,
+        PRIMARY KEY (collection_id, kind, item_id)
+    );
+    ",
+    "
+    CREATE TABLE creations (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL,
+        source_skins TEXT NOT NULL,
+        layers TEXT NOT NULL,
+        image_file TEXT,
+        created_at TEXT NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+    ",
+    "ALTER TABLE creations ADD COLUMN composition TEXT;",
+    "
+    CREATE TABLE focal_points (
+        skin_id INTEGER PRIMARY KEY,
+        x REAL NOT NULL,
+        y REAL NOT NULL,
+        updated_at TEXT NOT NULL
+    );
+    ",
+    "ALTER TABLE creations ADD COLUMN overlays TEXT NOT NULL DEFAULT '[]';",
+    "
+    CREATE TABLE assets (
+        path TEXT PRIMARY KEY,
+        bytes INTEGER NOT NULL,
+        pinned INTEGER NOT NULL,
+        last_access TEXT NOT NULL
+    );
+    CREATE INDEX assets_by_last_access ON assets (last_access);
+    ",
+];
+
+/// The local SQLite database holding everything users create (collections, creations, etc.)
+/// and the index of downloaded assets
+#[derive(Debug)]
+pub struct Db {
+    conn: Mutex<Connection>,
+}
+
+impl Db {
+    /// Opens (or creates) the database in `data_dir` and brings its schema up to date
+    pub fn open(data_dir: &Path) -> anyhow::Result<Self> {
+        create_dir_all(data_dir)
+            .with_context(|| format!("couldn't create the data directory {data_dir:?}"))?;
+        let conn = Connection::open(data_dir.join(DB_FILE))
+            .with_context(|| format!("couldn't open {DB_FILE} in {data_dir:?}"))?;
+        Self::init(conn)
+    }
+
+    pub fn open_in_memory() -> anyhow::Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> anyhow::Result<Self> {
+        conn.pragma_update(None, "foreign_keys", true)?;
+        let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            conn.execute_batch(migration)
+                .with_context(|| format!("failed to apply migration {}", i + 1))?;
+            conn.pragma_update(None, "user_version", i + 1)?;
+        }
+        Ok(Db {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn conn(&self) -> anyhow::Result<MutexGuard<'_, Connection>> {
+        self.conn
+            .lock()
+            .map_err(|_| anyhow!("the database connection was poisoned"))
+    }
+}
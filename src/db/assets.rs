@@ -0,0 +1,143 @@
+use chrono::{DateTime, Utc};
+use rusqlite::params;
+
+use super::Db;
+
+/// How much of the asset store is in use
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AssetTotals {
+    pub files: u64,
+    pub bytes: u64,
+    pub pinned_files: u64,
+    pub pinned_bytes: u64,
+}
+
+impl Db {
+    /// Records a downloaded asset, or updates one that was downloaded again
+    pub fn record_asset(
+        &self,
+        path: &str,
+        bytes: u64,
+        pinned: bool,
+        accessed: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        self.conn()?.execute(
+            "INSERT INTO assets (path, bytes, pinned, last_access) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (path) DO UPDATE SET bytes = ?2, pinned = ?3, last_access = ?4",
+            params![path, bytes, pinned, accessed],
+        )?;
+        Ok(())
+    }
+
+    /// Records an asset found on disk unless it's already known, keeping its last access
+    pub fn record_found_asset(
+        &self,
+        path: &str,
+        bytes: u64,
+        pinned: bool,
+        accessed: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        self.conn()?.execute(
+            "INSERT INTO assets (path, bytes, pinned, last_access) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (path) DO UPDATE SET pinned = ?3",
+            params![path, bytes, pinned, accessed],
+        )?;
+        Ok(())
+    }
+
+    /// Returns whether the asset is known
+    pub fn touch_asset(&self, path: &str, accessed: DateTime<Utc>) -> anyhow::Result<bool> {
+        let changed = self.conn()?.execute(
+            "UPDATE assets SET last_access = ?2 WHERE path = ?1",
+            params![path, accessed],
+        )?;
+        Ok(changed > 0)
+    }
+
+    pub fn forget_asset(&self, path: &str) -> anyhow::Result<()> {
+        self.conn()?
+            .execute("DELETE FROM assets WHERE path = ?1", [path])?;
+        Ok(())
+    }
+
+    pub fn asset_paths(&self) -> anyhow::Result<Vec<String>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare("SELECT path FROM assets")?;
+        let paths = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        Ok(paths)
+    }
+
+    /// Unpinned assets and their sizes, least recently used first
+    pub fn evictable_assets(&self) -> anyhow::Result<Vec<(String, u64)>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT path, bytes FROM assets WHERE NOT pinned ORDER BY last_access, path",
+        )?;
+        let assets = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+        Ok(assets)
+    }
+
+    pub fn asset_totals(&self) -> anyhow::Result<AssetTotals> {
+        let totals = self.conn()?.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(bytes), 0),
+                    COUNT(*) FILTER (WHERE pinned),
+                    COALESCE(SUM(bytes) FILTER (WHERE pinned), 0)
+             FROM assets",
+            [],
+            |row| {
+                Ok(AssetTotals {
+                    files: row.get(0)?,
+                    bytes: row.get(1)?,
+                    pinned_files: row.get(2)?,
+                    pinned_bytes: row.get(3)?,
+                })
+            },
+        )?;
+        Ok(totals)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeDelta;
+
+    #[test]
+    fn orders_unpinned_assets_by_last_access() -> anyhow::Result<()> {
+        let db = Db::open_in_memory()?;
+        let start = Utc::now();
+        let at = |seconds| start + TimeDelta::seconds(seconds);
+        db.record_asset("a.jpg", 100, false, at(0))?;
+        db.record_asset("b.jpg", 200, false, at(1))?;
+        db.record_asset("icon.png", 10, true, at(-5))?;
+        db.record_found_asset("c.jpg", 300, false, at(2))?;
+        assert!(db.touch_asset("a.jpg", at(3))?);
+        assert!(!db.touch_asset("missing.jpg", at(3))?);
+        // Finding a known asset again leaves its last access alone
+        db.record_found_asset("b.jpg", 200, false, at(9))?;
+
+        let order: Vec<String> = db
+            .evictable_assets()?
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(order, ["b.jpg", "c.jpg", "a.jpg"]);
+        assert_eq!(
+            db.asset_totals()?,
+            AssetTotals {
+                files: 4,
+                bytes: 610,
+                pinned_files: 1,
+                pinned_bytes: 10,
+            }
+        );
+
+        db.forget_asset("c.jpg")?;
+        assert_eq!(db.asset_paths()?.len(), 3);
+        Ok(())
+    }
+}
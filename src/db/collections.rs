@@ -0,0 +1,264 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{OptionalExtension, params};
+use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
+
+use super::Db;
+
+/// The collection every user starts with, created the first time it's needed
+pub const DEFAULT_COLLECTION: &str = "Favorites";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Display, EnumString, Deserialize, Serialize)]
+#[strum(serialize_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum ItemKind {
+    Champion,
+    Skin,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Collection {
+    pub id: i64,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CollectionItem {
+    pub kind: ItemKind,
+    pub item_id: u64,
+    pub position: i64,
+}
+
+impl Db {
+    pub fn collections(&self) -> anyhow::Result<Vec<Collection>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, name, created_at, updated_at FROM collections ORDER BY name COLLATE NOCASE",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(Collection {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                created_at: row.get(2)?,
+                updated_at: row.get(3)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    pub fn collection(&self, id: i64) -> anyhow::Result<Option<Collection>> {
+        let conn = self.conn()?;
+        let collection = conn
+            .query_row(
+                "SELECT id, name, created_at, updated_at FROM collections WHERE id = ?1",
+                [id],
+                |row| {
+                    Ok(Collection {
+                        id: row.get(0)?,
+                        name: row.get(1)?,
+                        created_at: row.get(2)?,
+                        updated_at: row.get(3)?,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(collection)
+    }
+
+    /// Returns the [`DEFAULT_COLLECTION`], creating it if no collection has that name
+    pub fn default_collection(&self) -> anyhow::Result<Collection> {
+        let existing = self
+            .collections()?
+            .into_iter()
+            .find(|collection| collection.name == DEFAULT_COLLECTION);
+        match existing {
+            Some(collection) => Ok(collection),
+            None => self.create_collection(DEFAULT_COLLECTION),
+        }
+    }
+
+    pub fn create_collection(&self, name: &str) -> anyhow::Result<Collection> {
+        let now = Utc::now();
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO collections (name, created_at, updated_at) VALUES (?1, ?2, ?2)",
+            params![name, now],
+        )?;
+        Ok(Collection {
+            id: conn.last_insert_rowid(),
+            name: name.to_string(),
+            created_at: now,
+            updated_at: now,
+        })
+    }
+
+    /// Returns whether a collection with that id existed
+    pub fn rename_collection(&self, id: i64, name: &str) -> anyhow::Result<bool> {
+        let changed = self.conn()?.execute(
+            "UPDATE collections SET name = ?2, updated_at = ?3 WHERE id = ?1",
+            params![id, name, Utc::now()],
+        )?;
+        Ok(changed > 0)
+    }
+
+    /// Returns whether a collection with that id existed
+    pub fn delete_collection(&self, id: i64) -> anyhow::Result<bool> {
+        let changed = self
+            .conn()?
+            .execute("DELETE FROM collections WHERE id = ?1", [id])?;
+        Ok(changed > 0)
+    }
+
+    /// The items in a collection, in their user-chosen order
+    pub fn collection_items(&self, collection_id: i64) -> anyhow::Result<Vec<CollectionItem>> {
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT kind, item_id, position FROM collection_items
+             WHERE collection_id = ?1 ORDER BY position",
+        )?;
+        let rows = stmt.query_map([collection_id], |row| {
+            let kind: String = row.get(0)?;
+            Ok(CollectionItem {
+                kind: kind.parse().map_err(|err| {
+                    rusqlite::Error::FromSqlConversionFailure(
+                        0,
+                        rusqlite::types::Type::Text,
+                        Box::new(err),
+                    )
+                })?,
+                item_id: row.get(1)?,
+                position: row.get(2)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Appends an item to the end of a collection. Adding an item that's already there is a no-op.
+    pub fn add_item(&self, collection_id: i64, kind: ItemKind, item_id: u64) -> anyhow::Result<()> {
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT OR IGNORE INTO collection_items (collection_id, kind, item_id, position)
+             SELECT ?1, ?2, ?3, COALESCE(MAX(position) + 1, 0)
+             FROM collection_items WHERE collection_id = ?1",
+            params![collection_id, kind.to_string(), item_id],
+        )?;
+        touch(&conn, collection_id)?;
+        Ok(())
+    }
+
+    pub fn remove_item(
+        &self,
+        collection_id: i64,
+        kind: ItemKind,
+        item_id: u64,
+    ) -> anyhow::Result<()> {
+        let conn = self.conn()?;
+        conn.execute(
+            "DELETE FROM collection_items WHERE collection_id = ?1 AND kind = ?2 AND item_id = ?3",
+            params![collection_id, kind.to_string(), item_id],
+        )?;
+        touch(&conn, collection_id)?;
+        Ok(())
+    }
+
+    /// Adds the item if it isn't in the collection, removes it if it is. Returns whether the item
+    /// is in the collection afterwards.
+    pub fn toggle_item(
+        &self,
+        collection_id: i64,
+        kind: ItemKind,
+        item_id: u64,
+    ) -> anyhow::Result<bool> {
+        let present = self
+            .collection_items(collection_id)?
+            .iter()
+            .any(|item| item.kind == kind && item.item_id == item_id);
+        if present {
+            self.remove_item(collection_id, kind, item_id)?;
+        } else {
+            self.add_item(collection_id, kind, item_id)?;
+        }
+        Ok(!present)
+    }
+
+    pub fn clear_items(&self, collection_id: i64) -> anyhow::Result<()> {
+        let conn = self.conn()?;
+        conn.execute(
+            "DELETE FROM collection_items WHERE collection_id = ?1",
+            [collection_id],
+        )?;
+        touch(&conn, collection_id)?;
+        Ok(())
+    }
+
+    /// Moves an item to index `to` within its collection, shifting the others to make room
+    pub fn move_item(
+        &self,
+        collection_id: i64,
+        kind: ItemKind,
+        item_id: u64,
+        to: usize,
+    ) -> anyhow::Result<()> {
+        let mut items = self.collection_items(collection_id)?;
+        let Some(from) = items
+            .iter()
+            .position(|item| item.kind == kind && item.item_id == item_id)
+        else {
+            return Ok(());
+        };
+        let item = items.remove(from);
+        items.insert(to.min(items.len()), item);
+
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        for (position, item) in items.iter().enumerate() {
+            tx.execute(
+                "UPDATE collection_items SET position = ?4
+                 WHERE collection_id = ?1 AND kind = ?2 AND item_id = ?3",
+                params![collection_id, item.kind.to_string(), item.item_id, position],
+            )?;
+        }
+        touch(&tx, collection_id)?;
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+fn touch(conn: &rusqlite::Connection, collection_id: i64) -> rusqlite::Result<usize> {
+    conn.execute(
+        "UPDATE collections SET updated_at = ?2 WHERE id = ?1",
+        params![collection_id, Utc::now()],
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn add_toggle_and_reorder() -> anyhow::Result<()> {
+        let db = Db::open_in_memory()?;
+        let favorites = db.default_collection()?;
+        assert_eq!(db.default_collection()?.id, favorites.id);
+
+        db.add_item(favorites.id, ItemKind::Champion, 1)?;
+        db.add_item(favorites.id, ItemKind::Skin, 145001)?;
+        db.add_item(favorites.id, ItemKind::Champion, 145)?;
+        db.add_item(favorites.id, ItemKind::Champion, 1)?;
+        assert!(!db.toggle_item(favorites.id, ItemKind::Skin, 145001)?);
+        db.move_item(favorites.id, ItemKind::Champion, 145, 0)?;
+
+        let ids: Vec<u64> = db
+            .collection_items(favorites.id)?
+            .iter()
+            .map(|item| item.item_id)
+            .collect();
+        assert_eq!(ids, [145, 1]);
+
+        assert!(db.delete_collection(favorites.id)?);
+        assert!(db.collection_items(favorites.id)?.is_empty());
+        Ok(())
+    }
+}
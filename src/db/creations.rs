@@ -0,0 +1,317 @@
+use chrono::{DateTime, Utc};
+use rusqlite::{OptionalExtension, Row, params};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cdrag::SkinAsset,
+    imaging::{compose::Composition, overlay::Overlay},
+};
+
+use super::Db;
+
+/// One source image in a [`Creation`]
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Layer {
+    pub skin_id: u64,
+    pub asset: SkinAsset,
+    /// The part of the asset to use, defaulting to all of it
+    #[serde(default)]
+    pub crop: Option<Crop>,
+    /// Shifts the image within its cell, as fractions of the cell's width and height
+    #[serde(default)]
+    pub offset_x: f32,
+    #[serde(default)]
+    pub offset_y: f32,
+    /// Zoom on top of scaling the image to cover its cell
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+/// The zooms a layer can have
+pub const SCALES: std::ops::RangeInclusive<f32> = 0.1..=10.0;
+/// The smallest crop, as a fraction of the asset's width or height. Smaller ones would be
+/// scaled up past any useful size to cover their cell.
+pub const MIN_CROP: f32 = 0.05;
+
+impl Layer {
+    pub fn new(skin_id: u64, asset: SkinAsset) -> Self {
+        Layer {
+            skin_id,
+            asset,
+            crop: None,
+            offset_x: 0.0,
+            offset_y: 0.0,
+            scale: default_scale(),
+        }
+    }
+
+    /// Why this layer can't be rendered, if it can't
+    pub fn check(&self) -> Result<(), String> {
+        if !SCALES.contains(&self.scale) {
+            return Err(format!(
+                "layer scales must be between {} and {}",
+                SCALES.start(),
+                SCALES.end()
+            ));
+        }
+        if !(-1.0..=1.0).contains(&self.offset_x) || !(-1.0..=1.0).contains(&self.offset_y) {
+            return Err("layer offsets must be between -1 and 1".into());
+        }
+        if let Some(crop) = self.crop {
+            let within = |start: f32, size: f32| {
+                (0.0..1.0).contains(&start) && (MIN_CROP..=1.0).contains(&size)
+            };
+            if !within(crop.x, crop.width) || !within(crop.y, crop.height) {
+                return Err(format!(
+                    "crops must start within the image and be between {MIN_CROP} and 1 of it \
+                     across and down"
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A rectangle of an image, in fractions of its width and height. It may run past the
+/// image's right and bottom edges, which clip it.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct Crop {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// Something a user made out of one or more skins' art
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Creation {
+    pub id: i64,
+    pub name: String,
+    pub source_skins: Vec<u64>,
+    pub layers: Vec<Layer>,
+    /// Text, logos and badges drawn over the layers, bottom first
+    pub overlays: Vec<Overlay>,
+    /// File name of the rendered output under the creations directory, once there is one
+    pub image_file: Option<String>,
+    /// How `image_file` was rendered from the layers, if it was rendered rather than uploaded
+    pub composition: Option<Composition>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Creation {
+    /// The source skin ids as the comma separated list the creation forms take
+    pub fn source_skin_list(&self) -> String {
+        let ids: Vec<String> = self.source_skins.iter().map(u64::to_string).collect();
+        ids.join(", ")
+    }
+}
+
+/// The fields of a [`Creation`] the user controls
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CreationDraft {
+    pub name: String,
+    pub source_skins: Vec<u64>,
+    pub layers: Vec<Layer>,
+    pub overlays: Vec<Overlay>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CreationSort {
+    /// Newest first
+    #[default]
+    DateCreated,
+    Name,
+}
+
+const COLUMNS: &str =
+    "id, name, source_skins, layers, image_file, created_at, updated_at, composition, overlays";
+
+fn json_column<T: for<'a> Deserialize<'a>>(row: &Row, idx: usize) -> rusqlite::Result<T> {
+    let text: String = row.get(idx)?;
+    serde_json::from_str(&text).map_err(|err| {
+        rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, Box::new(err))
+    })
+}
+
+fn creation_from_row(row: &Row) -> rusqlite::Result<Creation> {
+    Ok(Creation {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        source_skins: json_column(row, 2)?,
+        layers: json_column(row, 3)?,
+        image_file: row.get(4)?,
+        created_at: row.get(5)?,
+        updated_at: row.get(6)?,
+        composition: row
+            .get::<_, Option<String>>(7)?
+            .map(|text| serde_json::from_str(&text))
+            .transpose()
+            .map_err(|err| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    7,
+                    rusqlite::types::Type::Text,
+                    Box::new(err),
+                )
+            })?,
+        overlays: json_column(row, 8)?,
+    })
+}
+
+impl Db {
+    /// Creations whose name contains `search_term`, ignoring case
+    pub fn creations(
+        &self,
+        search_term: &str,
+        sort: CreationSort,
+    ) -> anyhow::Result<Vec<Creation>> {
+        let order = match sort {
+            CreationSort::DateCreated => "created_at DESC",
+            CreationSort::Name => "name COLLATE NOCASE",
+        };
+        let conn = self.conn()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {COLUMNS} FROM creations WHERE instr(lower(name), lower(?1)) > 0 ORDER BY {order}"
+        ))?;
+        let rows = stmt.query_map([search_term], creation_from_row)?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    pub fn creation(&self, id: i64) -> anyhow::Result<Option<Creation>> {
+        let conn = self.conn()?;
+        let creation = conn
+            .query_row(
+                &format!("SELECT {COLUMNS} FROM creations WHERE id = ?1"),
+                [id],
+                creation_from_row,
+            )
+            .optional()?;
+        Ok(creation)
+    }
+
+    pub fn create_creation(&self, draft: &CreationDraft) -> anyhow::Result<Creation> {
+        let now = Utc::now();
+        let conn = self.conn()?;
+        conn.execute(
+            "INSERT INTO creations (name, source_skins, layers, overlays, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?5)",
+            params![
+                draft.name,
+                serde_json::to_string(&draft.source_skins)?,
+                serde_json::to_string(&draft.layers)?,
+                serde_json::to_string(&draft.overlays)?,
+                now
+            ],
+        )?;
+        Ok(Creation {
+            id: conn.last_insert_rowid(),
+            name: draft.name.clone(),
+            source_skins: draft.source_skins.clone(),
+            layers: draft.layers.clone(),
+            overlays: draft.overlays.clone(),
+            image_file: None,
+            composition: None,
+            created_at: now,
+            updated_at: now,
+        })
+    }
+
+    /// Returns whether a creation with that id existed
+    pub fn update_creation(&self, id: i64, draft: &CreationDraft) -> anyhow::Result<bool> {
+        Ok(update_draft(&*self.conn()?, id, draft)? > 0)
+    }
+
+    /// Records the creation's output image, and the composition it was rendered with if any,
+    /// along with the draft it was made from, all at once
+    pub fn set_creation_image(
+        &self,
+        id: i64,
+        draft: Option<&CreationDraft>,
+        image_file: &str,
+        composition: Option<&Composition>,
+    ) -> anyhow::Result<()> {
+        let mut conn = self.conn()?;
+        let tx = conn.transaction()?;
+        if let Some(draft) = draft {
+            update_draft(&tx, id, draft)?;
+        }
+        tx.execute(
+            "UPDATE creations SET image_file = ?2, composition = ?3, updated_at = ?4 WHERE id = ?1",
+            params![
+                id,
+                image_file,
+                composition.map(serde_json::to_string).transpose()?,
+                Utc::now()
+            ],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Returns whether a creation with that id existed
+    pub fn delete_creation(&self, id: i64) -> anyhow::Result<bool> {
+        let changed = self
+            .conn()?
+            .execute("DELETE FROM creations WHERE id = ?1", [id])?;
+        Ok(changed > 0)
+    }
+}
+
+fn update_draft(
+    conn: &rusqlite::Connection,
+    id: i64,
+    draft: &CreationDraft,
+) -> anyhow::Result<usize> {
+    Ok(conn.execute(
+        "UPDATE creations SET name = ?2, source_skins = ?3, layers = ?4, overlays = ?5,
+         updated_at = ?6 WHERE id = ?1",
+        params![
+            id,
+            draft.name,
+            serde_json::to_string(&draft.source_skins)?,
+            serde_json::to_string(&draft.layers)?,
+            serde_json::to_string(&draft.overlays)?,
+            Utc::now()
+        ],
+    )?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn search_and_sort() -> anyhow::Result<()> {
+        let db = Db::open_in_memory()?;
+        for name in ["Jhin vs. Zed", "K/DA Stage Mix", "PROJECT Dystopia"] {
+            db.create_creation(&CreationDraft {
+                name: name.into(),
+                source_skins: vec![202001],
+                layers: vec![Layer::new(202001, SkinAsset::UncenteredSplash)],
+                ..Default::default()
+            })?;
+        }
+
+        let names = |creations: Vec<Creation>| -> Vec<String> {
+            creations.into_iter().map(|c| c.name).collect()
+        };
+        assert_eq!(
+            names(db.creations("", CreationSort::Name)?),
+            ["Jhin vs. Zed", "K/DA Stage Mix", "PROJECT Dystopia"]
+        );
+        assert_eq!(
+            names(db.creations("project", CreationSort::DateCreated)?),
+            ["PROJECT Dystopia"]
+        );
+        assert_eq!(
+            db.creation(1)?.unwrap().layers[0].asset,
+            SkinAsset::UncenteredSplash
+        );
+        Ok(())
+    }
+}
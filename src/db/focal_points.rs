@@ -0,0 +1,43 @@
+use chrono::Utc;
+use rusqlite::{OptionalExtension, params};
+
+use crate::imaging::focal::FocalPoint;
+
+use super::Db;
+
+impl Db {
+    /// The focal point a user picked for a skin's splash, replacing the estimated one
+    pub fn focal_override(&self, skin_id: u64) -> anyhow::Result<Option<FocalPoint>> {
+        let focal = self
+            .conn()?
+            .query_row(
+                "SELECT x, y FROM focal_points WHERE skin_id = ?1",
+                [skin_id],
+                |row| {
+                    Ok(FocalPoint {
+                        x: row.get(0)?,
+                        y: row.get(1)?,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(focal)
+    }
+
+    pub fn set_focal_override(&self, skin_id: u64, focal: FocalPoint) -> anyhow::Result<()> {
+        self.conn()?.execute(
+            "INSERT INTO focal_points (skin_id, x, y, updated_at) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (skin_id) DO UPDATE SET x = ?2, y = ?3, updated_at = ?4",
+            params![skin_id, focal.x, focal.y, Utc::now()],
+        )?;
+        Ok(())
+    }
+
+    /// Returns whether the skin had an override
+    pub fn clear_focal_override(&self, skin_id: u64) -> anyhow::Result<bool> {
+        let changed = self
+            .conn()?
+            .execute("DELETE FROM focal_points WHERE skin_id = ?1", [skin_id])?;
+        Ok(changed > 0)
+    }
+}
@@ -0,0 +1,33 @@
+use image::{DynamicImage, imageops::FilterType};
+
+pub mod compose;
+pub mod derive;
+pub mod focal;
+pub mod overlay;
+pub mod palette;
+pub mod phash;
+pub mod poster;
+pub mod slideshow;
+pub mod wallpaper;
+
+/// Smallest and largest edge, in pixels, of anything rendered on request
+pub const MIN_EDGE: u32 = 64;
+pub const MAX_EDGE: u32 = 7680;
+
+/// Creation thumbnails match the 3:4 cards on the creations page
+pub const THUMBNAIL_WIDTH: u32 = 600;
+pub const THUMBNAIL_HEIGHT: u32 = 800;
+const JPEG_QUALITY: u8 = 85;
+
+/// Scales and center-crops an image to fill a [`THUMBNAIL_WIDTH`]x[`THUMBNAIL_HEIGHT`] JPEG
+pub fn thumbnail(image: &DynamicImage) -> anyhow::Result<Vec<u8>> {
+    let thumb = image.resize_to_fill(THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT, FilterType::Lanczos3);
+    encode_jpeg(&thumb)
+}
+
+pub fn encode_jpeg(image: &DynamicImage) -> anyhow::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut bytes, JPEG_QUALITY);
+    image.to_rgb8().write_with_encoder(encoder)?;
+    Ok(bytes)
+}
@@ -0,0 +1,534 @@
+use std::io::Cursor;
+
+use image::{DynamicImage, ImageFormat, Rgba, RgbaImage, imageops::FilterType};
+use serde::{Deserialize, Serialize};
+
+use super::{MAX_EDGE, MIN_EDGE, focal::FocalPoint, overlay::Prepared};
+use crate::db::creations::Layer;
+
+const BACKGROUND: Rgba<u8> = Rgba([10, 10, 14, 255]);
+const DIVIDER: Rgba<u8> = Rgba([240, 240, 240, 255]);
+/// How far the diagonal dividers lean, as a fraction of the output height
+const SLANT: f32 = 0.25;
+/// Width of the "vs." divider, as a fraction of the output width
+const VERSUS_GAP: f32 = 1.0 / 80.0;
+/// Most layers a creation can have. Each one may download a splash and holds it decoded
+/// while composing.
+pub const MAX_LAYERS: usize = 16;
+/// Most pixels a layer's image can be scaled to before it's cut to its cell, about 240 MB
+const MAX_FITTED_PIXELS: u64 = MAX_EDGE as u64 * MAX_EDGE as u64;
+
+/// How the layers of a creation are arranged on the canvas
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Deserialize,
+    Serialize,
+    strum::Display,
+    strum::EnumIter,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum Layout {
+    /// Side by side bands split by leaning dividers
+    #[default]
+    SplitDiagonal,
+    /// As square a grid as fits the layers, the last row stretched to fill
+    Grid,
+    /// Side by side vertical strips
+    Strip,
+    /// Exactly two layers split diagonally around a bright divider
+    Versus,
+}
+
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Deserialize,
+    Serialize,
+    strum::Display,
+    strum::EnumIter,
+)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum OutputFormat {
+    #[default]
+    Png,
+    Jpeg,
+}
+
+impl OutputFormat {
+    pub fn image_format(self) -> ImageFormat {
+        match self {
+            OutputFormat::Png => ImageFormat::Png,
+            OutputFormat::Jpeg => ImageFormat::Jpeg,
+        }
+    }
+}
+
+/// Everything needed to re-render a creation from its layers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Composition {
+    pub layout: Layout,
+    pub width: u32,
+    pub height: u32,
+    pub format: OutputFormat,
+}
+
+impl Default for Composition {
+    fn default() -> Self {
+        Composition {
+            layout: Layout::default(),
+            width: 1920,
+            height: 1080,
+            format: OutputFormat::default(),
+        }
+    }
+}
+
+impl Composition {
+    /// Why this composition can't be rendered with `layers` layers, if it can't
+    pub fn check(&self, layers: usize) -> Result<(), String> {
+        let edges = MIN_EDGE..=MAX_EDGE;
+        if !edges.contains(&self.width) || !edges.contains(&self.height) {
+            return Err(format!(
+                "the output must be between {MIN_EDGE} and {MAX_EDGE} pixels on each side"
+            ));
+        }
+        match (self.layout, layers) {
+            (_, 0) => Err("there's nothing to compose without layers".into()),
+            (_, n) if n > MAX_LAYERS => {
+                Err(format!("creations can have at most {MAX_LAYERS} layers"))
+            }
+            (Layout::Versus, n) if n != 2 => Err("a versus layout takes exactly two layers".into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Lays each image out according to its layer, then draws the overlays on top. `layers`
+    /// and `images` pair up by index.
+    pub fn compose(
+        &self,
+        layers: &[Layer],
+        images: &[LayerImage],
+        overlays: &[Prepared],
+    ) -> anyhow::Result<RgbaImage> {
+        self.check(layers.len()).map_err(anyhow::Error::msg)?;
+        anyhow::ensure!(
+            layers.len() == images.len(),
+            "got {} images for {} layers",
+            images.len(),
+            layers.len()
+        );
+        let grid = Grid::new(self.layout, layers.len(), self.width, self.height);
+        let mut canvas = RgbaImage::from_pixel(self.width, self.height, BACKGROUND);
+        for (i, (layer, image)) in layers.iter().zip(images).enumerate() {
+            let cell = grid.bounds(i);
+            let fitted = fit(image, layer, cell.width, cell.height)?;
+            for y in cell.y..cell.y + cell.height {
+                for x in cell.x..cell.x + cell.width {
+                    if grid.owner(x, y) != Some(i) {
+                        continue;
+                    }
+                    let (fx, fy) = (
+                        x as i64 - cell.x as i64 - fitted.left,
+                        y as i64 - cell.y as i64 - fitted.top,
+                    );
+                    if (0..fitted.image.width() as i64).contains(&fx)
+                        && (0..fitted.image.height() as i64).contains(&fy)
+                    {
+                        canvas.put_pixel(x, y, *fitted.image.get_pixel(fx as u32, fy as u32));
+                    }
+                }
+            }
+        }
+        if self.layout == Layout::Versus {
+            for (x, y, pixel) in canvas.enumerate_pixels_mut() {
+                if grid.owner(x, y).is_none() {
+                    *pixel = DIVIDER;
+                }
+            }
+        }
+        for overlay in overlays {
+            overlay.draw(&mut canvas)?;
+        }
+        Ok(canvas)
+    }
+
+    /// Composes the layers and encodes the result in the composition's format
+    pub fn render(
+        &self,
+        layers: &[Layer],
+        images: &[LayerImage],
+        overlays: &[Prepared],
+    ) -> anyhow::Result<Vec<u8>> {
+        let canvas = DynamicImage::ImageRgba8(self.compose(layers, images, overlays)?);
+        match self.format {
+            OutputFormat::Png => {
+                let mut bytes = Vec::new();
+                canvas.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)?;
+                Ok(bytes)
+            }
+            OutputFormat::Jpeg => super::encode_jpeg(&canvas),
+        }
+    }
+}
+
+/// A layer's source image, with the point that should stay in view when it's cropped to its cell
+pub struct LayerImage {
+    pub image: DynamicImage,
+    pub focal: FocalPoint,
+}
+
+impl From<DynamicImage> for LayerImage {
+    fn from(image: DynamicImage) -> Self {
+        LayerImage {
+            image,
+            focal: FocalPoint::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Rect {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+/// Which layer owns each pixel of the canvas, and the box around each layer's pixels
+struct Grid {
+    layout: Layout,
+    layers: u32,
+    width: u32,
+    height: u32,
+    /// Columns in every row but the last, for [`Layout::Grid`]
+    columns: u32,
+    rows: u32,
+}
+
+impl Grid {
+    fn new(layout: Layout, layers: usize, width: u32, height: u32) -> Self {
+        let layers = layers as u32;
+        let (columns, rows) = match layout {
+            Layout::Grid => {
+                let columns = (layers as f64).sqrt().ceil() as u32;
+                (columns, layers.div_ceil(columns))
+            }
+            _ => (layers, 1),
+        };
+        Grid {
+            layout,
+            layers,
+            width,
+            height,
+            columns,
+            rows,
+        }
+    }
+
+    fn columns_in_row(&self, row: u32) -> u32 {
+        if row + 1 == self.rows {
+            self.layers - self.columns * row
+        } else {
+            self.columns
+        }
+    }
+
+    /// Horizontal distance from the vertical center line of the diagonal divider at `y`
+    fn lean(&self, y: u32) -> f32 {
+        let slant = SLANT * self.height as f32;
+        slant * (0.5 - y as f32 / self.height as f32)
+    }
+
+    fn owner(&self, x: u32, y: u32) -> Option<usize> {
+        match self.layout {
+            Layout::Strip => Some((x * self.layers / self.width) as usize),
+            Layout::Grid => {
+                let row = y * self.rows / self.height;
+                let column = x * self.columns_in_row(row) / self.width;
+                Some((row * self.columns + column) as usize)
+            }
+            Layout::SplitDiagonal | Layout::Versus => {
+                let t = x as f32 - self.lean(y);
+                let band_width = self.width as f32 / self.layers as f32;
+                if self.layout == Layout::Versus {
+                    let gap = VERSUS_GAP * self.width as f32 / 2.0;
+                    if (t - band_width).abs() < gap {
+                        return None;
+                    }
+                }
+                let band = (t / band_width)
+                    .floor()
+                    .clamp(0.0, (self.layers - 1) as f32);
+                Some(band as usize)
+            }
+        }
+    }
+
+    fn bounds(&self, i: usize) -> Rect {
+        let i = i as u32;
+        // Pixel `p` lands in slot `floor(p * n / len)`, so slot `k` starts at `ceil(k * len / n)`
+        let span = |k: u32, n: u32, len: u32| {
+            let start = (k * len).div_ceil(n);
+            (start, ((k + 1) * len).div_ceil(n) - start)
+        };
+        match self.layout {
+            Layout::Strip => {
+                let (x, width) = span(i, self.layers, self.width);
+                Rect {
+                    x,
+                    y: 0,
+                    width,
+                    height: self.height,
+                }
+            }
+            Layout::Grid => {
+                let (row, column) = (i / self.columns, i % self.columns);
+                let (x, width) = span(column, self.columns_in_row(row), self.width);
+                let (y, height) = span(row, self.rows, self.height);
+                Rect {
+                    x,
+                    y,
+                    width,
+                    height,
+                }
+            }
+            Layout::SplitDiagonal | Layout::Versus => {
+                let reach = self.lean(0).abs().ceil() as u32;
+                let (x, width) = span(i, self.layers, self.width);
+                let start = x.saturating_sub(reach);
+                let end = (x + width + reach).min(self.width);
+                Rect {
+                    x: start,
+                    y: 0,
+                    width: end - start,
+                    height: self.height,
+                }
+            }
+        }
+    }
+}
+
+/// A layer's image scaled to cover its cell, and where its top left corner sits in the cell
+struct Fitted {
+    image: RgbaImage,
+    left: i64,
+    top: i64,
+}
+
+/// Scales the image to cover the cell. Uncropped images are shifted toward their focal point as
+/// far as they can be without uncovering the cell; cropped ones are centered, since the crop
+/// already picked what's shown.
+fn fit(source: &LayerImage, layer: &Layer, width: u32, height: u32) -> anyhow::Result<Fitted> {
+    layer.check().map_err(anyhow::Error::msg)?;
+    let image = &source.image;
+    let cropped = match layer.crop {
+        Some(crop) => {
+            let (w, h) = (image.width() as f32, image.height() as f32);
+            let x = ((crop.x.clamp(0.0, 1.0) * w) as u32).min(image.width() - 1);
+            let y = ((crop.y.clamp(0.0, 1.0) * h) as u32).min(image.height() - 1);
+            let cw = ((crop.width * w) as u32).clamp(1, image.width() - x);
+            let ch = ((crop.height * h) as u32).clamp(1, image.height() - y);
+            image.crop_imm(x, y, cw, ch)
+        }
+        None => image.clone(),
+    };
+    let cover = f32::max(
+        width as f32 / cropped.width() as f32,
+        height as f32 / cropped.height() as f32,
+    );
+    let scale = cover * layer.scale;
+    let scaled_width = ((cropped.width() as f32 * scale).round() as u32).max(1);
+    let scaled_height = ((cropped.height() as f32 * scale).round() as u32).max(1);
+    anyhow::ensure!(
+        scaled_width as u64 * scaled_height as u64 <= MAX_FITTED_PIXELS,
+        "a layer zooms in too far: its image would be {scaled_width}×{scaled_height} pixels"
+    );
+    let image = cropped
+        .resize_exact(scaled_width, scaled_height, FilterType::CatmullRom)
+        .to_rgba8();
+    let focal = match layer.crop {
+        Some(_) => FocalPoint::default(),
+        None => source.focal,
+    };
+    // Puts the focal point in the middle of the cell, within the overhang on either side
+    let place = |cell: u32, scaled: u32, focal: f32| {
+        let (cell, scaled) = (cell as f32, scaled as f32);
+        let centered = (cell - scaled) / 2.0;
+        let start = if scaled > cell {
+            (cell / 2.0 - focal.clamp(0.0, 1.0) * scaled).clamp(cell - scaled, 0.0)
+        } else {
+            centered
+        };
+        start as i64
+    };
+    let left = place(width, scaled_width, focal.x) + (layer.offset_x * width as f32) as i64;
+    let top = place(height, scaled_height, focal.y) + (layer.offset_y * height as f32) as i64;
+    Ok(Fitted { image, left, top })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cdrag::SkinAsset;
+    use rstest::*;
+
+    const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+    const BLUE: Rgba<u8> = Rgba([0, 0, 255, 255]);
+    const GREEN: Rgba<u8> = Rgba([0, 255, 0, 255]);
+
+    fn solid(color: Rgba<u8>) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_pixel(40, 30, color))
+    }
+
+    fn compose(layout: Layout, colors: &[Rgba<u8>]) -> RgbaImage {
+        let layers: Vec<Layer> = (0..colors.len())
+            .map(|i| Layer::new(i as u64, SkinAsset::UncenteredSplash))
+            .collect();
+        let images: Vec<LayerImage> = colors.iter().copied().map(|c| solid(c).into()).collect();
+        Composition {
+            layout,
+            width: 400,
+            height: 200,
+            ..Default::default()
+        }
+        .compose(&layers, &images, &[])
+        .unwrap()
+    }
+
+    #[rstest]
+    #[case::diagonal(Layout::SplitDiagonal, &[RED, BLUE], [RED, BLUE, RED, BLUE])]
+    #[case::strip(Layout::Strip, &[RED, BLUE, GREEN], [RED, GREEN, RED, GREEN])]
+    #[case::grid(Layout::Grid, &[RED, BLUE, GREEN], [RED, BLUE, GREEN, GREEN])]
+    #[case::versus(Layout::Versus, &[RED, BLUE], [RED, BLUE, RED, BLUE])]
+    fn corners(
+        #[case] layout: Layout,
+        #[case] colors: &[Rgba<u8>],
+        #[case] expected: [Rgba<u8>; 4],
+    ) {
+        let canvas = compose(layout, colors);
+        let corners = [(0, 0), (399, 0), (0, 199), (399, 199)];
+        assert_eq!(corners.map(|(x, y)| *canvas.get_pixel(x, y)), expected);
+    }
+
+    #[test]
+    fn diagonal_leans() {
+        let canvas = compose(Layout::SplitDiagonal, &[RED, BLUE]);
+        // Just right of center at the top still belongs to the left band, but not at the bottom
+        assert_eq!(*canvas.get_pixel(210, 0), RED);
+        assert_eq!(*canvas.get_pixel(210, 199), BLUE);
+        let versus = compose(Layout::Versus, &[RED, BLUE]);
+        assert_eq!(*versus.get_pixel(200, 100), DIVIDER);
+    }
+
+    #[test]
+    fn offsets_reveal_background() {
+        let mut layer = Layer::new(0, SkinAsset::Splash);
+        layer.offset_x = 0.5;
+        let canvas = Composition {
+            layout: Layout::Strip,
+            width: 100,
+            height: 100,
+            ..Default::default()
+        }
+        .compose(&[layer], &[solid(RED).into()], &[])
+        .unwrap();
+        assert_eq!(*canvas.get_pixel(10, 50), BACKGROUND);
+        assert_eq!(*canvas.get_pixel(90, 50), RED);
+    }
+
+    #[rstest]
+    #[case(0.0, RED)]
+    #[case(0.5, GREEN)]
+    #[case(1.0, BLUE)]
+    fn focal_points_stay_in_view(#[case] focal_x: f32, #[case] expected: Rgba<u8>) {
+        // Three vertical bands, of which a square cell only has room for one
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_fn(90, 30, |x, _| match x / 30 {
+            0 => RED,
+            1 => GREEN,
+            _ => BLUE,
+        }));
+        let canvas = Composition {
+            layout: Layout::Strip,
+            width: 100,
+            height: 100,
+            ..Default::default()
+        }
+        .compose(
+            &[Layer::new(0, SkinAsset::Splash)],
+            &[LayerImage {
+                image,
+                focal: FocalPoint { x: focal_x, y: 0.5 },
+            }],
+            &[],
+        )
+        .unwrap();
+        assert_eq!(*canvas.get_pixel(50, 50), expected);
+    }
+
+    #[test]
+    fn versus_needs_two_layers() {
+        let composition = Composition {
+            layout: Layout::Versus,
+            ..Default::default()
+        };
+        assert!(composition.check(3).is_err());
+        assert!(composition.check(2).is_ok());
+    }
+
+    #[rstest]
+    #[case::zoomed_out(0.1, None, true)]
+    #[case::too_far(1e9, None, false)]
+    #[case::infinite(f32::INFINITY, None, false)]
+    #[case::nan(f32::NAN, None, false)]
+    #[case::cropped(1.0, Some((0.5, 0.0, 0.5, 1.0)), true)]
+    #[case::sliver(1.0, Some((0.0, 0.0, 0.001, 1.0)), false)]
+    #[case::outside(1.0, Some((1.0, 0.0, 0.5, 0.5)), false)]
+    #[case::nan_crop(1.0, Some((0.0, f32::NAN, 0.5, 0.5)), false)]
+    fn layers_are_checked(
+        #[case] scale: f32,
+        #[case] crop: Option<(f32, f32, f32, f32)>,
+        #[case] valid: bool,
+    ) {
+        let mut layer = Layer::new(0, SkinAsset::Splash);
+        layer.scale = scale;
+        layer.crop = crop.map(|(x, y, width, height)| crate::db::creations::Crop {
+            x,
+            y,
+            width,
+            height,
+        });
+        assert_eq!(layer.check().is_ok(), valid, "{layer:?}");
+    }
+
+    #[test]
+    fn refuses_to_zoom_past_the_pixel_budget() {
+        // A thin crop has to be scaled up thousands of times to cover a large canvas
+        let mut layer = Layer::new(0, SkinAsset::Splash);
+        layer.crop = Some(crate::db::creations::Crop {
+            x: 0.0,
+            y: 0.0,
+            width: 0.05,
+            height: 1.0,
+        });
+        let err = Composition {
+            layout: Layout::Strip,
+            width: MAX_EDGE,
+            height: MAX_EDGE / 2,
+            ..Default::default()
+        }
+        .compose(&[layer], &[solid(RED).into()], &[])
+        .unwrap_err();
+        assert!(err.to_string().contains("zooms in too far"), "{err}");
+    }
+}
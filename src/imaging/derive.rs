@@ -0,0 +1,113 @@
+use std::io::Cursor;
+
+use image::{
+    DynamicImage,
+    codecs::{avif::AvifEncoder, webp::WebPEncoder},
+    imageops::FilterType,
+};
+use serde::Deserialize;
+
+/// The widths derivatives are rendered at. Anything in between would only fill the cache with
+/// near-duplicates, so browsers pick from these through `srcset`.
+pub const WIDTHS: [u32; 7] = [160, 320, 480, 640, 960, 1280, 1920];
+/// rav1e's speed, 1-10. Derivatives are made while a page waits on them, so favor speed.
+const AVIF_SPEED: u8 = 8;
+const AVIF_QUALITY: u8 = 70;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, strum::Display)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum Format {
+    Jpeg,
+    /// Lossless, since that's the only WebP the image crate writes. Still a fraction of the
+    /// original splash once resized.
+    Webp,
+    Avif,
+}
+
+impl Format {
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            Format::Jpeg => "image/jpeg",
+            Format::Webp => "image/webp",
+            Format::Avif => "image/avif",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Jpeg => "jpg",
+            Format::Webp => "webp",
+            Format::Avif => "avif",
+        }
+    }
+
+    pub fn encode(self, image: &DynamicImage) -> anyhow::Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        match self {
+            Format::Jpeg => return super::encode_jpeg(image),
+            Format::Webp => image
+                .to_rgba8()
+                .write_with_encoder(WebPEncoder::new_lossless(Cursor::new(&mut bytes)))?,
+            Format::Avif => {
+                image
+                    .to_rgba8()
+                    .write_with_encoder(AvifEncoder::new_with_speed_quality(
+                        &mut bytes,
+                        AVIF_SPEED,
+                        AVIF_QUALITY,
+                    ))?
+            }
+        }
+        Ok(bytes)
+    }
+}
+
+/// Scales the image down to `width`, keeping its aspect ratio. Images already narrower than
+/// that are left alone rather than blown up.
+pub fn resize(image: &DynamicImage, width: u32) -> DynamicImage {
+    if image.width() <= width {
+        return image.clone();
+    }
+    let height = (image.height() as u64 * width as u64 / image.width() as u64).max(1) as u32;
+    image.resize_exact(width, height, FilterType::Lanczos3)
+}
+
+/// Where the derivative of the asset at `path` (relative to the data directory) is served
+pub fn url(path: &str, width: u32, format: Format) -> String {
+    crate::app::mount::path(&format!("/derived/{width}/{format}/{path}"))
+}
+
+/// A `srcset` listing every width of the asset in `format`
+pub fn srcset(path: &str, format: Format) -> String {
+    WIDTHS
+        .iter()
+        .map(|width| format!("{} {width}w", url(path, *width, format)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use image::RgbImage;
+    use rstest::*;
+
+    #[rstest]
+    #[case(Format::Jpeg, image::ImageFormat::Jpeg)]
+    #[case(Format::Webp, image::ImageFormat::WebP)]
+    #[case(Format::Avif, image::ImageFormat::Avif)]
+    fn encodes(#[case] format: Format, #[case] expected: image::ImageFormat) {
+        let image = resize(&DynamicImage::ImageRgb8(RgbImage::new(400, 300)), 160);
+        assert_eq!((image.width(), image.height()), (160, 120));
+        let bytes = format.encode(&image).unwrap();
+        assert_eq!(image::guess_format(&bytes).unwrap(), expected);
+    }
+
+    #[test]
+    fn srcset_lists_every_width() {
+        let srcset = srcset("assets/a.jpg", Format::Webp);
+        assert!(srcset.starts_with("/derived/160/webp/assets/a.jpg 160w, "));
+        assert_eq!(srcset.matches("w,").count(), WIDTHS.len() - 1);
+    }
+}
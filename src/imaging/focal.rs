@@ -0,0 +1,146 @@
+use image::{DynamicImage, imageops::FilterType};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Images are shrunk to this width before looking for their subject
+const SAMPLE_WIDTH: u32 = 192;
+/// The saliency map is averaged over cells this many sample pixels wide, which blurs edges
+/// into the regions they outline
+const CELL: u32 = 4;
+/// Only cells at least this salient (as a fraction of the most salient one) pull the focal point
+const THRESHOLD: f32 = 0.6;
+/// How strongly the center of the frame is favored. Splash subjects are rarely at the very edge.
+const CENTER_BIAS: f32 = 0.4;
+
+/// A point of interest in an image, in fractions of its width and height
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct FocalPoint {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Default for FocalPoint {
+    fn default() -> Self {
+        FocalPoint { x: 0.5, y: 0.5 }
+    }
+}
+
+impl FocalPoint {
+    /// Whether both coordinates are within the image
+    pub fn is_valid(self) -> bool {
+        (0.0..=1.0).contains(&self.x) && (0.0..=1.0).contains(&self.y)
+    }
+}
+
+/// Guesses where the subject of an image is from how busy (edges) and how unusual (distance
+/// from the average color) each region is, favoring the center of the frame.
+pub fn estimate(image: &DynamicImage) -> FocalPoint {
+    if image.width() < 3 || image.height() < 3 {
+        return FocalPoint::default();
+    }
+    let height = (image.height() * SAMPLE_WIDTH / image.width()).max(CELL);
+    let sample = image
+        .resize_exact(SAMPLE_WIDTH, height, FilterType::Triangle)
+        .to_rgb8();
+    let (width, height) = sample.dimensions();
+
+    let pixels: Vec<[f32; 3]> = sample.pixels().map(|p| p.0.map(f32::from)).collect();
+    let mean = pixels.iter().fold([0.0; 3], |mut sum, p| {
+        for i in 0..3 {
+            sum[i] += p[i] / pixels.len() as f32;
+        }
+        sum
+    });
+    let luma = |x: u32, y: u32| {
+        let [r, g, b] = pixels[(y * width + x) as usize];
+        0.299 * r + 0.587 * g + 0.114 * b
+    };
+
+    let (columns, rows) = (width / CELL, height / CELL);
+    let mut edges = vec![0.0f32; (columns * rows) as usize];
+    let mut contrast = vec![0.0f32; (columns * rows) as usize];
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let (column, row) = (x / CELL, y / CELL);
+            if column >= columns || row >= rows {
+                continue;
+            }
+            let cell = (row * columns + column) as usize;
+            edges[cell] +=
+                (luma(x + 1, y) - luma(x - 1, y)).abs() + (luma(x, y + 1) - luma(x, y - 1)).abs();
+            let p = pixels[(y * width + x) as usize];
+            contrast[cell] += (0..3).map(|i| (p[i] - mean[i]).powi(2)).sum::<f32>().sqrt();
+        }
+    }
+    normalize(&mut edges);
+    normalize(&mut contrast);
+
+    let saliency: Vec<f32> = (0..rows)
+        .flat_map(|row| (0..columns).map(move |column| (column, row)))
+        .map(|(column, row)| {
+            let cell = (row * columns + column) as usize;
+            let dx = (column as f32 + 0.5) / columns as f32 - 0.5;
+            let dy = (row as f32 + 0.5) / rows as f32 - 0.5;
+            let center = (-(dx * dx + dy * dy) / (2.0 * 0.3 * 0.3)).exp();
+            (edges[cell] + contrast[cell]) / 2.0 * (1.0 - CENTER_BIAS + CENTER_BIAS * center)
+        })
+        .collect();
+    let peak = saliency.iter().copied().fold(0.0, f32::max);
+    if peak <= 0.0 {
+        return FocalPoint::default();
+    }
+
+    let (mut x, mut y, mut total) = (0.0, 0.0, 0.0);
+    for (cell, weight) in saliency.iter().enumerate() {
+        if *weight < peak * THRESHOLD {
+            continue;
+        }
+        let (column, row) = (cell as u32 % columns, cell as u32 / columns);
+        x += (column as f32 + 0.5) / columns as f32 * weight;
+        y += (row as f32 + 0.5) / rows as f32 * weight;
+        total += weight;
+    }
+    FocalPoint {
+        x: x / total,
+        y: y / total,
+    }
+}
+
+/// Scales the values so the largest is 1
+fn normalize(values: &mut [f32]) {
+    let max = values.iter().copied().fold(0.0, f32::max);
+    if max > 0.0 {
+        values.iter_mut().for_each(|v| *v /= max);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn finds_the_busy_corner() {
+        // A flat dark background with a checkered, brightly colored patch up and to the right
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(400, 200, |x, y| {
+            if (250..330).contains(&x) && (20..90).contains(&y) {
+                if (x / 6 + y / 6) % 2 == 0 {
+                    Rgb([250, 200, 40])
+                } else {
+                    Rgb([40, 90, 250])
+                }
+            } else {
+                Rgb([20, 24, 30])
+            }
+        }));
+        let focal = estimate(&image);
+        assert!((0.6..0.85).contains(&focal.x), "{focal:?}");
+        assert!((0.1..0.45).contains(&focal.y), "{focal:?}");
+    }
+
+    #[test]
+    fn flat_images_stay_centered() {
+        let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(300, 100, Rgb([90, 90, 90])));
+        assert_eq!(estimate(&image), FocalPoint::default());
+    }
+}
@@ -0,0 +1,697 @@
+use std::sync::LazyLock;
+
+use ab_glyph::{Font, FontRef, PxScale, ScaleFont, point};
+use image::{DynamicImage, GrayImage, Luma, RgbaImage, imageops::FilterType};
+use serde::{Deserialize, Serialize};
+
+use super::palette::Color;
+use crate::cdrag::Rarity;
+
+static HYPERSYSTEM: LazyLock<FontRef<'static>> = LazyLock::new(|| {
+    FontRef::try_from_slice(include_bytes!("../../assets/fonts/Hypersystem.ttf"))
+        .expect("the bundled font is a valid TrueType font")
+});
+
+/// Directions the text mask is shifted in to thicken it into a stroke
+const STROKE_DIRECTIONS: usize = 16;
+/// Badge labels are this tall, as a fraction of the badge
+const BADGE_TEXT: f32 = 0.6;
+/// Tallest badges can be, as a fraction of the canvas height
+const MAX_BADGE_SIZE: f32 = 0.25;
+/// Most overlays a creation can have
+pub const MAX_OVERLAYS: usize = 32;
+/// Longest text an overlay can have, in characters
+pub const MAX_TEXT_CHARS: usize = 500;
+/// Thickest stroke, and furthest a shadow can be offset, as fractions of the font size
+const MAX_STROKE: f32 = 0.25;
+const MAX_SHADOW_OFFSET: f32 = 0.5;
+/// Most blur a shadow can have, as a fraction of the font size. Blurring costs time with the
+/// square of it.
+const MAX_SHADOW_BLUR: f32 = 0.1;
+/// Most pixels a text's coverage mask, with its stroke and shadow, can take
+const MAX_MASK_PIXELS: u64 = 32 * 1024 * 1024;
+
+/// The fonts bundled under `assets/fonts`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FontFamily {
+    #[default]
+    Hypersystem,
+}
+
+impl FontFamily {
+    fn font(self) -> &'static FontRef<'static> {
+        match self {
+            FontFamily::Hypersystem => &HYPERSYSTEM,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Align {
+    Left,
+    #[default]
+    Center,
+    Right,
+}
+
+/// An outline around text. `width` is a fraction of the font size.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct Stroke {
+    pub color: Color,
+    pub width: f32,
+}
+
+/// A blurred copy of text drawn behind it. Offsets and blur are fractions of the font size.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct Shadow {
+    pub color: Color,
+    #[serde(default)]
+    pub offset_x: f32,
+    #[serde(default)]
+    pub offset_y: f32,
+    #[serde(default)]
+    pub blur: f32,
+    #[serde(default = "default_opacity")]
+    pub opacity: f32,
+}
+
+/// A title or caption. `x` is where the text's left edge, center or right edge goes depending
+/// on `align`, and `y` where its top goes, both as fractions of the canvas. `size` is the
+/// height of a line as a fraction of the canvas height.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TextOverlay {
+    pub text: String,
+    #[serde(default)]
+    pub font: FontFamily,
+    #[serde(default = "default_text_size")]
+    pub size: f32,
+    #[serde(default = "default_text_color")]
+    pub color: Color,
+    #[serde(default)]
+    pub stroke: Option<Stroke>,
+    #[serde(default)]
+    pub shadow: Option<Shadow>,
+    #[serde(default)]
+    pub align: Align,
+    #[serde(default = "default_center")]
+    pub x: f32,
+    #[serde(default)]
+    pub y: f32,
+}
+
+/// A logo or other CommunityDragon asset, centered on `x` and `y` and scaled to `width`, all
+/// fractions of the canvas
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ImageOverlay {
+    /// Normalized asset path, e.g. a champion's `square_portrait_path`
+    pub path: String,
+    #[serde(default = "default_center")]
+    pub x: f32,
+    #[serde(default = "default_center")]
+    pub y: f32,
+    pub width: f32,
+    #[serde(default = "default_opacity")]
+    pub opacity: f32,
+}
+
+/// A skin's rarity (and legacy) badges like on its champion's page, in a row starting at `x`
+/// and `y`. `size` is the badges' height as a fraction of the canvas height.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct BadgeOverlay {
+    pub skin_id: u64,
+    #[serde(default)]
+    pub x: f32,
+    #[serde(default)]
+    pub y: f32,
+    #[serde(default = "default_badge_size")]
+    pub size: f32,
+}
+
+/// Something drawn over a creation's layers, in order
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Overlay {
+    Text(TextOverlay),
+    Image(ImageOverlay),
+    RarityBadge(BadgeOverlay),
+}
+
+fn default_opacity() -> f32 {
+    1.0
+}
+
+fn default_text_size() -> f32 {
+    0.1
+}
+
+fn default_text_color() -> Color {
+    Color([255, 255, 255])
+}
+
+fn default_center() -> f32 {
+    0.5
+}
+
+fn default_badge_size() -> f32 {
+    0.05
+}
+
+impl Overlay {
+    /// Why the overlay can't be drawn, if it can't
+    pub fn check(&self) -> Result<(), String> {
+        let fraction = |name: &str, value: f32| {
+            if value > 0.0 && value <= 1.0 {
+                Ok(())
+            } else {
+                Err(format!("an overlay's {name} must be above 0 and at most 1"))
+            }
+        };
+        let at_most = |name: &str, value: f32, max: f32| {
+            if (-max..=max).contains(&value) {
+                Ok(())
+            } else {
+                Err(format!(
+                    "an overlay's {name} must be between -{max} and {max}"
+                ))
+            }
+        };
+        let opacity = |value: f32| {
+            if (0.0..=1.0).contains(&value) {
+                Ok(())
+            } else {
+                Err("an overlay's opacity must be between 0 and 1".to_string())
+            }
+        };
+        let (x, y) = match self {
+            Overlay::Text(text) => (text.x, text.y),
+            Overlay::Image(image) => (image.x, image.y),
+            Overlay::RarityBadge(badge) => (badge.x, badge.y),
+        };
+        // Anything further out is off the canvas anyway
+        at_most("x", x, 2.0)?;
+        at_most("y", y, 2.0)?;
+        match self {
+            Overlay::Text(text) if text.text.trim().is_empty() => {
+                Err("text overlays need some text".into())
+            }
+            Overlay::Text(text) if text.text.chars().count() > MAX_TEXT_CHARS => Err(format!(
+                "text overlays can be at most {MAX_TEXT_CHARS} characters"
+            )),
+            Overlay::Text(text) => {
+                fraction("size", text.size)?;
+                if let Some(stroke) = text.stroke
+                    && !(0.0..=MAX_STROKE).contains(&stroke.width)
+                {
+                    return Err(format!("strokes must be between 0 and {MAX_STROKE} wide"));
+                }
+                if let Some(shadow) = text.shadow {
+                    at_most("shadow offset_x", shadow.offset_x, MAX_SHADOW_OFFSET)?;
+                    at_most("shadow offset_y", shadow.offset_y, MAX_SHADOW_OFFSET)?;
+                    if !(0.0..=MAX_SHADOW_BLUR).contains(&shadow.blur) {
+                        return Err(format!(
+                            "shadow blur must be between 0 and {MAX_SHADOW_BLUR}"
+                        ));
+                    }
+                    opacity(shadow.opacity)?;
+                }
+                Ok(())
+            }
+            Overlay::Image(image) => {
+                fraction("width", image.width)?;
+                opacity(image.opacity)
+            }
+            Overlay::RarityBadge(badge) if badge.size > MAX_BADGE_SIZE => Err(format!(
+                "badges can be at most {MAX_BADGE_SIZE} of the canvas tall"
+            )),
+            Overlay::RarityBadge(badge) => fraction("size", badge.size),
+        }
+    }
+}
+
+/// One pill of a [`BadgeOverlay`], filled with a gradient from `fill[0]` on the left to
+/// `fill[1]` on the right
+#[derive(Debug, Clone, PartialEq)]
+pub struct Badge {
+    pub label: String,
+    pub fill: [Color; 2],
+    pub border: Color,
+}
+
+impl Badge {
+    fn solid(label: &str, color: Color) -> Self {
+        Badge {
+            label: label.into(),
+            fill: [color; 2],
+            border: color,
+        }
+    }
+}
+
+/// The badges `champion_detail.html` shows for a skin, in the same Tailwind colors
+pub fn badges(rarity: &Rarity, is_legacy: bool) -> Vec<Badge> {
+    const BLUE_500: Color = Color([0x3b, 0x82, 0xf6]);
+    const GREEN_500: Color = Color([0x22, 0xc5, 0x5e]);
+    let rarity = match rarity {
+        Rarity::KEpic => Some(Badge::solid("Epic", BLUE_500)),
+        Rarity::KLegendary => Some(Badge::solid("Legendary", Color([0xef, 0x44, 0x44]))),
+        Rarity::KMythic => Some(Badge::solid("Mythic", Color([0xa8, 0x55, 0xf7]))),
+        Rarity::KExalted => Some(Badge {
+            label: "Exalted".into(),
+            fill: [BLUE_500, GREEN_500],
+            border: BLUE_500,
+        }),
+        Rarity::KUltimate => Some(Badge::solid("Ultimate", Color([0xea, 0xb3, 0x08]))),
+        Rarity::KRare => Some(Badge::solid("Rare", GREEN_500)),
+        Rarity::KTranscendent => Some(Badge {
+            label: "Transcendent".into(),
+            fill: [Color([0xbf, 0xdb, 0xfe]), Color([0x60, 0xa5, 0xfa])],
+            border: Color([0x60, 0xa5, 0xfa]),
+        }),
+        Rarity::KNoRarity => None,
+    };
+    let legacy = is_legacy.then(|| Badge::solid("Legacy", Color([0x9c, 0xa3, 0xaf])));
+    rarity.into_iter().chain(legacy).collect()
+}
+
+/// An overlay with what it needs from outside the creation loaded, ready to draw
+pub enum Prepared {
+    Text(TextOverlay),
+    Image(ImageOverlay, DynamicImage),
+    Badges(BadgeOverlay, Vec<Badge>),
+}
+
+impl Prepared {
+    pub fn draw(&self, canvas: &mut RgbaImage) -> anyhow::Result<()> {
+        let (width, height) = (canvas.width() as f32, canvas.height() as f32);
+        match self {
+            Prepared::Text(text) => draw_text(canvas, text)?,
+            Prepared::Image(overlay, image) => {
+                let target_width = ((overlay.width * width).round() as u32).max(1);
+                let target_height = ((image.height() as f32 * target_width as f32
+                    / image.width().max(1) as f32)
+                    .round() as u32)
+                    .max(1);
+                let scaled = image
+                    .resize_exact(target_width, target_height, FilterType::CatmullRom)
+                    .to_rgba8();
+                let left = (overlay.x * width - target_width as f32 / 2.0).round() as i64;
+                let top = (overlay.y * height - target_height as f32 / 2.0).round() as i64;
+                for (x, y, pixel) in scaled.enumerate_pixels() {
+                    let alpha = pixel[3] as f32 / 255.0 * overlay.opacity.clamp(0.0, 1.0);
+                    blend(canvas, left + x as i64, top + y as i64, pixel.0, alpha);
+                }
+            }
+            Prepared::Badges(overlay, badges) => {
+                let badge_height = (overlay.size * height).round().max(4.0);
+                let (mut left, top) = (overlay.x * width, overlay.y * height);
+                for badge in badges {
+                    left += draw_badge(canvas, badge, left, top, badge_height) + badge_height / 3.0;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Rasterizes the text into a coverage mask, with `padding` spare pixels on every side, unless
+/// the mask would take more than [`MAX_MASK_PIXELS`]
+fn text_mask(text: &TextOverlay, px: f32, padding: u32) -> anyhow::Result<GrayImage> {
+    let font = text.font.font().as_scaled(PxScale::from(px));
+    let lines: Vec<&str> = text.text.lines().collect();
+    let line_width = |line: &str| {
+        let mut previous = None;
+        line.chars()
+            .map(|c| {
+                let id = font.glyph_id(c);
+                let kern = previous.map_or(0.0, |p| font.kern(p, id));
+                previous = Some(id);
+                kern + font.h_advance(id)
+            })
+            .sum::<f32>()
+    };
+    let widths: Vec<f32> = lines.iter().map(|line| line_width(line)).collect();
+    let block_width = widths.iter().copied().fold(0.0, f32::max);
+    let line_height = font.height() + font.line_gap();
+    let mask_width = block_width.ceil() as u64 + 2 * padding as u64;
+    let mask_height = (line_height * lines.len() as f32).ceil() as u64 + 2 * padding as u64;
+    anyhow::ensure!(
+        mask_width * mask_height <= MAX_MASK_PIXELS,
+        "the text {:?} is too big to draw at this size",
+        text.text
+    );
+    let mut mask = GrayImage::new(mask_width as u32, mask_height as u32);
+
+    for (i, (line, line_width)) in lines.iter().zip(&widths).enumerate() {
+        let mut x = padding as f32
+            + match text.align {
+                Align::Left => 0.0,
+                Align::Center => (block_width - line_width) / 2.0,
+                Align::Right => block_width - line_width,
+            };
+        let baseline = padding as f32 + i as f32 * line_height + font.ascent();
+        let mut previous = None;
+        for c in line.chars() {
+            let id = font.glyph_id(c);
+            if let Some(p) = previous {
+                x += font.kern(p, id);
+            }
+            previous = Some(id);
+            let glyph = id.with_scale_and_position(px, point(x, baseline));
+            x += font.h_advance(id);
+            let Some(outline) = font.outline_glyph(glyph) else {
+                continue;
+            };
+            let bounds = outline.px_bounds();
+            outline.draw(|gx, gy, coverage| {
+                let (mx, my) = (
+                    bounds.min.x as i64 + gx as i64,
+                    bounds.min.y as i64 + gy as i64,
+                );
+                if let Some(pixel) = mask.get_pixel_mut_checked(mx as u32, my as u32) {
+                    pixel[0] = pixel[0].max((coverage * 255.0) as u8);
+                }
+            });
+        }
+    }
+    Ok(mask)
+}
+
+/// The mask grown by `radius` pixels in every direction
+fn spread(mask: &GrayImage, radius: f32) -> GrayImage {
+    let mut grown = mask.clone();
+    for i in 0..STROKE_DIRECTIONS {
+        let angle = i as f32 / STROKE_DIRECTIONS as f32 * std::f32::consts::TAU;
+        let (dx, dy) = (
+            (angle.cos() * radius).round() as i64,
+            (angle.sin() * radius).round() as i64,
+        );
+        for (x, y, pixel) in mask.enumerate_pixels() {
+            let (sx, sy) = (x as i64 + dx, y as i64 + dy);
+            if sx < 0 || sy < 0 {
+                continue;
+            }
+            if let Some(target) = grown.get_pixel_mut_checked(sx as u32, sy as u32) {
+                target[0] = target[0].max(pixel[0]);
+            }
+        }
+    }
+    grown
+}
+
+fn draw_text(canvas: &mut RgbaImage, text: &TextOverlay) -> anyhow::Result<()> {
+    let (width, height) = (canvas.width() as f32, canvas.height() as f32);
+    let px = text.size * height;
+    let stroke_radius = text.stroke.map_or(0.0, |stroke| stroke.width * px);
+    let shadow_reach = text.shadow.map_or(0.0, |shadow| {
+        shadow.blur * px * 3.0 + shadow.offset_x.abs().max(shadow.offset_y.abs()) * px
+    });
+    let padding = (stroke_radius + shadow_reach).ceil() as u32 + 1;
+    let mask = text_mask(text, px, padding)?;
+    let outline = match text.stroke {
+        Some(_) if stroke_radius >= 0.5 => Some(spread(&mask, stroke_radius)),
+        _ => None,
+    };
+
+    let block_width = (mask.width() - 2 * padding) as f32;
+    let anchor = match text.align {
+        Align::Left => 0.0,
+        Align::Center => block_width / 2.0,
+        Align::Right => block_width,
+    };
+    let left = (text.x * width - anchor).round() as i64 - padding as i64;
+    let top = (text.y * height).round() as i64 - padding as i64;
+
+    if let Some(shadow) = text.shadow {
+        let silhouette = outline.as_ref().unwrap_or(&mask);
+        let blurred = match shadow.blur * px {
+            sigma if sigma > 0.0 => imageproc::filter::gaussian_blur_f32(silhouette, sigma),
+            _ => silhouette.clone(),
+        };
+        let (dx, dy) = (
+            (shadow.offset_x * px).round() as i64,
+            (shadow.offset_y * px).round() as i64,
+        );
+        paint(
+            canvas,
+            &blurred,
+            left + dx,
+            top + dy,
+            shadow.color,
+            shadow.opacity,
+        );
+    }
+    if let (Some(stroke), Some(outline)) = (text.stroke, &outline) {
+        paint(canvas, outline, left, top, stroke.color, 1.0);
+    }
+    paint(canvas, &mask, left, top, text.color, 1.0);
+    Ok(())
+}
+
+/// Left-aligned text in the default font, for labels placed in pixels rather than fractions
+fn plain(text: &str, color: Color) -> TextOverlay {
+    TextOverlay {
+        text: text.into(),
+        font: FontFamily::default(),
+        size: 1.0,
+        color,
+        stroke: None,
+        shadow: None,
+        align: Align::Left,
+        x: 0.0,
+        y: 0.0,
+    }
+}
+
+/// How wide [`draw_label`] would draw the text
+pub(super) fn label_width(text: &str, px: f32) -> u32 {
+    text_mask(&plain(text, Color([0, 0, 0])), px, 0).map_or(0, |mask| mask.width())
+}
+
+/// Draws a line of text `px` tall with its top left corner at `left` and `top`
+pub(super) fn draw_label(
+    canvas: &mut RgbaImage,
+    text: &str,
+    px: f32,
+    left: i64,
+    top: i64,
+    color: Color,
+) {
+    if let Ok(mask) = text_mask(&plain(text, color), px, 0) {
+        paint(canvas, &mask, left, top, color, 1.0);
+    }
+}
+
+/// Draws a badge with its top left corner at `left` and `top`, returning how wide it was
+pub(super) fn draw_badge(
+    canvas: &mut RgbaImage,
+    badge: &Badge,
+    left: f32,
+    top: f32,
+    height: f32,
+) -> f32 {
+    let text = text_mask(
+        &plain(&badge.label, Color([255, 255, 255])),
+        height * BADGE_TEXT,
+        0,
+    )
+    .unwrap_or_default();
+    let width = text.width() as f32 + height;
+    let radius = height / 2.0;
+    let border = (height / 12.0).max(1.0);
+
+    let (x0, y0) = (left.round() as i64, top.round() as i64);
+    for y in 0..height.ceil() as i64 {
+        for x in 0..width.ceil() as i64 {
+            let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+            // Distance outside the pill shape, negative inside
+            let cx = px.clamp(radius, width - radius);
+            let outside = ((px - cx).powi(2) + (py - radius).powi(2)).sqrt() - radius;
+            if outside > 0.5 {
+                continue;
+            }
+            let coverage = (0.5 - outside).clamp(0.0, 1.0);
+            let color = if outside > -border {
+                badge.border
+            } else {
+                let t = px / width;
+                let [from, to] = badge.fill.map(|c| c.0.map(f32::from));
+                Color([0, 1, 2].map(|i| (from[i] + (to[i] - from[i]) * t).round() as u8))
+            };
+            let alpha = if outside > -border { 1.0 } else { 0.7 };
+            let [r, g, b] = color.0;
+            blend(canvas, x0 + x, y0 + y, [r, g, b, 255], coverage * alpha);
+        }
+    }
+    let text_left = x0 + (height / 2.0).round() as i64;
+    let text_top = y0 + ((height - text.height() as f32) / 2.0).round() as i64;
+    paint(
+        canvas,
+        &text,
+        text_left,
+        text_top,
+        Color([255, 255, 255]),
+        1.0,
+    );
+    width
+}
+
+/// Fills `color` through the mask onto the canvas, with the mask's top left at `left`, `top`
+fn paint(
+    canvas: &mut RgbaImage,
+    mask: &GrayImage,
+    left: i64,
+    top: i64,
+    color: Color,
+    opacity: f32,
+) {
+    let [r, g, b] = color.0;
+    for (x, y, Luma([coverage])) in mask.enumerate_pixels() {
+        if *coverage > 0 {
+            let alpha = *coverage as f32 / 255.0 * opacity.clamp(0.0, 1.0);
+            blend(
+                canvas,
+                left + x as i64,
+                top + y as i64,
+                [r, g, b, 255],
+                alpha,
+            );
+        }
+    }
+}
+
+/// Mixes `color` into the canvas pixel at `x`, `y` by `alpha`, if the pixel is on the canvas
+fn blend(canvas: &mut RgbaImage, x: i64, y: i64, color: [u8; 4], alpha: f32) {
+    if x < 0 || y < 0 || alpha <= 0.0 {
+        return;
+    }
+    let Some(pixel) = canvas.get_pixel_mut_checked(x as u32, y as u32) else {
+        return;
+    };
+    for i in 0..3 {
+        pixel[i] = (pixel[i] as f32 * (1.0 - alpha) + color[i] as f32 * alpha).round() as u8;
+    }
+    pixel[3] = pixel[3].max((alpha * 255.0) as u8);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use image::Rgba;
+
+    const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+
+    fn text(json: &str) -> TextOverlay {
+        match serde_json::from_str(json).unwrap() {
+            Overlay::Text(text) => text,
+            other => panic!("{other:?} isn't text"),
+        }
+    }
+
+    /// The bounding box of pixels that aren't black anymore
+    fn painted(canvas: &RgbaImage) -> Option<(u32, u32, u32, u32)> {
+        canvas
+            .enumerate_pixels()
+            .filter(|(_, _, pixel)| **pixel != BLACK)
+            .fold(None, |bounds, (x, y, _)| {
+                let (x0, y0, x1, y1) = bounds.unwrap_or((x, y, x, y));
+                Some((x0.min(x), y0.min(y), x1.max(x), y1.max(y)))
+            })
+    }
+
+    #[test]
+    fn text_lands_where_aligned() {
+        let draw = |json: &str| {
+            let mut canvas = RgbaImage::from_pixel(400, 200, BLACK);
+            Prepared::Text(text(json)).draw(&mut canvas).unwrap();
+            painted(&canvas).expect("something was drawn")
+        };
+        let (left, top, _, bottom) =
+            draw(r#"{"kind": "text", "text": "ARCADE", "align": "left", "x": 0.1, "y": 0.5}"#);
+        assert!((38..=50).contains(&left), "{left}");
+        assert!(top >= 100 && bottom < 130, "{top}..{bottom}");
+        let (_, _, right, _) =
+            draw(r#"{"kind": "text", "text": "ARCADE", "align": "right", "x": 0.9}"#);
+        assert!((340..=362).contains(&right), "{right}");
+    }
+
+    #[test]
+    fn strokes_and_shadows_widen_text() {
+        let plain = r#"{"kind": "text", "text": "K/DA", "y": 0.3}"#;
+        let styled = r##"{"kind": "text", "text": "K/DA", "y": 0.3, "color": "#ffd700",
+            "stroke": {"color": "#000080", "width": 0.08},
+            "shadow": {"color": "#ff00ff", "offset_x": 0.1, "offset_y": 0.1, "blur": 0.05}}"##;
+        let [plain, styled] = [plain, styled].map(|json| {
+            let mut canvas = RgbaImage::from_pixel(400, 200, BLACK);
+            Prepared::Text(text(json)).draw(&mut canvas).unwrap();
+            let (x0, y0, x1, y1) = painted(&canvas).unwrap();
+            (x1 - x0, y1 - y0)
+        });
+        assert!(
+            styled.0 > plain.0 && styled.1 > plain.1,
+            "{plain:?} {styled:?}"
+        );
+    }
+
+    #[test]
+    fn legacy_epic_skins_get_two_badges() {
+        let badges = badges(&Rarity::KEpic, true);
+        let labels: Vec<&str> = badges.iter().map(|badge| badge.label.as_str()).collect();
+        assert_eq!(labels, ["Epic", "Legacy"]);
+        assert!(super::badges(&Rarity::KNoRarity, false).is_empty());
+
+        let overlay = BadgeOverlay {
+            skin_id: 1,
+            x: 0.05,
+            y: 0.05,
+            size: 0.1,
+        };
+        let mut canvas = RgbaImage::from_pixel(400, 200, BLACK);
+        Prepared::Badges(overlay, badges).draw(&mut canvas).unwrap();
+        // Inside the first badge's border, on its left end
+        let pixel = canvas.get_pixel(21, 20);
+        assert!(pixel[2] > pixel[0], "{pixel:?} should be Epic blue");
+    }
+
+    #[test]
+    fn checks_sizes() {
+        let overlay: Overlay =
+            serde_json::from_str(r#"{"kind": "image", "path": "a.png", "width": 1.5}"#).unwrap();
+        assert!(overlay.check().is_err());
+        let overlay: Overlay = serde_json::from_str(r#"{"kind": "text", "text": " "}"#).unwrap();
+        assert!(overlay.check().is_err());
+        let overlay: Overlay =
+            serde_json::from_str(r#"{"kind": "rarity_badge", "skin_id": 1}"#).unwrap();
+        assert!(overlay.check().is_ok());
+    }
+
+    #[rstest::rstest]
+    #[case(r##"{"kind": "text", "text": "K/DA", "stroke": {"color": "#000000", "width": 1e6}}"##)]
+    #[case(r##"{"kind": "text", "text": "K/DA", "shadow": {"color": "#000000", "blur": 5}}"##)]
+    #[case(r##"{"kind": "text", "text": "K/DA", "shadow": {"color": "#000000", "offset_x": -9}}"##)]
+    #[case(r#"{"kind": "text", "text": "K/DA", "x": 1e30}"#)]
+    #[case(r#"{"kind": "image", "path": "a.png", "width": 0.5, "opacity": 2}"#)]
+    #[case(r#"{"kind": "rarity_badge", "skin_id": 1, "y": -1e9}"#)]
+    fn bounds_styles_and_positions(#[case] json: &str) {
+        let overlay: Overlay = serde_json::from_str(json).unwrap();
+        assert!(overlay.check().is_err(), "{json}");
+    }
+
+    #[test]
+    fn long_text_is_refused() {
+        let long = Overlay::Text(TextOverlay {
+            text: "A".repeat(MAX_TEXT_CHARS + 1),
+            ..text(r#"{"kind": "text", "text": "A"}"#)
+        });
+        assert!(long.check().is_err());
+
+        // Within the length limit, but far too wide for a mask at a full-height size
+        let wide = text(&format!(
+            r#"{{"kind": "text", "text": "{}", "size": 1.0}}"#,
+            "W".repeat(MAX_TEXT_CHARS)
+        ));
+        let mut canvas = RgbaImage::new(400, 2000);
+        assert!(Prepared::Text(wide).draw(&mut canvas).is_err());
+    }
+}
@@ -0,0 +1,183 @@
+use std::{fmt::Display, str::FromStr};
+
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// How many colors are kept per skin
+pub const PALETTE_SIZE: usize = 5;
+/// Splashes are shrunk to fit this square before sampling. Palettes don't need every pixel.
+const SAMPLE_EDGE: u32 = 128;
+/// Boxes whose averages are closer than this are one color split by an uneven median
+const MERGE_DISTANCE: f32 = 24.0;
+
+/// An sRGB color, written as `#rrggbb`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize, ToSchema)]
+#[serde(into = "String", try_from = "String")]
+#[schema(value_type = String, example = "#1e90ff")]
+pub struct Color(pub [u8; 3]);
+
+impl Color {
+    /// The "redmean" approximation of perceptual distance: cheap, and much closer to how
+    /// different two colors look than plain RGB distance. Ranges from 0 to about 765.
+    pub fn distance(self, other: Color) -> f32 {
+        let [r1, g1, b1] = self.0.map(f32::from);
+        let [r2, g2, b2] = other.0.map(f32::from);
+        let mean_r = (r1 + r2) / 2.0;
+        let (dr, dg, db) = (r1 - r2, g1 - g2, b1 - b2);
+        ((2.0 + mean_r / 256.0) * dr * dr
+            + 4.0 * dg * dg
+            + (2.0 + (255.0 - mean_r) / 256.0) * db * db)
+            .sqrt()
+    }
+}
+
+impl Display for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let [r, g, b] = self.0;
+        write!(f, "#{r:02x}{g:02x}{b:02x}")
+    }
+}
+
+impl FromStr for Color {
+    type Err = String;
+
+    /// Parses `#rrggbb`, with or without the `#`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        let channel = |i: usize| {
+            hex.get(i..i + 2)
+                .and_then(|c| u8::from_str_radix(c, 16).ok())
+                .ok_or_else(|| format!("{s} isn't a #rrggbb color"))
+        };
+        if hex.len() != 6 {
+            return Err(format!("{s} isn't a #rrggbb color"));
+        }
+        Ok(Color([channel(0)?, channel(2)?, channel(4)?]))
+    }
+}
+
+impl From<Color> for String {
+    fn from(color: Color) -> Self {
+        color.to_string()
+    }
+}
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// The image's [`PALETTE_SIZE`] dominant colors, most common first
+pub fn palette(image: &DynamicImage) -> Vec<Color> {
+    let sample = image.thumbnail(SAMPLE_EDGE, SAMPLE_EDGE).to_rgb8();
+    median_cut(sample.pixels().map(|p| p.0).collect(), PALETTE_SIZE)
+}
+
+/// Splits the colors into up to `n` boxes, each time halving the box that spans the widest
+/// range of one channel (weighted by how many colors it holds) at that channel's median.
+/// Returns each box's average color, the fullest boxes first, with near-duplicates merged.
+pub fn median_cut(colors: Vec<[u8; 3]>, n: usize) -> Vec<Color> {
+    if colors.is_empty() || n == 0 {
+        return vec![];
+    }
+    let mut boxes = vec![colors];
+    while boxes.len() < n {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| {
+                let (channel, range) = widest_channel(b);
+                (i, channel, range as usize * b.len())
+            })
+            .filter(|(_, _, score)| *score > 0)
+            .max_by_key(|(_, _, score)| *score);
+        let Some((i, channel, _)) = widest else {
+            break;
+        };
+        let mut colors = boxes.swap_remove(i);
+        colors.sort_unstable_by_key(|c| c[channel]);
+        let upper = colors.split_off(colors.len() / 2);
+        boxes.push(colors);
+        boxes.push(upper);
+    }
+    let mut merged: Vec<(Color, usize)> = vec![];
+    for b in &boxes {
+        let color = average(b);
+        match merged
+            .iter_mut()
+            .find(|(other, _)| other.distance(color) < MERGE_DISTANCE)
+        {
+            Some((other, count)) => {
+                let total = (*count + b.len()) as f32;
+                let blend = |o: u8, c: u8| {
+                    ((o as f32 * *count as f32 + c as f32 * b.len() as f32) / total).round() as u8
+                };
+                *other = Color([0, 1, 2].map(|i| blend(other.0[i], color.0[i])));
+                *count += b.len();
+            }
+            None => merged.push((color, b.len())),
+        }
+    }
+    merged.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    merged.into_iter().map(|(color, _)| color).collect()
+}
+
+fn widest_channel(colors: &[[u8; 3]]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let (min, max) = colors.iter().fold((u8::MAX, u8::MIN), |(min, max), c| {
+                (min.min(c[channel]), max.max(c[channel]))
+            });
+            (channel, max - min)
+        })
+        .max_by_key(|(_, range)| *range)
+        .expect("there are three channels")
+}
+
+fn average(colors: &[[u8; 3]]) -> Color {
+    let sums = colors.iter().fold([0u64; 3], |mut sums, c| {
+        for (sum, v) in sums.iter_mut().zip(c) {
+            *sum += *v as u64;
+        }
+        sums
+    });
+    Color(sums.map(|sum| (sum / colors.len() as u64) as u8))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn dominant_colors_come_first() {
+        // Three quarters red, a quarter blue
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(64, 64, |x, _| {
+            if x < 48 {
+                Rgb([220, 20, 20])
+            } else {
+                Rgb([20, 20, 220])
+            }
+        }));
+        let colors = palette(&image);
+        assert_eq!(colors.len(), 2);
+        // Sampling blurs the seam between the halves a little
+        assert!(colors[0].distance(Color([220, 20, 20])) < 5.0);
+        assert!(colors[1].distance(Color([20, 20, 220])) < 5.0);
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        let color: Color = "#1E90ff".parse().unwrap();
+        assert_eq!(color, Color([0x1e, 0x90, 0xff]));
+        assert_eq!(color.to_string(), "#1e90ff");
+        assert_eq!("1e90ff".parse::<Color>(), Ok(color));
+        assert!("#1e90f".parse::<Color>().is_err());
+        assert!(color.distance(Color([0x1e, 0x90, 0xfe])) < color.distance(Color([0, 0, 0])));
+    }
+}
@@ -0,0 +1,156 @@
+use std::{f32::consts::PI, fmt::Display, str::FromStr};
+
+use image::{DynamicImage, imageops::FilterType};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Images are shrunk to a square this wide before their frequencies are taken
+const SAMPLE_EDGE: usize = 32;
+/// Only the lowest frequencies, this many in each direction, make it into the hash. They carry
+/// the overall layout of the image, which survives resizing, re-encoding and light cropping.
+const HASH_EDGE: usize = 8;
+/// Hashes at most this many bits apart are treated as the same art by default. Splashes cropped
+/// for different champions of a group splash land well within it; unrelated splashes rarely
+/// come closer than 20.
+pub const NEAR_DUPLICATE_DISTANCE: u32 = 10;
+
+/// A 64 bit DCT perceptual hash, written as 16 hex digits. Similar images have hashes that
+/// differ in few bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize, ToSchema)]
+#[serde(into = "String", try_from = "String")]
+#[schema(value_type = String, example = "c3d1a0f0e8b49c36")]
+pub struct PerceptualHash(pub u64);
+
+impl PerceptualHash {
+    /// How many bits differ between the hashes, from 0 (the same art) to 64
+    pub fn distance(self, other: PerceptualHash) -> u32 {
+        (self.0 ^ other.0).count_ones()
+    }
+}
+
+impl Display for PerceptualHash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl FromStr for PerceptualHash {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 16 {
+            return Err(format!("{s} isn't a 16 digit hash"));
+        }
+        u64::from_str_radix(s, 16)
+            .map(PerceptualHash)
+            .map_err(|_| format!("{s} isn't a 16 digit hash"))
+    }
+}
+
+impl From<PerceptualHash> for String {
+    fn from(hash: PerceptualHash) -> Self {
+        hash.to_string()
+    }
+}
+
+impl TryFrom<String> for PerceptualHash {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Hashes the image by which of its lowest frequencies (past the average brightness) are
+/// stronger than their median
+pub fn hash(image: &DynamicImage) -> PerceptualHash {
+    let sample = image
+        .resize_exact(SAMPLE_EDGE as u32, SAMPLE_EDGE as u32, FilterType::Triangle)
+        .to_luma32f();
+    let pixels: Vec<f32> = sample.pixels().map(|p| p.0[0]).collect();
+
+    let cosines: Vec<f32> = (0..HASH_EDGE * SAMPLE_EDGE)
+        .map(|i| {
+            let (k, n) = (i / SAMPLE_EDGE, i % SAMPLE_EDGE);
+            (PI / SAMPLE_EDGE as f32 * (n as f32 + 0.5) * k as f32).cos()
+        })
+        .collect();
+    let cosine = |k: usize, n: usize| cosines[k * SAMPLE_EDGE + n];
+    // The 2D DCT is separable: transform the rows, then the columns of the result. Only the
+    // frequencies that end up in the hash are computed.
+    let mut rows = vec![0.0f32; SAMPLE_EDGE * HASH_EDGE];
+    for y in 0..SAMPLE_EDGE {
+        for u in 0..HASH_EDGE {
+            rows[y * HASH_EDGE + u] = (0..SAMPLE_EDGE)
+                .map(|x| pixels[y * SAMPLE_EDGE + x] * cosine(u, x))
+                .sum();
+        }
+    }
+    let mut frequencies = [0.0f32; HASH_EDGE * HASH_EDGE];
+    for v in 0..HASH_EDGE {
+        for u in 0..HASH_EDGE {
+            frequencies[v * HASH_EDGE + u] = (0..SAMPLE_EDGE)
+                .map(|y| rows[y * HASH_EDGE + u] * cosine(v, y))
+                .sum();
+        }
+    }
+
+    // The first frequency is the average brightness, which would swamp the median
+    let mut sorted = frequencies[1..].to_vec();
+    sorted.sort_by(f32::total_cmp);
+    let median = sorted[sorted.len() / 2];
+    let bits = frequencies
+        .iter()
+        .enumerate()
+        .filter(|(_, f)| **f > median)
+        .fold(0u64, |bits, (i, _)| bits | (1 << i));
+    PerceptualHash(bits)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    /// Soft diagonal bands with a bright disc, busy enough to have a layout worth hashing
+    fn scene(width: u32, height: u32, shift: f32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            let (u, v) = (x as f32 / width as f32, y as f32 / height as f32);
+            let band = (((u + v + shift) * 9.0).sin() * 100.0 + 120.0) as u8;
+            let disc = (u - 0.7).powi(2) + (v - 0.35).powi(2) < 0.02;
+            if disc {
+                Rgb([250, 230, 180])
+            } else {
+                Rgb([band, band / 2, 255 - band])
+            }
+        }))
+    }
+
+    #[test]
+    fn resized_and_cropped_copies_stay_close() {
+        let original = scene(800, 450, 0.0);
+        let hash = hash(&original);
+        let resized = super::hash(&original.resize_exact(400, 225, FilterType::Lanczos3));
+        let cropped = super::hash(&original.crop_imm(16, 9, 768, 432));
+        assert!(hash.distance(resized) <= 2, "{hash} {resized}");
+        assert!(
+            hash.distance(cropped) <= NEAR_DUPLICATE_DISTANCE,
+            "{hash} {cropped}"
+        );
+    }
+
+    #[test]
+    fn different_art_is_far_apart() {
+        let a = hash(&scene(800, 450, 0.0));
+        let b = hash(&scene(800, 450, 1.7).fliph());
+        assert!(a.distance(b) > NEAR_DUPLICATE_DISTANCE * 2, "{a} {b}");
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        let hash = PerceptualHash(0x00c3_d1a0_f0e8_b49c);
+        assert_eq!(hash.to_string(), "00c3d1a0f0e8b49c");
+        assert_eq!("00c3d1a0f0e8b49c".parse(), Ok(hash));
+        assert!("c3d1a0f0e8b49c".parse::<PerceptualHash>().is_err());
+    }
+}
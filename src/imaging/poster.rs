@@ -0,0 +1,379 @@
+use std::{fmt::Write as _, io::Cursor};
+
+use image::{DynamicImage, ImageFormat, Rgba, RgbaImage};
+use serde::Deserialize;
+
+use super::{
+    MAX_EDGE,
+    focal::FocalPoint,
+    overlay::{self, Badge},
+    palette::Color,
+    wallpaper::crop_to_fill,
+};
+use crate::cdrag::SkinAsset;
+
+/// The page and card colors of `champion_detail.html` (Tailwind's gray-900 and gray-800)
+const BACKGROUND: Rgba<u8> = Rgba([0x11, 0x18, 0x27, 255]);
+const CARD: Rgba<u8> = Rgba([0x1f, 0x29, 0x37, 255]);
+const TEXT: Color = Color([255, 255, 255]);
+const SUBTITLE: Color = Color([0xd1, 0xd5, 0xdb]);
+/// Printers expect a resolution; the poster is laid out in pixels at this many per inch
+const PDF_DPI: f32 = 150.0;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PosterFormat {
+    #[default]
+    Png,
+    Jpeg,
+    /// A single page PDF holding the poster as a JPEG, sized for printing at 150 DPI
+    Pdf,
+}
+
+impl PosterFormat {
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            PosterFormat::Png => "image/png",
+            PosterFormat::Jpeg => "image/jpeg",
+            PosterFormat::Pdf => "application/pdf",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            PosterFormat::Png => "png",
+            PosterFormat::Jpeg => "jpg",
+            PosterFormat::Pdf => "pdf",
+        }
+    }
+}
+
+/// A grid of a champion's skins under a title, each labeled with its name and badges
+#[derive(Debug, Clone, PartialEq)]
+pub struct Poster {
+    pub title: String,
+    pub subtitle: String,
+    pub asset: SkinAsset,
+    /// Defaults to roughly square grids
+    pub columns: Option<u32>,
+    /// Width of each skin's card in pixels
+    pub cell_width: u32,
+    pub format: PosterFormat,
+}
+
+/// One skin's card on a [`Poster`]
+pub struct PosterCell {
+    pub image: DynamicImage,
+    pub focal: FocalPoint,
+    pub name: String,
+    pub badges: Vec<Badge>,
+}
+
+/// Pixel measurements of a poster, worked out from its cell width
+struct Grid {
+    columns: u32,
+    rows: u32,
+    cell_width: u32,
+    image_height: u32,
+    label_height: u32,
+    gap: u32,
+    header_height: u32,
+}
+
+impl Grid {
+    fn size(&self) -> (u32, u32) {
+        (
+            self.columns * self.cell_width + (self.columns + 1) * self.gap,
+            self.header_height + self.rows * (self.image_height + self.label_height + self.gap),
+        )
+    }
+
+    /// Top left corner of the cell at `i`
+    fn origin(&self, i: u32) -> (u32, u32) {
+        let (column, row) = (i % self.columns, i / self.columns);
+        (
+            self.gap + column * (self.cell_width + self.gap),
+            self.header_height + row * (self.image_height + self.label_height + self.gap),
+        )
+    }
+}
+
+/// How tall each asset is for its width
+fn aspect_ratio(asset: SkinAsset) -> f32 {
+    match asset {
+        SkinAsset::Splash | SkinAsset::UncenteredSplash => 9.0 / 16.0,
+        SkinAsset::Tile => 1.0,
+        SkinAsset::LoadScreen => 560.0 / 308.0,
+    }
+}
+
+impl Poster {
+    fn grid(&self, cells: usize) -> Grid {
+        let cells = cells.max(1) as u32;
+        let columns = self
+            .columns
+            .unwrap_or_else(|| (cells as f32).sqrt().ceil() as u32)
+            .clamp(1, cells);
+        let gap = self.cell_width / 16;
+        Grid {
+            columns,
+            rows: cells.div_ceil(columns),
+            cell_width: self.cell_width,
+            image_height: (self.cell_width as f32 * aspect_ratio(self.asset)).round() as u32,
+            label_height: self.cell_width / 4,
+            gap,
+            header_height: self.cell_width / 2 + gap,
+        }
+    }
+
+    /// Why a poster of `cells` skins can't be made, if it can't
+    pub fn check(&self, cells: usize) -> Result<(), String> {
+        if cells == 0 {
+            return Err("there are no skins to put on the poster".into());
+        }
+        if !(super::MIN_EDGE..=1920).contains(&self.cell_width) {
+            return Err(format!(
+                "cells must be between {} and 1920 pixels wide",
+                super::MIN_EDGE
+            ));
+        }
+        let (width, height) = self.grid(cells).size();
+        if width > MAX_EDGE || height > MAX_EDGE {
+            return Err(format!(
+                "a {width}x{height} poster is larger than {MAX_EDGE} pixels on a side; try \
+                 narrower cells or another number of columns"
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn compose(&self, cells: &[PosterCell]) -> anyhow::Result<RgbaImage> {
+        self.check(cells.len()).map_err(anyhow::Error::msg)?;
+        let grid = self.grid(cells.len());
+        let (width, height) = grid.size();
+        let mut canvas = RgbaImage::from_pixel(width, height, BACKGROUND);
+
+        let title_px = grid.header_height as f32 * 0.45;
+        let title_width = overlay::label_width(&self.title, title_px);
+        let title_top = (grid.gap / 2) as i64;
+        overlay::draw_label(
+            &mut canvas,
+            &self.title,
+            title_px,
+            grid.gap as i64,
+            title_top,
+            TEXT,
+        );
+        overlay::draw_label(
+            &mut canvas,
+            &self.subtitle,
+            title_px * 0.5,
+            (grid.gap * 2 + title_width) as i64,
+            title_top + (title_px * 0.4) as i64,
+            SUBTITLE,
+        );
+
+        for (i, cell) in cells.iter().enumerate() {
+            let (x, y) = grid.origin(i as u32);
+            let image = crop_to_fill(&cell.image, grid.cell_width, grid.image_height, cell.focal);
+            image::imageops::overlay(&mut canvas, &image.to_rgba8(), x as i64, y as i64);
+            let label_top = y + grid.image_height;
+            for py in label_top..label_top + grid.label_height {
+                for px in x..x + grid.cell_width {
+                    canvas.put_pixel(px, py, CARD);
+                }
+            }
+
+            let padding = grid.label_height as f32 / 8.0;
+            let room = grid.cell_width as f32 - 2.0 * padding;
+            // Long names shrink to fit rather than spill into the next card
+            let name_px = grid.label_height as f32 * 0.32;
+            let name_px = name_px
+                .min(name_px * room / overlay::label_width(&cell.name, name_px).max(1) as f32);
+            overlay::draw_label(
+                &mut canvas,
+                &cell.name,
+                name_px,
+                (x as f32 + padding) as i64,
+                (label_top as f32 + padding) as i64,
+                TEXT,
+            );
+            let badge_height = grid.label_height as f32 * 0.3;
+            let mut left = x as f32 + padding;
+            for badge in &cell.badges {
+                left += overlay::draw_badge(
+                    &mut canvas,
+                    badge,
+                    left,
+                    label_top as f32 + grid.label_height as f32 - padding - badge_height,
+                    badge_height,
+                ) + badge_height / 3.0;
+            }
+        }
+        Ok(canvas)
+    }
+
+    /// Composes the poster and encodes it in the poster's format
+    pub fn render(&self, cells: &[PosterCell]) -> anyhow::Result<Vec<u8>> {
+        let canvas = DynamicImage::ImageRgba8(self.compose(cells)?);
+        match self.format {
+            PosterFormat::Png => {
+                let mut bytes = Vec::new();
+                canvas.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)?;
+                Ok(bytes)
+            }
+            PosterFormat::Jpeg => super::encode_jpeg(&canvas),
+            PosterFormat::Pdf => Ok(jpeg_pdf(
+                &super::encode_jpeg(&canvas)?,
+                canvas.width(),
+                canvas.height(),
+            )),
+        }
+    }
+}
+
+/// A one page PDF showing the JPEG edge to edge, with the page sized so the image prints at
+/// [`PDF_DPI`]. PDF readers decode JPEGs natively, so the bytes are embedded as they are.
+fn jpeg_pdf(jpeg: &[u8], width: u32, height: u32) -> Vec<u8> {
+    let points = |pixels: u32| pixels as f32 * 72.0 / PDF_DPI;
+    let (page_width, page_height) = (points(width), points(height));
+    let content = format!("q {page_width:.2} 0 0 {page_height:.2} 0 0 cm /Poster Do Q");
+
+    let mut pdf: Vec<u8> = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+    let mut offsets = vec![];
+    let mut object = |pdf: &mut Vec<u8>, body: &[u8]| {
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n", offsets.len()).as_bytes());
+        pdf.extend_from_slice(body);
+        pdf.extend_from_slice(b"\nendobj\n");
+    };
+    object(&mut pdf, b"<< /Type /Catalog /Pages 2 0 R >>");
+    object(&mut pdf, b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>");
+    object(
+        &mut pdf,
+        format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {page_width:.2} {page_height:.2}] \
+             /Resources << /XObject << /Poster 5 0 R >> >> /Contents 4 0 R >>"
+        )
+        .as_bytes(),
+    );
+    object(
+        &mut pdf,
+        format!(
+            "<< /Length {} >>\nstream\n{content}\nendstream",
+            content.len()
+        )
+        .as_bytes(),
+    );
+    let mut image = format!(
+        "<< /Type /XObject /Subtype /Image /Width {width} /Height {height} \
+         /ColorSpace /DeviceRGB /BitsPerComponent 8 /Filter /DCTDecode /Length {} >>\nstream\n",
+        jpeg.len()
+    )
+    .into_bytes();
+    image.extend_from_slice(jpeg);
+    image.extend_from_slice(b"\nendstream");
+    object(&mut pdf, &image);
+
+    let xref = pdf.len();
+    let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", offsets.len() + 1);
+    for offset in &offsets {
+        writeln!(table, "{offset:010} 00000 n ").expect("writing to a String can't fail");
+    }
+    write!(
+        table,
+        "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+        offsets.len() + 1
+    )
+    .expect("writing to a String can't fail");
+    pdf.extend_from_slice(table.as_bytes());
+    pdf
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cdrag::Rarity;
+    use image::{Rgb, RgbImage};
+
+    fn cells(n: usize) -> Vec<PosterCell> {
+        (0..n)
+            .map(|i| PosterCell {
+                image: DynamicImage::ImageRgb8(RgbImage::from_pixel(
+                    400,
+                    400,
+                    Rgb([200, 30 * i as u8, 40]),
+                )),
+                focal: FocalPoint::default(),
+                name: format!("Skin number {i} with a rather long name"),
+                badges: overlay::badges(&Rarity::KLegendary, i % 2 == 0),
+            })
+            .collect()
+    }
+
+    fn poster(columns: Option<u32>) -> Poster {
+        Poster {
+            title: "Kai'Sa".into(),
+            subtitle: "Daughter of the Void".into(),
+            asset: SkinAsset::Tile,
+            columns,
+            cell_width: 160,
+            format: PosterFormat::Png,
+        }
+    }
+
+    #[test]
+    fn lays_out_a_labeled_grid() {
+        let canvas = poster(None).compose(&cells(5)).unwrap();
+        // Three columns of 160px cells with 10px gaps, under an 90px header, in two rows of
+        // 160px images and 40px labels
+        assert_eq!(
+            canvas.dimensions(),
+            (3 * 160 + 4 * 10, 90 + 2 * (160 + 40 + 10))
+        );
+        assert_eq!(*canvas.get_pixel(10 + 80, 90 + 80), Rgba([200, 0, 40, 255]));
+        // The sixth cell is empty
+        assert_eq!(*canvas.get_pixel(350 + 80, 300 + 80), BACKGROUND);
+        // Something was written in the first label
+        let label = (10..170).flat_map(|x| (250..290).map(move |y| (x, y)));
+        assert!(
+            label
+                .into_iter()
+                .any(|(x, y)| canvas.get_pixel(x, y)[0] > 0xe0)
+        );
+    }
+
+    #[test]
+    fn refuses_oversized_posters() {
+        let mut wide = poster(Some(60));
+        wide.cell_width = 400;
+        assert!(wide.check(60).is_err());
+        assert!(poster(None).check(0).is_err());
+        assert!(poster(None).check(60).is_ok());
+    }
+
+    #[test]
+    fn pdf_cross_references_its_objects() {
+        let pdf = jpeg_pdf(b"\xff\xd8not really a jpeg\xff\xd9", 300, 150);
+        assert!(pdf.starts_with(b"%PDF-1.4"));
+        // Everything after the embedded JPEG is plain ASCII
+        let tail_start = pdf.windows(9).rposition(|w| w == b"endstream").unwrap();
+        let tail = std::str::from_utf8(&pdf[tail_start..]).unwrap();
+        let startxref: usize = tail
+            .rsplit("startxref\n")
+            .next()
+            .and_then(|rest| rest.lines().next())
+            .unwrap()
+            .parse()
+            .unwrap();
+        let table = std::str::from_utf8(&pdf[startxref..]).unwrap();
+        assert!(table.starts_with("xref\n0 6\n"));
+        // Every object starts where the table says it does
+        for (i, line) in table.lines().skip(3).take(5).enumerate() {
+            let offset: usize = line[..10].parse().unwrap();
+            assert!(pdf[offset..].starts_with(format!("{} 0 obj", i + 1).as_bytes()));
+        }
+        let media_box = b"/MediaBox [0 0 144.00 72.00]";
+        assert!(pdf.windows(media_box.len()).any(|w| w == media_box));
+    }
+}
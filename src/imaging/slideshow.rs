@@ -0,0 +1,359 @@
+use std::{io::Cursor, rc::Rc};
+
+use anyhow::Context;
+use image::{
+    DynamicImage, Frame, RgbaImage,
+    codecs::gif::{GifEncoder, Repeat},
+    imageops::FilterType,
+};
+use serde::Deserialize;
+
+use super::{
+    focal::FocalPoint,
+    wallpaper::{crop_to_fill, crop_to_fill_zoomed},
+};
+
+/// Largest edge of a slideshow. Frames are encoded as they're made, but GIFs this size are
+/// already several megabytes.
+pub const MAX_SLIDESHOW_EDGE: u32 = 1280;
+/// Most frames a slideshow may have, across all of its slides
+pub const MAX_FRAMES: usize = 600;
+/// How long each frame of a transition or pan is shown
+const FRAME_MS: u32 = 100;
+/// How far Ken Burns slides zoom in by their last frame
+const KEN_BURNS_ZOOM: f32 = 1.15;
+/// gif's NeuQuant speed, 1-30. Every frame gets its own palette, so favor speed.
+const GIF_SPEED: i32 = 10;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transition {
+    /// Each splash holds still, then fades into the next
+    #[default]
+    Crossfade,
+    /// Each splash slowly zooms in on its focal point, then fades into the next
+    KenBurns,
+}
+
+/// Animated WebP isn't offered: the image crate only encodes single WebP frames, so asking
+/// for `webp` is a bad request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnimationFormat {
+    #[default]
+    Gif,
+    /// Animated PNG: full color, but larger than the GIF
+    Apng,
+}
+
+impl AnimationFormat {
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            AnimationFormat::Gif => "image/gif",
+            AnimationFormat::Apng => "image/apng",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            AnimationFormat::Gif => "gif",
+            AnimationFormat::Apng => "png",
+        }
+    }
+}
+
+/// How to animate a series of splashes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Slideshow {
+    pub width: u32,
+    pub height: u32,
+    pub transition: Transition,
+    pub format: AnimationFormat,
+    /// How long each splash is shown before the transition to the next starts
+    pub hold_ms: u32,
+    pub fade_ms: u32,
+}
+
+impl Default for Slideshow {
+    fn default() -> Self {
+        Slideshow {
+            width: 640,
+            height: 360,
+            transition: Transition::default(),
+            format: AnimationFormat::default(),
+            hold_ms: 2000,
+            fade_ms: 500,
+        }
+    }
+}
+
+/// One splash of a slideshow
+pub struct Slide {
+    pub image: DynamicImage,
+    pub focal: FocalPoint,
+}
+
+impl Slideshow {
+    /// Why a slideshow of `slides` splashes can't be made, if it can't
+    pub fn check(&self, slides: usize) -> Result<(), String> {
+        let edges = super::MIN_EDGE..=MAX_SLIDESHOW_EDGE;
+        if !edges.contains(&self.width) || !edges.contains(&self.height) {
+            return Err(format!(
+                "slideshows must be between {} and {MAX_SLIDESHOW_EDGE} pixels on each side",
+                super::MIN_EDGE
+            ));
+        }
+        if slides == 0 {
+            return Err("there are no downloaded splashes to show".into());
+        }
+        let frames = self.frame_count(slides);
+        if frames > MAX_FRAMES {
+            return Err(format!(
+                "that would take {frames} frames, more than the {MAX_FRAMES} allowed; try shorter \
+                 holds and fades, or crossfades"
+            ));
+        }
+        Ok(())
+    }
+
+    fn hold_frames(&self) -> usize {
+        match self.transition {
+            Transition::Crossfade => 1,
+            Transition::KenBurns => (self.hold_ms / FRAME_MS).max(1) as usize,
+        }
+    }
+
+    fn fade_frames(&self) -> usize {
+        (self.fade_ms / FRAME_MS) as usize
+    }
+
+    fn frames_per_slide(&self) -> usize {
+        self.hold_frames() + self.fade_frames()
+    }
+
+    /// How many frames a slideshow of `slides` splashes has. A lone splash has nothing to
+    /// fade into.
+    fn frame_count(&self, slides: usize) -> usize {
+        match slides {
+            1 => self.hold_frames(),
+            _ => slides * self.frames_per_slide(),
+        }
+    }
+
+    /// Every frame of the slideshow with how long it's shown, looping from the last slide
+    /// back into the first. Frames are made as they're asked for, so only the one being
+    /// encoded is in memory.
+    fn frames<'a>(&'a self, slides: &[Slide]) -> impl Iterator<Item = (RgbaImage, u32)> + 'a {
+        let (hold, fade) = (self.hold_frames(), self.fade_frames());
+        // Pans crop into the splash, so keep enough of it around to stay sharp at full zoom
+        let margin = match self.transition {
+            Transition::Crossfade => 1.0,
+            Transition::KenBurns => KEN_BURNS_ZOOM,
+        };
+        let sources: Vec<DynamicImage> = slides
+            .iter()
+            .map(|slide| {
+                crop_to_fill(
+                    &slide.image,
+                    (self.width as f32 * margin).round() as u32,
+                    (self.height as f32 * margin).round() as u32,
+                    slide.focal,
+                )
+            })
+            .collect();
+        // Where each slide's focal point ended up in its source
+        let focals: Vec<FocalPoint> = match self.transition {
+            Transition::Crossfade => vec![FocalPoint::default(); slides.len()],
+            Transition::KenBurns => slides
+                .iter()
+                .map(|slide| cropped_focal(&slide.image, self.width, self.height, slide.focal))
+                .collect(),
+        };
+        let count = slides.len();
+        let total = hold + fade;
+        let view = Rc::new(move |i: usize, progress: f32| {
+            let zoom = match self.transition {
+                Transition::Crossfade => 1.0,
+                Transition::KenBurns => 1.0 + (KEN_BURNS_ZOOM - 1.0) * progress,
+            };
+            crop_to_fill_zoomed(&sources[i], self.width, self.height, focals[i], zoom).to_rgba8()
+        });
+        let hold_ms = match self.transition {
+            Transition::Crossfade => self.hold_ms,
+            Transition::KenBurns => FRAME_MS,
+        };
+
+        (0..count).flat_map(move |i| {
+            let next = (i + 1) % count;
+            let fades = if count == 1 { 0 } else { fade };
+            let (holding, fading) = (view.clone(), view.clone());
+            let holds =
+                (0..hold).map(move |step| (holding(i, step as f32 / total as f32), hold_ms));
+            let fades = (0..fades).map(move |step| {
+                let mix = (step + 1) as f32 / (fade + 1) as f32;
+                let from = fading(i, (hold + step) as f32 / total as f32);
+                let to = fading(next, 0.0);
+                (crossfade(&from, &to, mix), FRAME_MS)
+            });
+            holds.chain(fades)
+        })
+    }
+
+    /// Renders and encodes the slideshow, looping forever
+    pub fn render(&self, slides: &[Slide]) -> anyhow::Result<Vec<u8>> {
+        self.check(slides.len()).map_err(anyhow::Error::msg)?;
+        let mut bytes = Vec::new();
+        match self.format {
+            AnimationFormat::Gif => {
+                let mut encoder = GifEncoder::new_with_speed(&mut bytes, GIF_SPEED);
+                encoder.set_repeat(Repeat::Infinite)?;
+                encoder.encode_frames(self.frames(slides).map(|(image, ms)| {
+                    Frame::from_parts(image, 0, 0, image::Delay::from_numer_denom_ms(ms, 1))
+                }))?;
+            }
+            AnimationFormat::Apng => {
+                let mut encoder =
+                    png::Encoder::new(Cursor::new(&mut bytes), self.width, self.height);
+                encoder.set_color(png::ColorType::Rgba);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder.set_animated(self.frame_count(slides.len()) as u32, 0)?;
+                let mut writer = encoder.write_header()?;
+                for (image, ms) in self.frames(slides) {
+                    writer.set_frame_delay(ms.min(u16::MAX as u32) as u16, 1000)?;
+                    writer.write_image_data(image.as_raw())?;
+                }
+                writer.finish().context("couldn't finish the APNG")?;
+            }
+        }
+        Ok(bytes)
+    }
+}
+
+/// Where the focal point lands within [`crop_to_fill`]'s crop of the image
+fn cropped_focal(image: &DynamicImage, width: u32, height: u32, focal: FocalPoint) -> FocalPoint {
+    let (image_width, image_height) = (image.width() as f32, image.height() as f32);
+    let target = width as f32 / height as f32;
+    let (crop_width, crop_height) = if image_width / image_height > target {
+        (image_height * target, image_height)
+    } else {
+        (image_width, image_width / target)
+    };
+    let left = (focal.x * image_width - crop_width / 2.0).clamp(0.0, image_width - crop_width);
+    let top = (focal.y * image_height - crop_height / 2.0).clamp(0.0, image_height - crop_height);
+    FocalPoint {
+        x: ((focal.x * image_width - left) / crop_width).clamp(0.0, 1.0),
+        y: ((focal.y * image_height - top) / crop_height).clamp(0.0, 1.0),
+    }
+}
+
+/// `from` blended toward `to` by `mix`, from 0 (all `from`) to 1 (all `to`)
+fn crossfade(from: &RgbaImage, to: &RgbaImage, mix: f32) -> RgbaImage {
+    let mut blended = from.clone();
+    for (out, other) in blended.pixels_mut().zip(to.pixels()) {
+        for i in 0..4 {
+            out[i] = (out[i] as f32 * (1.0 - mix) + other[i] as f32 * mix).round() as u8;
+        }
+    }
+    blended
+}
+
+/// Shrinks a splash so it's no bigger than a slideshow needs, which makes every frame cheaper
+pub fn shrink(image: DynamicImage, slideshow: &Slideshow) -> DynamicImage {
+    let (width, height) = (
+        (slideshow.width as f32 * KEN_BURNS_ZOOM * 1.5) as u32,
+        (slideshow.height as f32 * KEN_BURNS_ZOOM * 1.5) as u32,
+    );
+    if image.width() <= width || image.height() <= height {
+        return image;
+    }
+    let scale = f32::max(
+        width as f32 / image.width() as f32,
+        height as f32 / image.height() as f32,
+    );
+    image.resize_exact(
+        (image.width() as f32 * scale).round() as u32,
+        (image.height() as f32 * scale).round() as u32,
+        FilterType::Triangle,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use image::{AnimationDecoder, Rgb, RgbImage, codecs::gif::GifDecoder};
+    use rstest::*;
+
+    fn slides(colors: &[[u8; 3]]) -> Vec<Slide> {
+        colors
+            .iter()
+            .map(|color| Slide {
+                image: DynamicImage::ImageRgb8(RgbImage::from_pixel(320, 180, Rgb(*color))),
+                focal: FocalPoint::default(),
+            })
+            .collect()
+    }
+
+    #[rstest]
+    #[case(Transition::Crossfade, 2 * (1 + 5))]
+    #[case(Transition::KenBurns, 2 * (20 + 5))]
+    fn gif_frames_loop_through_the_slides(#[case] transition: Transition, #[case] frames: usize) {
+        let slideshow = Slideshow {
+            width: 160,
+            height: 90,
+            transition,
+            ..Default::default()
+        };
+        let bytes = slideshow
+            .render(&slides(&[[255, 0, 0], [0, 0, 255]]))
+            .unwrap();
+        let decoded: Vec<_> = GifDecoder::new(Cursor::new(bytes))
+            .unwrap()
+            .into_frames()
+            .collect_frames()
+            .unwrap();
+        assert_eq!(decoded.len(), frames);
+        let first = decoded[0].buffer().get_pixel(80, 45);
+        assert!(first[0] > 200 && first[2] < 50, "{first:?}");
+        // Halfway through the fade into the second slide
+        let fading = decoded[frames / 2 - 3].buffer().get_pixel(80, 45);
+        assert!(fading[0] > 50 && fading[2] > 50, "{fading:?}");
+    }
+
+    #[test]
+    fn apng_declares_its_frames() {
+        let slideshow = Slideshow {
+            width: 64,
+            height: 64,
+            format: AnimationFormat::Apng,
+            ..Default::default()
+        };
+        let bytes = slideshow.render(&slides(&[[0, 255, 0]; 3])).unwrap();
+        let decoder = png::Decoder::new(Cursor::new(bytes)).read_info().unwrap();
+        assert_eq!(decoder.info().animation_control.unwrap().num_frames, 3 * 6);
+    }
+
+    #[test]
+    fn a_lone_splash_only_holds() {
+        let slideshow = Slideshow {
+            width: 64,
+            height: 64,
+            format: AnimationFormat::Apng,
+            ..Default::default()
+        };
+        let bytes = slideshow.render(&slides(&[[0, 255, 0]])).unwrap();
+        let decoder = png::Decoder::new(Cursor::new(bytes)).read_info().unwrap();
+        assert_eq!(decoder.info().animation_control.unwrap().num_frames, 1);
+    }
+
+    #[test]
+    fn long_slideshows_are_refused() {
+        let slideshow = Slideshow {
+            transition: Transition::KenBurns,
+            hold_ms: 10_000,
+            ..Default::default()
+        };
+        assert!(slideshow.check(5).is_ok());
+        assert!(slideshow.check(6).is_err());
+        assert!(slideshow.check(0).is_err());
+    }
+}
@@ -0,0 +1,124 @@
+use image::{DynamicImage, imageops::FilterType};
+use serde::Deserialize;
+
+use super::focal::FocalPoint;
+
+/// Common screen shapes, each with a size to use when only the shape is asked for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Preset {
+    #[serde(rename = "16:9", alias = "desktop")]
+    Desktop,
+    #[serde(rename = "21:9", alias = "ultrawide")]
+    Ultrawide,
+    #[serde(rename = "9:19.5", alias = "phone")]
+    Phone,
+}
+
+impl Preset {
+    pub fn aspect_ratio(self) -> f32 {
+        match self {
+            Preset::Desktop => 16.0 / 9.0,
+            Preset::Ultrawide => 21.0 / 9.0,
+            Preset::Phone => 9.0 / 19.5,
+        }
+    }
+
+    pub fn default_size(self) -> (u32, u32) {
+        match self {
+            Preset::Desktop => (2560, 1440),
+            Preset::Ultrawide => (3440, 1440),
+            Preset::Phone => (1170, 2535),
+        }
+    }
+
+    /// Fills in whichever of `width` and `height` is missing from the preset's aspect ratio
+    pub fn size(self, width: Option<u32>, height: Option<u32>) -> (u32, u32) {
+        let ratio = self.aspect_ratio();
+        match (width, height) {
+            (Some(width), Some(height)) => (width, height),
+            (Some(width), None) => (width, (width as f32 / ratio).round() as u32),
+            (None, Some(height)) => ((height as f32 * ratio).round() as u32, height),
+            (None, None) => self.default_size(),
+        }
+    }
+}
+
+/// Crops the largest `width`:`height` region of the image that keeps the focal point as close
+/// to its center as the image's edges allow, then scales it to exactly `width`x`height`.
+pub fn crop_to_fill(
+    image: &DynamicImage,
+    width: u32,
+    height: u32,
+    focal: FocalPoint,
+) -> DynamicImage {
+    crop_to_fill_zoomed(image, width, height, focal, 1.0)
+}
+
+/// Like [`crop_to_fill`], but crops a region `zoom` times smaller, closing in on the focal point
+pub fn crop_to_fill_zoomed(
+    image: &DynamicImage,
+    width: u32,
+    height: u32,
+    focal: FocalPoint,
+    zoom: f32,
+) -> DynamicImage {
+    let (image_width, image_height) = (image.width() as f32, image.height() as f32);
+    let target = width as f32 / height as f32;
+    let (crop_width, crop_height) = if image_width / image_height > target {
+        (image_height * target, image_height)
+    } else {
+        (image_width, image_width / target)
+    };
+    let zoom = zoom.max(1.0);
+    let (crop_width, crop_height) = (crop_width / zoom, crop_height / zoom);
+    let left = (focal.x.clamp(0.0, 1.0) * image_width - crop_width / 2.0)
+        .clamp(0.0, image_width - crop_width);
+    let top = (focal.y.clamp(0.0, 1.0) * image_height - crop_height / 2.0)
+        .clamp(0.0, image_height - crop_height);
+    image
+        .crop_imm(
+            left.round() as u32,
+            top.round() as u32,
+            (crop_width.round() as u32).max(1),
+            (crop_height.round() as u32).max(1),
+        )
+        .resize_exact(width, height, FilterType::Lanczos3)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use image::{Rgb, RgbImage};
+    use rstest::*;
+
+    #[rstest]
+    #[case(Preset::Desktop, None, None, (2560, 1440))]
+    #[case(Preset::Phone, Some(1080), None, (1080, 2340))]
+    #[case(Preset::Ultrawide, None, Some(1080), (2520, 1080))]
+    #[case(Preset::Desktop, Some(100), Some(100), (100, 100))]
+    fn preset_sizes(
+        #[case] preset: Preset,
+        #[case] width: Option<u32>,
+        #[case] height: Option<u32>,
+        #[case] expected: (u32, u32),
+    ) {
+        assert_eq!(preset.size(width, height), expected);
+    }
+
+    #[test]
+    fn focal_point_picks_the_crop() {
+        // Left half red, right half blue
+        let image = DynamicImage::ImageRgb8(RgbImage::from_fn(200, 100, |x, _| {
+            if x < 100 {
+                Rgb([255, 0, 0])
+            } else {
+                Rgb([0, 0, 255])
+            }
+        }));
+        let left = crop_to_fill(&image, 50, 100, FocalPoint { x: 0.1, y: 0.5 }).to_rgb8();
+        let right = crop_to_fill(&image, 50, 100, FocalPoint { x: 0.9, y: 0.5 }).to_rgb8();
+        assert_eq!(*left.get_pixel(25, 50), Rgb([255, 0, 0]));
+        assert_eq!(*right.get_pixel(25, 50), Rgb([0, 0, 255]));
+        assert_eq!(left.dimensions(), (50, 100));
+    }
+}
@@ -1,2 +1,8 @@
 pub mod app;
 pub mod cdrag;
+pub mod cli;
+pub mod config;
+pub mod db;
+pub mod imaging;
+pub mod metrics;
+pub mod tls;
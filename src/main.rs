@@ -4,75 +4,197 @@ use axum::{
     Router,
     extract::{MatchedPath, Query},
     http::Request,
+    middleware,
     response::{Html, IntoResponse},
     routing::get,
 };
+use clap::Parser;
 use std::collections::HashMap;
-use tower_http::{services::ServeDir, trace::TraceLayer};
+use tokio_util::sync::CancellationToken;
+use tower_http::{
+    compression::CompressionLayer,
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    services::ServeDir,
+    trace::TraceLayer,
+};
 use tracing::info_span;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-use fourth_shot::app::{AppError, AppState, champions};
+use fourth_shot::{
+    app::{
+        AppError, AppState, admin, api, assets, caching, champions, collections, creations,
+        derived, graphql,
+        health::{self, Readiness},
+        home, metrics,
+        mount::{self, Mount},
+        posters, request_id, skins, slideshows, wallpapers,
+    },
+    cli::{Cli, Command},
+    config::{Config, LogFormat},
+    tls,
+};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| {
-                format!(
-                    "{}=debug,tower_http=debug,axum::rejection=trace",
-                    env!("CARGO_CRATE_NAME")
-                )
-                .into()
-            }),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    let cli = Cli::parse();
+    let config = cli.overrides.config()?;
+    let registry =
+        tracing_subscriber::registry().with(tracing_subscriber::EnvFilter::new(&config.log));
+    match config.log_format {
+        LogFormat::Text => registry.with(tracing_subscriber::fmt::layer()).init(),
+        LogFormat::Json => registry
+            .with(tracing_subscriber::fmt::layer().json().flatten_event(true))
+            .init(),
+    }
 
-    let app_state = AppState::new().await?;
+    match cli.command.unwrap_or_default() {
+        Command::Serve => serve(config).await,
+        command => command.run(&config).await,
+    }
+}
+
+async fn serve(config: Config) -> anyhow::Result<()> {
+    let tls = match &config.tls {
+        Some(tls) => Some((tls.clone(), tls::load(tls).await?)),
+        None => None,
+    };
+    let addr = config.addr();
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to listen on {addr}"))?;
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    tracing::debug!(
+        "listening on {scheme}://{}{}/",
+        listener.local_addr().unwrap(),
+        config.prefix()
+    );
 
-    for champ in app_state.cdrag.champions.values() {
-        for skin in &champ.skins {
-            app_state
-                .cdrag
-                .download_skin_asset(&skin, &fourth_shot::cdrag::SkinAsset::UncenteredSplash)
-                .await?;
+    let readiness = Readiness::default();
+    let shutdown = CancellationToken::new();
+    let startup = tokio::spawn(start(config, readiness.clone(), shutdown.clone()));
+    let app = health::router(readiness.clone())
+        .merge(metrics::router(readiness))
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid));
+    match tls {
+        None => axum::serve(listener, app)
+            .with_graceful_shutdown(shutdown_signal(shutdown.clone()))
+            .await
+            .with_context(|| "Failed to server the app")?,
+        Some((tls, rustls)) => {
+            tokio::spawn(tls::Reloader::new(tls, rustls.clone()).run(shutdown.clone()));
+            let handle = axum_server::Handle::new();
+            tokio::spawn({
+                let (handle, shutdown) = (handle.clone(), shutdown.clone());
+                async move {
+                    shutdown_signal(shutdown).await;
+                    handle.graceful_shutdown(None);
+                }
+            });
+            axum_server::from_tcp_rustls(listener.into_std()?, rustls)
+                .handle(handle)
+                .serve(app.into_make_service())
+                .await
+                .with_context(|| "Failed to serve the app over TLS")?;
         }
     }
+    shutdown.cancel();
+    startup.await?
+}
+
+/// Syncs, then hands the app to `readiness`. Shutting down first cancels the sync and its
+/// downloads; a failed sync shuts the server down.
+async fn start(
+    config: Config,
+    readiness: Readiness,
+    shutdown: CancellationToken,
+) -> anyhow::Result<()> {
+    let app_state = tokio::select! {
+        app_state = AppState::new(config) => app_state,
+        () = shutdown.cancelled() => {
+            tracing::info!("shut down before the sync finished");
+            return Ok(());
+        }
+    };
+    let app_state = match app_state {
+        Ok(app_state) => app_state,
+        Err(err) => {
+            shutdown.cancel();
+            return Err(err);
+        }
+    };
+    readiness.ready(app_state.clone(), app(app_state));
+    tracing::info!("ready");
+    Ok(())
+}
 
+fn app(app_state: AppState) -> Router {
+    let mount = Mount::from(app_state.config.as_ref());
     let app = Router::new()
-        .nest_service("/assets", ServeDir::new("assets"))
-        .nest_service(
-            "/cdrag-assets",
-            ServeDir::new(app_state.cdrag.data_dir.clone()),
+        .nest(
+            "/assets",
+            Router::new()
+                .fallback_service(ServeDir::new("assets"))
+                .layer(middleware::from_fn(caching::static_assets)),
         )
-        .route("/", get(hello))
         .route("/hello", get(say_hello))
+        .merge(home::router())
+        .merge(admin::router(app_state.clone()))
         .merge(champions::router(app_state.clone()))
+        .merge(collections::router(app_state.clone()))
+        .merge(creations::router(app_state.clone()))
+        .merge(derived::router(app_state.clone()))
+        .merge(graphql::router(app_state.clone()))
+        .merge(posters::router(app_state.clone()))
+        .merge(skins::router(app_state.clone()))
+        .merge(slideshows::router(app_state.clone()))
+        .merge(api::router(app_state.clone()))
+        .merge(assets::router(app_state.clone()))
+        .merge(wallpapers::router(app_state.clone()))
         .with_state(app_state)
+        .layer(CompressionLayer::new())
+        .layer(middleware::from_fn(metrics::track_requests))
+        .layer(middleware::from_fn(request_id::scope))
         .layer(
             TraceLayer::new_for_http().make_span_with(|request: &Request<_>| {
                 let matched_path = request
                     .extensions()
                     .get::<MatchedPath>()
                     .map(MatchedPath::as_str);
+                let request_id = request
+                    .headers()
+                    .get("x-request-id")
+                    .and_then(|id| id.to_str().ok());
                 info_span!(
                     "http_request",
                         method = ?request.method(),
                         matched_path,
-                        some_other_field = tracing::field::Empty,
+                        request_id,
                 )
             }),
         );
+    mount::router(mount, app)
+}
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3000")
-        .await
-        .with_context(|| "Failed to open a TCP connection")?;
-    tracing::debug!("listening on {}", listener.local_addr().unwrap());
-    axum::serve(listener, app)
-        .await
-        .with_context(|| "Failed to server the app")?;
-    Ok(())
+/// Resolves on Ctrl-C, SIGTERM or a cancelled `shutdown`, which it then cancels so the sync
+/// stops too. In-flight requests are drained before the server exits.
+async fn shutdown_signal(shutdown: CancellationToken) {
+    let terminate = async {
+        #[cfg(unix)]
+        if let Ok(mut sigterm) =
+            tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+        {
+            sigterm.recv().await;
+            return;
+        }
+        std::future::pending::<()>().await
+    };
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => tracing::info!("interrupted, shutting down"),
+        () = terminate => tracing::info!("terminated, shutting down"),
+        () = shutdown.cancelled() => {}
+    }
+    shutdown.cancel();
 }
 
 async fn say_hello(
@@ -91,11 +213,3 @@ async fn say_hello(
 struct HelloTemplate {
     name: String,
 }
-
-#[derive(Template)]
-#[template(path = "home.html")]
-struct IndexTemplate {}
-
-async fn hello() -> Result<impl IntoResponse, AppError> {
-    Ok(Html(IndexTemplate {}.render()?))
-}
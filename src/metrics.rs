@@ -0,0 +1,172 @@
+use std::sync::LazyLock;
+
+use prometheus_client::{
+    encoding::{EncodeLabelSet, EncodeLabelValue},
+    metrics::{
+        counter::Counter,
+        family::Family,
+        gauge::Gauge,
+        histogram::{Histogram, exponential_buckets},
+    },
+    registry::Registry,
+};
+
+/// The process's metrics, served at `/metrics`. Like tracing, they're global so anything can
+/// record to them without having them passed in.
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct RequestLabels {
+    pub method: String,
+    /// The route, e.g. `/champions/{key}`, so ids don't each get a series
+    pub path: String,
+    pub status: u16,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct RouteLabels {
+    pub method: String,
+    pub path: String,
+}
+
+/// What was fetched from CommunityDragon
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, EncodeLabelValue)]
+pub enum Fetch {
+    Plugins,
+    ChampionSummary,
+    Champion,
+    SkinLines,
+    Asset,
+    /// A HEAD request for an asset's size
+    AssetSize,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct FetchLabels {
+    pub kind: Fetch,
+}
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, EncodeLabelValue)]
+pub enum Lookup {
+    Hit,
+    Miss,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct LookupLabels {
+    pub result: Lookup,
+}
+
+#[derive(Debug)]
+pub struct Metrics {
+    registry: Registry,
+    pub requests: Family<RequestLabels, Counter>,
+    pub request_duration: Family<RouteLabels, Histogram, fn() -> Histogram>,
+    pub fetches: Family<FetchLabels, Counter>,
+    pub fetch_errors: Family<FetchLabels, Counter>,
+    pub fetched_bytes: Family<FetchLabels, Counter>,
+    /// Requests for `/cdrag-assets` that found the file on disk, or didn't
+    pub asset_lookups: Family<LookupLabels, Counter>,
+    pub refresh_failures: Counter,
+    /// Unix seconds
+    pub last_refresh: Gauge,
+    pub champions: Gauge,
+    pub skins: Gauge,
+    pub skin_lines: Gauge,
+    pub asset_files: Gauge,
+    pub asset_bytes: Gauge,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let mut metrics = Metrics {
+            registry: Registry::with_prefix("fourth_shot"),
+            requests: Family::default(),
+            request_duration: Family::new_with_constructor(|| {
+                Histogram::new(exponential_buckets(0.005, 2.0, 12))
+            }),
+            fetches: Family::default(),
+            fetch_errors: Family::default(),
+            fetched_bytes: Family::default(),
+            asset_lookups: Family::default(),
+            refresh_failures: Counter::default(),
+            last_refresh: Gauge::default(),
+            champions: Gauge::default(),
+            skins: Gauge::default(),
+            skin_lines: Gauge::default(),
+            asset_files: Gauge::default(),
+            asset_bytes: Gauge::default(),
+        };
+        let registry = &mut metrics.registry;
+        registry.register(
+            "http_requests",
+            "HTTP requests by route and status",
+            metrics.requests.clone(),
+        );
+        registry.register(
+            "http_request_duration_seconds",
+            "How long HTTP requests took to answer, by route",
+            metrics.request_duration.clone(),
+        );
+        registry.register(
+            "cdragon_fetches",
+            "Requests made to CommunityDragon",
+            metrics.fetches.clone(),
+        );
+        registry.register(
+            "cdragon_fetch_errors",
+            "Requests to CommunityDragon that failed",
+            metrics.fetch_errors.clone(),
+        );
+        registry.register(
+            "cdragon_fetched_bytes",
+            "Bytes downloaded from CommunityDragon",
+            metrics.fetched_bytes.clone(),
+        );
+        registry.register(
+            "asset_lookups",
+            "Requests for downloaded assets, by whether they were on disk",
+            metrics.asset_lookups.clone(),
+        );
+        registry.register(
+            "refresh_failures",
+            "Failed refreshes of the CommunityDragon data",
+            metrics.refresh_failures.clone(),
+        );
+        registry.register(
+            "last_refresh_timestamp_seconds",
+            "When the CommunityDragon data was last refreshed",
+            metrics.last_refresh.clone(),
+        );
+        registry.register(
+            "champions",
+            "Champions in the data",
+            metrics.champions.clone(),
+        );
+        registry.register("skins", "Skins in the data", metrics.skins.clone());
+        registry.register(
+            "skin_lines",
+            "Skin lines in the data",
+            metrics.skin_lines.clone(),
+        );
+        registry.register(
+            "asset_files",
+            "Downloaded assets in the store",
+            metrics.asset_files.clone(),
+        );
+        registry.register(
+            "asset_bytes",
+            "Bytes of downloaded assets in the store",
+            metrics.asset_bytes.clone(),
+        );
+        metrics
+    }
+
+    /// Every metric, in the OpenMetrics text format
+    pub fn encode(&self) -> String {
+        let mut text = String::new();
+        prometheus_client::encoding::text::encode(&mut text, &self.registry)
+            .expect("writing to a String can't fail");
+        text
+    }
+}
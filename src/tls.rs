@@ -0,0 +1,131 @@
+use std::{fs, path::Path, time::Duration, time::SystemTime};
+
+use anyhow::Context;
+use axum_server::tls_rustls::RustlsConfig;
+use tokio_util::sync::CancellationToken;
+
+use crate::config::Tls;
+
+/// How often the certificate and key are checked for changes. Renewals replace them days
+/// before the old ones expire, so there's no hurry.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Reads the certificate and key, for serving HTTPS
+pub async fn load(tls: &Tls) -> anyhow::Result<RustlsConfig> {
+    // Both ring and aws-lc-rs can end up compiled in, so rustls can't pick one itself
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    RustlsConfig::from_pem_file(&tls.cert, &tls.key)
+        .await
+        .with_context(|| format!("couldn't load the TLS certificate {:?}", tls.cert))
+}
+
+/// Swaps in the certificate and key whenever their files change. New connections get the new
+/// ones; open connections keep theirs.
+#[derive(Debug)]
+pub struct Reloader {
+    tls: Tls,
+    rustls: RustlsConfig,
+    loaded: Option<(SystemTime, SystemTime)>,
+}
+
+impl Reloader {
+    /// Watches the files `rustls` was just loaded from
+    pub fn new(tls: Tls, rustls: RustlsConfig) -> Self {
+        let loaded = modified(&tls);
+        Reloader {
+            tls,
+            rustls,
+            loaded,
+        }
+    }
+
+    /// Reloads if either file changed since the last load, returning whether it did. When the
+    /// new files are unusable, say a certificate written before its key, the old ones stay
+    /// and the next check tries again.
+    pub async fn check(&mut self) -> anyhow::Result<bool> {
+        let current = modified(&self.tls);
+        if current.is_none() || current == self.loaded {
+            return Ok(false);
+        }
+        self.rustls
+            .reload_from_pem_file(&self.tls.cert, &self.tls.key)
+            .await
+            .with_context(|| format!("couldn't reload the TLS certificate {:?}", self.tls.cert))?;
+        self.loaded = current;
+        Ok(true)
+    }
+
+    /// Checks every [`RELOAD_INTERVAL`] until `shutdown`
+    pub async fn run(mut self, shutdown: CancellationToken) {
+        let mut interval = tokio::time::interval(RELOAD_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                () = shutdown.cancelled() => return,
+            }
+            match self.check().await {
+                Ok(true) => tracing::info!(cert = ?self.tls.cert, "reloaded the TLS certificate"),
+                Ok(false) => {}
+                Err(err) => tracing::warn!("{err:#}"),
+            }
+        }
+    }
+}
+
+/// When the certificate and key were last written, following symlinks as Kubernetes secrets
+/// and certbot's `live/` use them
+fn modified(tls: &Tls) -> Option<(SystemTime, SystemTime)> {
+    let time = |path: &Path| fs::metadata(path).and_then(|meta| meta.modified()).ok();
+    Some((time(&tls.cert)?, time(&tls.key)?))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn write_pair(tls: &Tls, name: &str) {
+        let pair = rcgen::generate_simple_self_signed(vec![name.to_string()]).unwrap();
+        fs::write(&tls.cert, pair.cert.pem()).unwrap();
+        fs::write(&tls.key, pair.key_pair.serialize_pem()).unwrap();
+    }
+
+    /// Sets both files' modification time, since writes within a second can share one
+    fn touch(tls: &Tls, secs: u64) {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+        for path in [&tls.cert, &tls.key] {
+            fs::File::options()
+                .append(true)
+                .open(path)
+                .unwrap()
+                .set_modified(time)
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn reloads_changed_certificates() {
+        let dir = tempfile::tempdir().unwrap();
+        let tls = Tls {
+            cert: dir.path().join("cert.pem"),
+            key: dir.path().join("key.pem"),
+        };
+        write_pair(&tls, "localhost");
+        touch(&tls, 1_000);
+        let rustls = load(&tls).await.unwrap();
+        let mut reloader = Reloader::new(tls.clone(), rustls.clone());
+        assert!(!reloader.check().await.unwrap());
+
+        let before = rustls.get_inner();
+        write_pair(&tls, "example.com");
+        touch(&tls, 2_000);
+        assert!(reloader.check().await.unwrap());
+        assert!(!std::sync::Arc::ptr_eq(&before, &rustls.get_inner()));
+        assert!(!reloader.check().await.unwrap());
+
+        fs::write(&tls.key, "not a key").unwrap();
+        touch(&tls, 3_000);
+        assert!(reloader.check().await.is_err());
+        assert!(reloader.check().await.is_err(), "should keep retrying");
+    }
+}